@@ -0,0 +1,251 @@
+use crate::error::AppError;
+use crate::helper::get_config_directory;
+use crate::models::{DurationField, NotifyVia, PomoAlert};
+use chrono_tz::Tz;
+use clap::ValueEnum;
+use std::fs;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+const CONFIG_FILE_NAME: &str = "/config";
+
+/// User settings read from `~/.tasklog/config`, one `key = value` pair per line. Lines starting
+/// with `#` are comments.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    /// Time zone whose midnights delimit days for all-day tasks and `ls --days`
+    pub timezone: Option<Tz>,
+    /// How `notify` delivers due reminders
+    pub notify: Option<NotifyVia>,
+    /// Program and arguments run by the `command` channel, the reminder summary and body are
+    /// appended as two more arguments (e.g. `notify-send -a "Task Log"`). Quote an argument
+    /// that contains spaces, it is not run through a shell.
+    pub notify_command: Option<String>,
+    /// File the `file` channel appends reminders to
+    pub notify_file: Option<String>,
+    /// How a finished pomodoro gets your attention, bell and flash when unset
+    pub pomo_alert: Option<Vec<PomoAlert>>,
+    /// Program and arguments run by the `command` pomodoro alert, the session summary and body
+    /// are appended as two more arguments (e.g. `paplay "/home/me/complete sound.oga"` or
+    /// `notify-send`). Arguments are quoted as in `notify_command`.
+    pub pomo_alert_command: Option<String>,
+    /// Length of the breaks started from the time's up screen
+    pub pomo_break: Option<DurationField>,
+    /// How many daily snapshots of the database to keep in `~/.tasklog/backups`, none are taken
+    /// when unset or 0
+    pub daily_snapshots: Option<usize>,
+    /// Git repository `sync` pulls from and pushes to, a url or a local path
+    pub sync_remote: Option<String>,
+    /// Where `sync` keeps its own clone, `~/.tasklog/sync` when unset
+    pub sync_dir: Option<String>,
+}
+
+impl Config {
+    /// The settings of the config file, the defaults when there is none. A file that cannot be
+    /// read is an IO error, a setting that cannot be a parse error.
+    pub fn load() -> Result<Config, AppError> {
+        let path = get_config_directory()? + CONFIG_FILE_NAME;
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => Config::parse(&contents).map_err(|e| e.context(path)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(err) => Err(AppError::from(err).context(format!("Could not read {}", path))),
+        }
+    }
+
+    pub fn parse(contents: &str) -> Result<Config, AppError> {
+        let mut config = Config::default();
+
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line.split_once('=').ok_or_else(|| {
+                AppError::Parse(format!("line {}: expected `key = value`", i + 1))
+            })?;
+            let value = value.trim();
+            let value = match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+                Some(unquoted) => unquoted,
+                None => value,
+            };
+
+            match key.trim() {
+                "timezone" => {
+                    let tz = value.parse::<Tz>().map_err(|_| {
+                        AppError::Parse(format!("line {}: unknown time zone '{}'", i + 1, value))
+                    })?;
+                    config.timezone = Some(tz);
+                }
+                "notify" => {
+                    let via = NotifyVia::from_str(value, true).map_err(|_| {
+                        AppError::Parse(format!(
+                            "line {}: unknown notify channel '{}' (bell, command or file)",
+                            i + 1,
+                            value
+                        ))
+                    })?;
+                    config.notify = Some(via);
+                }
+                "notify_command" => config.notify_command = Some(value.to_string()),
+                "notify_file" => config.notify_file = Some(value.to_string()),
+                "pomo_alert" => {
+                    let mut alerts = Vec::new();
+                    for alert in value.split(',').map(str::trim).filter(|a| !a.is_empty()) {
+                        if alert == "none" {
+                            continue;
+                        }
+                        alerts.push(PomoAlert::from_str(alert, true).map_err(|_| {
+                            AppError::Parse(format!(
+                                "line {}: unknown pomodoro alert '{}' (bell, flash, command or none)",
+                                i + 1,
+                                alert
+                            ))
+                        })?);
+                    }
+                    config.pomo_alert = Some(alerts);
+                }
+                "pomo_alert_command" => config.pomo_alert_command = Some(value.to_string()),
+                "pomo_break" => {
+                    let duration = DurationField::from_str(value)
+                        .map_err(|e| AppError::Parse(format!("line {}: {}", i + 1, e)))?;
+                    config.pomo_break = Some(duration);
+                }
+                "daily_snapshots" => {
+                    let keep = value.parse::<usize>().map_err(|_| {
+                        AppError::Parse(format!(
+                            "line {}: daily_snapshots has to be a number of days",
+                            i + 1
+                        ))
+                    })?;
+                    config.daily_snapshots = Some(keep);
+                }
+                "sync_remote" => config.sync_remote = Some(value.to_string()),
+                "sync_dir" => config.sync_dir = Some(value.to_string()),
+                other => {
+                    return Err(AppError::Parse(format!(
+                        "line {}: unknown setting '{}'",
+                        i + 1,
+                        other
+                    )))
+                }
+            }
+        }
+
+        Ok(config)
+    }
+
+    pub fn pomo_alerts(&self) -> Vec<PomoAlert> {
+        self.pomo_alert
+            .clone()
+            .unwrap_or_else(|| vec![PomoAlert::Bell, PomoAlert::Flash])
+    }
+
+    pub fn pomo_break(&self) -> DurationField {
+        self.pomo_break
+            .clone()
+            .unwrap_or_else(|| DurationField(chrono::Duration::minutes(5)))
+    }
+}
+
+/// The configured home time zone, falling back to the system time zone and then UTC. Loaded once
+/// per process; an unreadable config is reported on stderr and ignored.
+pub fn home_timezone() -> Tz {
+    static HOME_TIMEZONE: OnceLock<Tz> = OnceLock::new();
+
+    *HOME_TIMEZONE.get_or_init(|| {
+        let configured = match Config::load() {
+            Ok(config) => config.timezone,
+            Err(err) => {
+                eprintln!("ignoring config: {}", err);
+                None
+            }
+        };
+
+        configured
+            .or_else(|| {
+                iana_time_zone::get_timezone()
+                    .ok()
+                    .and_then(|name| name.parse::<Tz>().ok())
+            })
+            .unwrap_or(Tz::UTC)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config = Config::parse("# where I live\n\n  timezone = \"Europe/Berlin\"  \n").unwrap();
+        assert_eq!(config.timezone, Some(chrono_tz::Europe::Berlin));
+    }
+
+    #[test]
+    fn test_parse_notify_config() {
+        let config = Config::parse(
+            "notify = command\nnotify_command = notify-send -a \"tasklog\"\nnotify_file = \"~/reminders.log\"",
+        )
+        .unwrap();
+        assert_eq!(config.notify, Some(NotifyVia::Command));
+        assert_eq!(
+            config.notify_command.as_deref(),
+            Some("notify-send -a \"tasklog\"")
+        );
+        assert_eq!(config.notify_file.as_deref(), Some("~/reminders.log"));
+        assert!(Config::parse("notify = pigeon").is_err());
+    }
+
+    #[test]
+    fn test_parse_pomo_alert_config() {
+        let config = Config::parse(
+            "pomo_alert = bell, command\npomo_alert_command = paplay done.oga\npomo_break = 10m",
+        )
+        .unwrap();
+        assert_eq!(
+            config.pomo_alerts(),
+            vec![PomoAlert::Bell, PomoAlert::Command]
+        );
+        assert_eq!(
+            config.pomo_alert_command.as_deref(),
+            Some("paplay done.oga")
+        );
+        assert_eq!(config.pomo_break().to_i64(), 600);
+
+        let silent = Config::parse("pomo_alert = none").unwrap();
+        assert!(silent.pomo_alerts().is_empty());
+
+        let defaults = Config::default();
+        assert_eq!(
+            defaults.pomo_alerts(),
+            vec![PomoAlert::Bell, PomoAlert::Flash]
+        );
+        assert_eq!(defaults.pomo_break().to_i64(), 300);
+
+        assert!(Config::parse("pomo_alert = siren").is_err());
+        assert!(Config::parse("pomo_break = soon").is_err());
+    }
+
+    #[test]
+    fn test_parse_daily_snapshots_config() {
+        let config = Config::parse("daily_snapshots = 7").unwrap();
+        assert_eq!(config.daily_snapshots, Some(7));
+        assert!(Config::parse("daily_snapshots = weekly").is_err());
+
+        let config = Config::parse("sync_remote = git@example.com:me/tasks.git").unwrap();
+        assert_eq!(
+            config.sync_remote.as_deref(),
+            Some("git@example.com:me/tasks.git")
+        );
+    }
+
+    #[test]
+    fn test_parse_config_errors() {
+        assert!(Config::parse("timezone = Mars/Olympus").is_err());
+        assert!(Config::parse("timezone").is_err());
+        assert!(Config::parse("colour = blue").is_err());
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+    }
+}
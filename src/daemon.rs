@@ -0,0 +1,660 @@
+use crate::config::Config;
+use crate::error::AppError;
+use crate::helper::get_config_directory;
+use crate::models::{DurationField, PomoAlert, PomoStatus, PomoTask, PomoType, RecordMeta};
+use crate::notify;
+use crate::store::{Repository, TaskStore};
+use chrono::{DateTime, Duration, Local};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::thread;
+
+const STATE_FILE_NAME: &str = "/pomo.state";
+const SOCKET_FILE_NAME: &str = "/pomo.sock";
+const LOG_FILE_NAME: &str = "/pomo.log";
+
+/// How long the daemon sleeps between checks for requests and the end of the countdown
+const POLL_INTERVAL_MILLIS: u64 = 200;
+/// How many times, POLL_INTERVAL_MILLIS apart, a freshly spawned daemon is given to listen
+const STARTUP_POLLS: u32 = 25;
+
+/// The session counted down by the background daemon. It is written to `~/.tasklog/pomo.state`
+/// on every change, so a session outliving its daemon can be picked up again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionState {
+    pub id: u64,
+    pub pomo_type: PomoType,
+    pub title: String,
+    pub category: Option<String>,
+    pub task_id: Option<u64>,
+    pub duration: DurationField,
+    pub started_at: DateTime<Local>,
+    /// Time counted down before `running_since`
+    pub elapsed: Duration,
+    /// When the countdown last started or resumed, `None` while paused
+    pub running_since: Option<DateTime<Local>>,
+    /// When the state last changed, for a paused session the moment it was paused
+    pub updated_at: DateTime<Local>,
+}
+
+impl SessionState {
+    pub(crate) fn new(pomo_task: &PomoTask) -> SessionState {
+        SessionState {
+            id: pomo_task.id,
+            pomo_type: pomo_task.pomo_type,
+            title: pomo_task.title.clone(),
+            category: pomo_task.category.clone(),
+            task_id: pomo_task.task_id,
+            duration: pomo_task.duration.clone(),
+            started_at: pomo_task.start_time,
+            elapsed: Duration::zero(),
+            running_since: Some(pomo_task.start_time),
+            updated_at: pomo_task.start_time,
+        }
+    }
+
+    pub(crate) fn is_paused(&self) -> bool {
+        self.running_since.is_none()
+    }
+
+    pub fn elapsed_at(&self, now: DateTime<Local>) -> Duration {
+        match self.running_since {
+            Some(since) => self.elapsed + (now - since).max(Duration::zero()),
+            None => self.elapsed,
+        }
+    }
+
+    pub(crate) fn remaining_at(&self, now: DateTime<Local>) -> Duration {
+        (self.duration.0 - self.elapsed_at(now)).max(Duration::zero())
+    }
+
+    /// When the countdown reaches zero, `None` while paused.
+    pub(crate) fn finishes_at(&self) -> Option<DateTime<Local>> {
+        self.running_since
+            .map(|since| since + (self.duration.0 - self.elapsed).max(Duration::zero()))
+    }
+
+    pub(crate) fn pause(&mut self, now: DateTime<Local>) -> Result<(), AppError> {
+        if self.is_paused() {
+            return Err(AppError::Validation(format!(
+                "Session #{} is already paused",
+                self.id
+            )));
+        }
+
+        self.elapsed = self.elapsed_at(now);
+        self.running_since = None;
+        self.updated_at = now;
+        Ok(())
+    }
+
+    pub(crate) fn resume(&mut self, now: DateTime<Local>) -> Result<(), AppError> {
+        if !self.is_paused() {
+            return Err(AppError::Validation(format!(
+                "Session #{} is not paused",
+                self.id
+            )));
+        }
+
+        self.running_since = Some(now);
+        self.updated_at = now;
+        Ok(())
+    }
+
+    /// The pomodoro row of the session, ended at `end_time`.
+    pub(crate) fn to_pomo_task(&self, status: PomoStatus, end_time: DateTime<Local>) -> PomoTask {
+        PomoTask {
+            id: self.id,
+            status,
+            pomo_type: self.pomo_type,
+            title: self.title.clone(),
+            duration: self.duration.clone(),
+            category: self.category.clone(),
+            task_id: self.task_id,
+            elapsed: DurationField(self.elapsed_at(end_time).min(self.duration.0)),
+            start_time: self.started_at,
+            end_time,
+            meta: RecordMeta::default(),
+        }
+    }
+
+    /// One line describing the session and the time left on it.
+    pub fn summary(&self, now: DateTime<Local>) -> String {
+        let remaining = self.remaining_at(now).num_seconds();
+        let category = match &self.category {
+            Some(category) => format!(" [{}]", category),
+            None => String::new(),
+        };
+
+        format!(
+            "{} session #{} {}{}: {:02}:{:02} left{}",
+            String::from(self.pomo_type),
+            self.id,
+            self.title,
+            category,
+            remaining / 60,
+            remaining % 60,
+            if self.is_paused() { " (paused)" } else { "" }
+        )
+    }
+
+    /// The `key = value` lines of the state file.
+    pub(crate) fn serialize(&self) -> String {
+        let mut lines = vec![
+            format!("id = {}", self.id),
+            format!("type = {}", String::from(self.pomo_type)),
+            format!("title = {}", self.title.replace('\n', " ")),
+            format!("duration = {}", self.duration.to_i64()),
+            format!("started_at = {}", self.started_at.to_rfc3339()),
+            format!("elapsed = {}", self.elapsed.num_seconds()),
+            format!("updated_at = {}", self.updated_at.to_rfc3339()),
+        ];
+        if let Some(category) = &self.category {
+            lines.push(format!("category = {}", category.replace('\n', " ")));
+        }
+        if let Some(task_id) = self.task_id {
+            lines.push(format!("task_id = {}", task_id));
+        }
+        if let Some(since) = self.running_since {
+            lines.push(format!("running_since = {}", since.to_rfc3339()));
+        }
+
+        lines.join("\n") + "\n"
+    }
+
+    pub(crate) fn parse(contents: &str) -> Result<SessionState, AppError> {
+        let mut id = None;
+        let mut pomo_type = PomoType::Work;
+        let mut title = None;
+        let mut category = None;
+        let mut task_id = None;
+        let mut duration = None;
+        let mut started_at = None;
+        let mut elapsed = Duration::zero();
+        let mut running_since = None;
+        let mut updated_at = None;
+
+        let date = |value: &str| {
+            DateTime::parse_from_rfc3339(value)
+                .map(|d| d.with_timezone(&Local))
+                .map_err(|e| AppError::Parse(format!("invalid time '{}': {}", value, e)))
+        };
+        let number = |value: &str| {
+            value
+                .parse::<i64>()
+                .map_err(|_| AppError::Parse(format!("invalid number '{}'", value)))
+        };
+
+        for line in contents.lines().filter(|l| !l.trim().is_empty()) {
+            let (key, value) = line.split_once('=').ok_or_else(|| {
+                AppError::Parse(format!("expected `key = value`, got '{}'", line))
+            })?;
+            let value = value.trim();
+
+            match key.trim() {
+                "id" => id = Some(number(value)? as u64),
+                "type" => pomo_type = PomoType::from(value.to_string()),
+                "title" => title = Some(value.to_string()),
+                "category" => category = Some(value.to_string()),
+                "task_id" => task_id = Some(number(value)? as u64),
+                "duration" => duration = Some(DurationField::from_i64(number(value)?)),
+                "started_at" => started_at = Some(date(value)?),
+                "elapsed" => elapsed = Duration::seconds(number(value)?),
+                "running_since" => running_since = Some(date(value)?),
+                "updated_at" => updated_at = Some(date(value)?),
+                other => return Err(AppError::Parse(format!("unknown key '{}'", other))),
+            }
+        }
+
+        let started_at: DateTime<Local> =
+            started_at.ok_or_else(|| AppError::Parse("missing started_at".to_string()))?;
+        Ok(SessionState {
+            id: id.ok_or_else(|| AppError::Parse("missing id".to_string()))?,
+            pomo_type,
+            title: title.ok_or_else(|| AppError::Parse("missing title".to_string()))?,
+            category,
+            task_id,
+            duration: duration.ok_or_else(|| AppError::Parse("missing duration".to_string()))?,
+            started_at,
+            elapsed,
+            running_since,
+            updated_at: updated_at.unwrap_or(started_at),
+        })
+    }
+}
+
+/// The requests the daemon answers on its socket, one per connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DaemonRequest {
+    Status,
+    Pause,
+    Resume,
+    Stop,
+}
+
+impl DaemonRequest {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            DaemonRequest::Status => "status",
+            DaemonRequest::Pause => "pause",
+            DaemonRequest::Resume => "resume",
+            DaemonRequest::Stop => "stop",
+        }
+    }
+
+    pub(crate) fn parse(s: &str) -> Option<DaemonRequest> {
+        match s.trim() {
+            "status" => Some(DaemonRequest::Status),
+            "pause" => Some(DaemonRequest::Pause),
+            "resume" => Some(DaemonRequest::Resume),
+            "stop" => Some(DaemonRequest::Stop),
+            _ => None,
+        }
+    }
+}
+
+fn state_path() -> Result<String, AppError> {
+    Ok(get_config_directory()? + STATE_FILE_NAME)
+}
+
+fn socket_path() -> Result<String, AppError> {
+    Ok(get_config_directory()? + SOCKET_FILE_NAME)
+}
+
+/// The session of the state file, whether or not a daemon is still counting it down. A file
+/// that cannot be read is an IO error, one that does not hold a session a parse error.
+pub fn load_state() -> Result<Option<SessionState>, AppError> {
+    read_state(&state_path()?)
+}
+
+fn read_state(path: &str) -> Result<Option<SessionState>, AppError> {
+    match fs::read_to_string(path) {
+        Ok(contents) => SessionState::parse(&contents)
+            .map(Some)
+            .map_err(|e| AppError::Parse(format!("{}: {}", path, e))),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(AppError::from(err).context(format!("Could not read {}", path))),
+    }
+}
+
+/// Replaces the state file through a rename, so a crash never leaves half of it behind.
+fn save_state(state: &SessionState) -> Result<(), AppError> {
+    let path = state_path()?;
+    let tmp_path = format!("{}.tmp", path);
+
+    fs::write(&tmp_path, state.serialize())
+        .and_then(|_| fs::rename(&tmp_path, &path))
+        .map_err(|e| AppError::from(e).context(format!("Could not write {}", path)))
+}
+
+fn clear_state() -> Result<(), AppError> {
+    for path in [state_path()?, socket_path()?] {
+        match fs::remove_file(&path) {
+            Err(err) if err.kind() != ErrorKind::NotFound => {
+                return Err(AppError::from(err).context(format!("Could not remove {}", path)))
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether a daemon is listening on the socket.
+pub fn is_running() -> Result<bool, AppError> {
+    Ok(UnixStream::connect(socket_path()?).is_ok())
+}
+
+/// Saves the state of a freshly recorded session and spawns the daemon counting it down.
+pub fn start(pomo_task: &PomoTask) -> Result<(), AppError> {
+    save_state(&SessionState::new(pomo_task))?;
+    // Left behind, the state would have `recover` start the session later.
+    spawn().inspect_err(|_| {
+        let _ = clear_state();
+    })
+}
+
+/// Runs `tasklog pomo daemon` in its own process group, so closing or interrupting the terminal
+/// does not take it down, with its errors appended to `~/.tasklog/pomo.log`.
+fn spawn() -> Result<(), AppError> {
+    let exe = std::env::current_exe()?;
+    let log_path = get_config_directory()? + LOG_FILE_NAME;
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)
+        .map_err(|e| AppError::from(e).context(format!("Could not open {}", log_path)))?;
+
+    Command::new(exe)
+        .args(["pomo", "daemon"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(log)
+        .process_group(0)
+        .spawn()
+        .map_err(|e| AppError::from(e).context("Could not start the background session"))?;
+
+    for _ in 0..STARTUP_POLLS {
+        if is_running()? {
+            return Ok(());
+        }
+        thread::sleep(std::time::Duration::from_millis(POLL_INTERVAL_MILLIS));
+    }
+
+    Err(AppError::Io(format!(
+        "The background session did not start, see {}",
+        log_path
+    )))
+}
+
+/// Picks up a session left behind by a daemon that crashed or did not survive a reboot: one whose
+/// time ran out is recorded as finished when it ran out, any other gets a new daemon. Returns what
+/// was done, if anything.
+pub fn recover() -> Result<Option<String>, AppError> {
+    let state = match load_state()? {
+        Some(state) => state,
+        None => return Ok(None),
+    };
+
+    if is_running()? {
+        return Ok(None);
+    }
+
+    match state.finishes_at() {
+        Some(finished_at) if finished_at <= Local::now() => {
+            TaskStore::open_default()?
+                .update_session(&state.to_pomo_task(PomoStatus::Finished, finished_at))?;
+            clear_state()?;
+            Ok(Some(format!(
+                "Recorded session #{} as finished at {}",
+                state.id,
+                finished_at.format("%Y-%m-%d %H:%M")
+            )))
+        }
+        _ => {
+            spawn()?;
+            Ok(Some(format!(
+                "Restarted the background daemon of session #{}",
+                state.id
+            )))
+        }
+    }
+}
+
+/// Sends a request to the daemon and returns the session as it stands after it.
+pub fn send(request: DaemonRequest) -> Result<SessionState, AppError> {
+    let mut stream = UnixStream::connect(socket_path()?).map_err(|_| {
+        AppError::NotFound("No pomodoro session is running in the background".to_string())
+    })?;
+
+    stream
+        .write_all(format!("{}\n", request.as_str()).as_bytes())
+        .and_then(|_| stream.shutdown(std::net::Shutdown::Write))
+        .map_err(|e| AppError::from(e).context("Could not reach the background session"))?;
+
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .map_err(|e| AppError::from(e).context("Could not reach the background session"))?;
+
+    parse_response(&response)
+}
+
+/// A response is `ok` followed by the state file lines of the session, or `error: <message>` when
+/// the daemon turned the request down.
+fn parse_response(response: &str) -> Result<SessionState, AppError> {
+    match response.split_once('\n') {
+        Some(("ok", state)) => SessionState::parse(state),
+        _ => match response.trim().strip_prefix("error: ") {
+            Some(err) => Err(AppError::Validation(err.to_string())),
+            None => Err(AppError::Io(format!(
+                "Unexpected response '{}'",
+                response.trim()
+            ))),
+        },
+    }
+}
+
+/// The daemon itself: counts down the session of the state file, answering requests on the socket
+/// until the time is up or it is stopped, and records the session on the way out.
+pub fn run() -> Result<(), AppError> {
+    let mut state = load_state()?
+        .ok_or_else(|| AppError::NotFound("No session to run in the background".to_string()))?;
+    let config = Config::load()?;
+
+    let socket = socket_path()?;
+    let _ = fs::remove_file(&socket);
+    let listener = UnixListener::bind(&socket)
+        .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+        .map_err(|e| AppError::from(e).context(format!("Could not listen on {}", socket)))?;
+
+    loop {
+        if let Some(finished_at) = state.finishes_at() {
+            if finished_at <= Local::now() {
+                finish(&state, finished_at, &config)?;
+                break;
+            }
+        }
+
+        match listener.accept() {
+            Ok((stream, _)) => {
+                if serve(stream, &mut state)? {
+                    break;
+                }
+            }
+            Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                thread::sleep(std::time::Duration::from_millis(POLL_INTERVAL_MILLIS));
+            }
+            Err(err) => {
+                return Err(AppError::Io(format!(
+                    "Could not accept a connection: {}",
+                    err
+                )))
+            }
+        }
+    }
+
+    clear_state()
+}
+
+/// Answers one request, returns whether the session was stopped.
+fn serve(mut stream: UnixStream, state: &mut SessionState) -> Result<bool, AppError> {
+    let mut request = String::new();
+    let read = stream
+        .set_nonblocking(false)
+        .and_then(|_| stream.set_read_timeout(Some(std::time::Duration::from_secs(1))))
+        .and_then(|_| stream.read_to_string(&mut request));
+    if read.is_err() {
+        return Ok(false);
+    }
+
+    let now = Local::now();
+    let outcome = match DaemonRequest::parse(&request) {
+        Some(DaemonRequest::Status) => Ok(false),
+        Some(DaemonRequest::Pause) => state
+            .pause(now)
+            .and_then(|_| save_state(state))
+            .map(|_| false),
+        Some(DaemonRequest::Resume) => state
+            .resume(now)
+            .and_then(|_| save_state(state))
+            .map(|_| false),
+        Some(DaemonRequest::Stop) => {
+            // Stopping early counts like quitting the foreground screen does.
+            state.elapsed = state.elapsed_at(now);
+            state.running_since = None;
+            state.updated_at = now;
+            TaskStore::open_default()
+                .and_then(|store| {
+                    store.update_session(&state.to_pomo_task(PomoStatus::Paused, now))
+                })
+                .map(|_| true)
+                .map_err(AppError::from)
+        }
+        None => Err(AppError::Validation(format!(
+            "unknown request '{}'",
+            request.trim()
+        ))),
+    };
+
+    let response = match &outcome {
+        Ok(_) => format!("ok\n{}", state.serialize()),
+        Err(err) => format!("error: {}\n", err),
+    };
+    // The client may have given up waiting, the request has been handled either way.
+    let _ = stream.write_all(response.as_bytes());
+
+    Ok(outcome.unwrap_or(false))
+}
+
+/// Records the finished session and runs the `pomo_alert_command` hook when it is configured,
+/// there is no terminal to ring or flash.
+fn finish(
+    state: &SessionState,
+    finished_at: DateTime<Local>,
+    config: &Config,
+) -> Result<(), AppError> {
+    let pomo_task = state.to_pomo_task(PomoStatus::Finished, finished_at);
+    TaskStore::open_default()?.update_session(&pomo_task)?;
+
+    if config.pomo_alerts().contains(&PomoAlert::Command) {
+        if let Some(command) = &config.pomo_alert_command {
+            let (summary, body) = notify::pomo_message(&pomo_task);
+            if let Err(err) = notify::spawn_hook(command, &summary, &body) {
+                eprintln!("{}", err);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(s: &str) -> DateTime<Local> {
+        Local
+            .from_local_datetime(
+                &chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap(),
+            )
+            .unwrap()
+    }
+
+    fn session() -> SessionState {
+        SessionState::new(&PomoTask {
+            id: 9,
+            title: "Write = report".to_string(),
+            duration: DurationField(Duration::minutes(25)),
+            category: Some("work".to_string()),
+            task_id: Some(4),
+            start_time: at("2026-06-15 12:00:00"),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_session_pause_and_resume() {
+        let mut state = session();
+        assert_eq!(state.finishes_at(), Some(at("2026-06-15 12:25:00")));
+        assert_eq!(
+            state.remaining_at(at("2026-06-15 12:10:00")),
+            Duration::minutes(15)
+        );
+
+        state.pause(at("2026-06-15 12:10:00")).unwrap();
+        assert!(state.pause(at("2026-06-15 12:11:00")).is_err());
+        assert_eq!(state.finishes_at(), None);
+        assert_eq!(
+            state.remaining_at(at("2026-06-15 13:00:00")),
+            Duration::minutes(15)
+        );
+        assert!(state
+            .summary(at("2026-06-15 13:00:00"))
+            .ends_with("15:00 left (paused)"));
+
+        state.resume(at("2026-06-15 13:00:00")).unwrap();
+        assert!(state.resume(at("2026-06-15 13:01:00")).is_err());
+        assert_eq!(state.finishes_at(), Some(at("2026-06-15 13:15:00")));
+        assert_eq!(
+            state.remaining_at(at("2026-06-15 14:00:00")),
+            Duration::zero()
+        );
+
+        let pomo = state.to_pomo_task(PomoStatus::Finished, at("2026-06-15 13:15:00"));
+        assert_eq!(pomo.id, 9);
+        assert_eq!(pomo.start_time, at("2026-06-15 12:00:00"));
+        assert_eq!(pomo.duration.to_i64(), 25 * 60);
+        assert_eq!(pomo.elapsed.to_i64(), 25 * 60);
+
+        let mut stopped = session();
+        stopped.pause(at("2026-06-15 12:03:00")).unwrap();
+        let pomo = stopped.to_pomo_task(PomoStatus::Paused, at("2026-06-15 12:30:00"));
+        assert_eq!(pomo.elapsed.to_i64(), 3 * 60);
+    }
+
+    #[test]
+    fn test_session_state_round_trip() {
+        let mut state = session();
+        assert_eq!(
+            SessionState::parse(&state.serialize()).unwrap(),
+            state.clone()
+        );
+
+        state.pause(at("2026-06-15 12:05:00")).unwrap();
+        state.category = None;
+        state.task_id = None;
+        let parsed = SessionState::parse(&state.serialize()).unwrap();
+        assert_eq!(parsed, state);
+        assert_eq!(parsed.title, "Write = report");
+
+        assert!(SessionState::parse("id = 1\ntitle = x").is_err());
+        assert!(SessionState::parse("colour = blue").is_err());
+    }
+
+    #[test]
+    fn test_read_state_errors() {
+        let dir = std::env::temp_dir().join(format!("tasklog-state-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("pomo.state");
+        let path = path.to_str().unwrap();
+
+        assert_eq!(read_state(path).unwrap(), None);
+        fs::write(path, session().serialize()).unwrap();
+        assert_eq!(read_state(path).unwrap(), Some(session()));
+
+        // A state file that cannot be read is no parse error.
+        fs::write(path, "colour = blue").unwrap();
+        assert_eq!(read_state(path).unwrap_err().exit_code(), 6);
+        assert_eq!(
+            read_state(dir.to_str().unwrap()).unwrap_err().exit_code(),
+            5
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let state = session();
+        assert_eq!(
+            parse_response(&format!("ok\n{}", state.serialize())).unwrap(),
+            state
+        );
+        assert_eq!(
+            parse_response("error: Session #9 is not paused\n")
+                .unwrap_err()
+                .to_string(),
+            "Session #9 is not paused"
+        );
+        assert_eq!(
+            DaemonRequest::parse(DaemonRequest::Resume.as_str()),
+            Some(DaemonRequest::Resume)
+        );
+        assert_eq!(DaemonRequest::parse("reboot"), None);
+    }
+}
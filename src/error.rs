@@ -0,0 +1,278 @@
+use std::fmt;
+use std::path::PathBuf;
+
+/// Everything that can go wrong while reading or writing the task database.
+#[derive(Debug)]
+pub enum StoreError {
+    /// The SQLite database rejected a query or could not be opened
+    Database(rusqlite::Error),
+    /// The database directory could not be created or read
+    Io(std::io::Error),
+    /// There is no home directory to keep the database in
+    NoHomeDirectory,
+    /// No database has been created at the path yet
+    Uninitialized(PathBuf),
+    /// A stored value could not be read back, e.g. a date that is not RFC 3339
+    Corrupt(rusqlite::Error),
+    /// No record of this kind has the id
+    NotFound { record: &'static str, id: u64 },
+    /// No record of this kind has a uuid starting with the prefix
+    UnknownUuid {
+        record: &'static str,
+        prefix: String,
+    },
+    /// The change clashes with a record already stored
+    Conflict(String),
+    /// The request itself is malformed, e.g. an empty date range
+    Invalid(String),
+    /// The schema could not be upgraded to the version
+    Migration {
+        version: usize,
+        source: rusqlite::Error,
+    },
+    /// `doctor` found problems it left as they were
+    Unhealthy(String),
+}
+
+impl StoreError {
+    /// Turns the "no rows" error of a lookup by id into `NotFound`.
+    pub(crate) fn or_not_found(err: rusqlite::Error, record: &'static str, id: u64) -> StoreError {
+        match err {
+            rusqlite::Error::QueryReturnedNoRows => StoreError::NotFound { record, id },
+            err => err.into(),
+        }
+    }
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Database(err) => write!(f, "{}", err),
+            StoreError::Io(err) => write!(f, "{}", err),
+            StoreError::NoHomeDirectory => write!(
+                f,
+                "Could not find the home directory to keep the database in"
+            ),
+            StoreError::Uninitialized(path) => {
+                write!(f, "There is no database at {}", path.display())
+            }
+            StoreError::Corrupt(err) => {
+                write!(f, "The database holds a value that cannot be read: {}", err)
+            }
+            StoreError::NotFound { record, id } => write!(f, "There is no {} #{}", record, id),
+            StoreError::UnknownUuid { record, prefix } => {
+                write!(
+                    f,
+                    "There is no {} with a uuid starting with {}",
+                    record, prefix
+                )
+            }
+            StoreError::Conflict(msg) | StoreError::Invalid(msg) | StoreError::Unhealthy(msg) => {
+                write!(f, "{}", msg)
+            }
+            StoreError::Migration { version, source } => write!(
+                f,
+                "Could not migrate the database to version {}: {}",
+                version, source
+            ),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StoreError::Database(err)
+            | StoreError::Corrupt(err)
+            | StoreError::Migration { source: err, .. } => Some(err),
+            StoreError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(err: rusqlite::Error) -> StoreError {
+        match err {
+            rusqlite::Error::FromSqlConversionFailure(..)
+            | rusqlite::Error::InvalidColumnType(..)
+            | rusqlite::Error::IntegralValueOutOfRange(..) => StoreError::Corrupt(err),
+            err => StoreError::Database(err),
+        }
+    }
+}
+
+impl From<std::io::Error> for StoreError {
+    fn from(err: std::io::Error) -> StoreError {
+        StoreError::Io(err)
+    }
+}
+
+/// What a command can fail with. Each kind exits with its own code:
+///
+/// | code | kind |
+/// |------|------|
+/// | 2 | validation, the arguments or the change are not acceptable |
+/// | 3 | not found |
+/// | 4 | storage, the database failed |
+/// | 5 | io, a file, the terminal or the background daemon failed |
+/// | 6 | parse, a config file, template or stored value cannot be read |
+#[derive(Debug)]
+pub enum AppError {
+    NotFound(String),
+    Validation(String),
+    Storage(StoreError),
+    Io(String),
+    /// A file, the terminal or a process failed, with the error the system gave
+    System(std::io::Error),
+    Parse(String),
+    /// What was being done when the error happened
+    Context {
+        context: String,
+        source: Box<AppError>,
+    },
+}
+
+impl AppError {
+    /// Wraps the error with what was being done, e.g. `Could not write report.md`.
+    pub fn context(self, context: impl Into<String>) -> AppError {
+        AppError::Context {
+            context: context.into(),
+            source: Box::new(self),
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            AppError::Validation(_)
+            | AppError::Storage(StoreError::Conflict(_) | StoreError::Invalid(_)) => 2,
+            AppError::NotFound(_)
+            | AppError::Storage(StoreError::NotFound { .. } | StoreError::UnknownUuid { .. }) => 3,
+            AppError::Storage(StoreError::Corrupt(_)) | AppError::Parse(_) => 6,
+            AppError::Storage(_) => 4,
+            AppError::Io(_) | AppError::System(_) => 5,
+            AppError::Context { source, .. } => source.exit_code(),
+        }
+    }
+
+    /// What the user can do about the error, when there is something obvious.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            // Commands create the database, it only goes missing under a running session.
+            AppError::Storage(StoreError::Uninitialized(_)) => {
+                Some("the database was moved or deleted, `tasklog restore` brings back a backup")
+            }
+            AppError::Storage(StoreError::NoHomeDirectory) => {
+                Some("set $HOME to the directory tasklog should keep its data in")
+            }
+            AppError::Storage(StoreError::Migration { .. }) => {
+                Some("the database was left as it was, a newer tasklog may have written it")
+            }
+            AppError::Storage(StoreError::Corrupt(_)) => {
+                Some("rerun with --verbose to see which value could not be read")
+            }
+            AppError::Context { source, .. } => source.hint(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::NotFound(msg)
+            | AppError::Validation(msg)
+            | AppError::Io(msg)
+            | AppError::Parse(msg) => write!(f, "{}", msg),
+            AppError::Storage(err) => write!(f, "{}", err),
+            AppError::System(err) => write!(f, "{}", err),
+            AppError::Context { context, source } => write!(f, "{}: {}", context, source),
+        }
+    }
+}
+
+impl std::error::Error for AppError {
+    // The message of a wrapped error is already part of the display, the chain goes on from its
+    // source. An io error is the cause itself, with its kind and os error code.
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AppError::Storage(err) => std::error::Error::source(err),
+            AppError::System(err) => Some(err),
+            AppError::Context { source, .. } => source.source(),
+            _ => None,
+        }
+    }
+}
+
+impl From<StoreError> for AppError {
+    fn from(err: StoreError) -> AppError {
+        AppError::Storage(err)
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(err: std::io::Error) -> AppError {
+        AppError::System(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+
+    #[test]
+    fn test_exit_codes() {
+        let missing = StoreError::NotFound {
+            record: "task",
+            id: 7,
+        };
+        assert_eq!(AppError::from(missing).exit_code(), 3);
+        assert_eq!(AppError::Validation("no".to_string()).exit_code(), 2);
+        assert_eq!(
+            AppError::from(StoreError::Conflict("overlaps".to_string())).exit_code(),
+            2
+        );
+        assert_eq!(AppError::from(StoreError::NoHomeDirectory).exit_code(), 4);
+        assert_eq!(
+            AppError::from(StoreError::Unhealthy("1 problems found".to_string())).exit_code(),
+            4
+        );
+        assert_eq!(AppError::Parse("config".to_string()).exit_code(), 6);
+
+        let io = std::io::Error::new(std::io::ErrorKind::PermissionDenied, "denied");
+        let wrapped = AppError::from(io).context("Could not write report.md");
+        assert_eq!(wrapped.exit_code(), 5);
+        assert_eq!(wrapped.to_string(), "Could not write report.md: denied");
+        let cause = std::error::Error::source(&wrapped).unwrap();
+        assert_eq!(
+            cause.downcast_ref::<std::io::Error>().map(|e| e.kind()),
+            Some(std::io::ErrorKind::PermissionDenied)
+        );
+    }
+
+    #[test]
+    fn test_hints_and_causes() {
+        let no_home =
+            AppError::from(StoreError::NoHomeDirectory).context("Could not list the tasks");
+        assert_eq!(
+            no_home.hint(),
+            Some("set $HOME to the directory tasklog should keep its data in")
+        );
+        let missing = AppError::from(StoreError::Uninitialized(PathBuf::from("/tmp/db")))
+            .context("Could not record the session");
+        assert_eq!(
+            missing.hint(),
+            Some("the database was moved or deleted, `tasklog restore` brings back a backup")
+        );
+        assert!(AppError::Validation("no".to_string()).hint().is_none());
+
+        let corrupt = AppError::from(StoreError::from(rusqlite::Error::InvalidColumnType(
+            3,
+            "due_date".to_string(),
+            rusqlite::types::Type::Null,
+        )));
+        assert_eq!(corrupt.exit_code(), 6);
+        assert!(corrupt.source().is_some());
+    }
+}
@@ -1,230 +1,1815 @@
-use crate::helper::{draw_ui, run_event_thread, run_timer_thread};
-use crate::models::{
-    AnalyzeArgs, AppState, CommandArgs, LSType, PomoStatus, PomodoroEvent, TableRow,
-};
-use crate::{
-    helper::{self, get_home_directory},
-    models::{format_string_with_color, Color, DoneArgs, LSArgs, PomoTask, Task, TaskStatus},
-    repository,
-};
-use chrono::Local;
+use chrono::{DateTime, Local, Utc};
 use crossterm::cursor;
 use crossterm::{execute, terminal};
-use std::io::stdout;
-use std::sync::mpsc;
-use std::thread;
-use std::time::Duration;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::{stdout, Stdout, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tasklog::config::{home_timezone, Config};
+use tasklog::daemon::{self, DaemonRequest, SessionState};
+use tasklog::models::{
+    format_string_with_color, BackupArgs, Color, DoctorArgs, DoneArgs, EditArgs, ExportArgs,
+    ImportArgs, LSArgs, ListedTask, PomoTask, ShowArgs, Task, TaskStatus,
+};
+use tasklog::models::{
+    AnalyzeArgs, AnalyzeType, AppState, CategoryInterruptions, CommandArgs, CountdownAction,
+    DurationField, Goal, GoalsArgs, GoalsCommand, InterruptArgs, InterruptionKind, LSType,
+    NotifyArgs, PomoAlert, PomoArgs, PomoCommand, PomoLogArgs, PomoStatus, PomoType, PomodoroEvent,
+    PromptArgs, ReportArgs, RestoreArgs, SessionEndChoice, SyncArgs, SyncCommand, SyncTable,
+    TableRow, TaskFormat, TimeEntry, TimesheetArgs, TodotxtSyncArgs, TrackArgs, TrackCommand,
+    TrackStartArgs,
+};
+use tasklog::notify::{self, Notifier};
+use tasklog::prompt::{self, PromptInfo};
+use tasklog::sync::{self, Side, SyncRepo};
+use tasklog::todotxt::{self, TodoItem};
+use tasklog::{report, timesheet, Analytics, AppError, Repository, StoreError, TaskStore};
+
+use crate::ui::{self, draw_end_ui, draw_ui, run_event_thread, run_timer_thread};
+
+/// How much longer the `[e]` choice of the time's up screen keeps a session going
+const SESSION_EXTENSION_MINUTES: i64 = 5;
+/// How often and how many times the time's up box flips its colors
+const FLASH_INTERVAL_MILLIS: u64 = 500;
+const FLASH_TOGGLES: u32 = 10;
+
+/// Prints the rows as a table, or a line saying there are none.
+fn print_rows(rows: &Vec<Box<dyn TableRow>>) {
+    match rows.is_empty() {
+        true => println!("Nothing found"),
+        false => ui::print_tables(rows),
+    }
+}
 
 /// When we have to map types 10000000000000000000 times in rust the most simple tasks like passing
 /// to a fucking function, why the fuck they say Rust's performance is good?????????
-pub fn handle_ls(args: &LSArgs) -> Result<(), String> {
-    args.validate().map_err(|e| format!("Err: {}", e))?;
+pub fn handle_ls(store: &dyn Repository, args: &LSArgs) -> Result<(), AppError> {
+    args.validate()?;
+
+    let (last_day, due_before) = args.due_until(Utc::now().with_timezone(&home_timezone()))?;
 
     let t: Vec<Box<dyn TableRow>> = match args.ls_type {
-        LSType::Task => repository::get_tasks(args)
-            .map_err(|e| format_string_with_color(e.as_str(), Color::Red))
-            .unwrap()
-            .into_iter()
-            .map(|t| Box::new(t) as Box<dyn TableRow>)
-            .collect(),
+        LSType::Task => {
+            let uuid_len = store.uuid_prefix_len()?;
+            store
+                .tasks(args, due_before.with_timezone(&Local))?
+                .into_iter()
+                .map(|task| Box::new(ListedTask { task, uuid_len }) as Box<dyn TableRow>)
+                .collect()
+        }
 
-        LSType::Pomo => repository::get_pomodoro(args)
-            .map_err(|e| format_string_with_color(e.as_str(), Color::Red))
-            .unwrap()
+        LSType::Pomo => store
+            .sessions(args.limit)?
             .into_iter()
             .map(|t| Box::new(t) as Box<dyn TableRow>)
             .collect(),
     };
 
-    let due_date = Local::now() + chrono::Duration::days(args.days as i64);
     let text = format_string_with_color(
         format!(
             "Results tasks with due_date as {} or before:\n",
-            due_date.format("%Y-%m-%d")
+            last_day.format("%Y-%m-%d")
         )
         .as_str(),
         Color::Green,
     );
 
     println!("{}", text);
-    helper::print_tables(&t)
+    print_rows(&t);
+    Ok(())
 }
 
-pub fn handel_add_task(task: Task) -> Result<(), String> {
-    task.validate().map_err(|e| format!("Err: {}", e))?;
+pub fn handel_add_task(store: &dyn Repository, task: Task) -> Result<(), AppError> {
+    task.validate()?;
 
     let mut task = task;
 
-    repository::save_task(&mut task)
-        .map_err(|e| format_string_with_color(format!("Error: {}", e).as_str(), Color::Red))?;
+    store.add_task(&mut task)?;
+
+    ui::print_tasks_table(&vec![task]);
+    Ok(())
+}
+
+pub fn handle_init_db(path: &Path) -> Result<(), AppError> {
+    TaskStore::init(path)?;
+
+    Ok(())
+}
+
+pub fn handle_doctor(path: &Path, doctor_args: DoctorArgs) -> Result<(), AppError> {
+    let diagnoses = TaskStore::diagnose(path, Local::now(), doctor_args.fix)
+        .map_err(|e| AppError::from(e).context(format!("Could not check {}", path.display())))?;
+
+    let mut left = 0;
+    for diagnosis in &diagnoses {
+        if diagnosis.findings.is_empty() {
+            println!(
+                "{} {}",
+                format_string_with_color("ok   ", Color::Green),
+                diagnosis.check
+            );
+        }
+        for finding in &diagnosis.findings {
+            let status = if finding.fixed {
+                format_string_with_color("fixed", Color::Cyan)
+            } else {
+                left += 1;
+                format_string_with_color("fail ", Color::Red)
+            };
+            println!("{} {}: {}", status, diagnosis.check, finding.message);
+        }
+    }
+
+    match (left, doctor_args.fix) {
+        (0, _) => Ok(()),
+        (left, false) => Err(StoreError::Unhealthy(format!(
+            "{} problems found, rerun with --fix to repair what can be",
+            left
+        ))
+        .into()),
+        (left, true) => {
+            Err(StoreError::Unhealthy(format!("{} problems could not be repaired", left)).into())
+        }
+    }
+}
+
+pub fn handle_backup(store: &TaskStore, backup_args: BackupArgs) -> Result<(), AppError> {
+    let path = match backup_args.path {
+        Some(path) => path,
+        None => TaskStore::default_backup_dir()?.join(backup_file_name("backup")),
+    };
+
+    store.backup(&path).map_err(|e| {
+        AppError::from(e).context(format!("Could not back up to {}", path.display()))
+    })?;
+
+    println!(
+        "{}",
+        format_string_with_color(
+            format!("backed up to {}", path.display()).as_str(),
+            Color::Green
+        )
+    );
+    Ok(())
+}
+
+pub fn handle_restore(store: &mut TaskStore, restore_args: RestoreArgs) -> Result<(), AppError> {
+    let file = &restore_args.file;
+    let context = || format!("Could not restore {}", file.display());
+    TaskStore::check_backup(file).map_err(|e| AppError::from(e).context(context()))?;
+
+    let question = format!(
+        "Replace every task, session and goal with the ones in {}?",
+        file.display()
+    );
+    if !restore_args.yes && !ui::confirm(&question)? {
+        println!("Nothing was restored");
+        return Ok(());
+    }
+
+    let snapshot = TaskStore::default_backup_dir()?.join(backup_file_name("pre-restore"));
+    store
+        .backup(&snapshot)
+        .map_err(|e| AppError::from(e).context("Could not snapshot the database"))?;
+    store
+        .restore(file)
+        .map_err(|e| AppError::from(e).context(context()))?;
+
+    println!(
+        "{}",
+        format_string_with_color(
+            format!(
+                "restored {}, the previous database is in {}",
+                file.display(),
+                snapshot.display()
+            )
+            .as_str(),
+            Color::Green
+        )
+    );
+    Ok(())
+}
+
+pub fn handle_sync(
+    store: &TaskStore,
+    config: &Config,
+    sync_args: SyncArgs,
+) -> Result<(), AppError> {
+    if let Some(SyncCommand::Todotxt(todotxt_args)) = sync_args.command {
+        return sync_todotxt(store, todotxt_args);
+    }
+
+    let dir = match &config.sync_dir {
+        Some(dir) => PathBuf::from(dir),
+        None => sync::default_dir()?,
+    };
+    let remote = match sync_args.offline {
+        true => None,
+        false => sync_args.remote.or_else(|| config.sync_remote.clone()),
+    };
+
+    let repo = SyncRepo::open(&dir, remote.as_deref())?;
+    let head = repo.head()?;
+    let remote_head = match remote {
+        Some(_) => repo.fetch()?,
+        None => None,
+    };
+
+    let (mut pulled, mut pushed, mut conflicts) = (0, 0, Vec::new());
+    for table in SyncTable::ALL {
+        let base = match &head {
+            Some(head) => repo.records_at(head, table)?,
+            None => sync::Records::new(),
+        };
+        let theirs = match &remote_head {
+            Some(remote_head) => repo.records_at(remote_head, table)?,
+            None => base.clone(),
+        };
+
+        let merge = sync::merge(table, &base, &store.sync_records(table)?, &theirs);
+        store.apply_sync_records(table, &merge.pulled)?;
+        pulled += merge.pulled.len();
+        pushed += merge.pushed;
+        conflicts.extend(merge.conflicts);
+    }
+
+    if let Some(remote_head) = &remote_head {
+        repo.join(remote_head)?;
+    }
+    let mut files = Vec::new();
+    for table in SyncTable::ALL {
+        files.push((table, sync::render(table, &store.sync_records(table)?)));
+    }
+    repo.commit(&files, &format!("Sync {}", Local::now().to_rfc3339()))?;
+    if remote.is_some() {
+        repo.push()?;
+    }
+
+    for conflict in &conflicts {
+        let kept = match conflict.kept {
+            Side::Local => "kept the change made here",
+            Side::Remote => "kept the change pulled",
+        };
+        println!(
+            "{}",
+            format_string_with_color(
+                format!(
+                    "conflict: {} '{}' ({}) changed on both machines, {}",
+                    conflict.table.record(),
+                    conflict.title,
+                    conflict.uuid,
+                    kept
+                )
+                .as_str(),
+                Color::Yellow
+            )
+        );
+    }
+
+    let summary = match &remote {
+        Some(remote) => format!(
+            "synced with {}: {} pulled, {} pushed, {} conflicts",
+            remote,
+            pulled,
+            pushed,
+            conflicts.len()
+        ),
+        None => format!("committed to {}, no remote to sync with", dir.display()),
+    };
+    println!(
+        "{}",
+        format_string_with_color(summary.as_str(), Color::Green)
+    );
+    Ok(())
+}
+
+/// Brings the tasks and the todo.txt file up to date with each other, then writes the tasks of the
+/// file back the way their line was and keeps every field of them aside to tell next time which
+/// side changed.
+fn sync_todotxt(store: &dyn Repository, todotxt_args: TodotxtSyncArgs) -> Result<(), AppError> {
+    let tz = home_timezone();
+    let file = todotxt_args.file;
+    let base_path = todotxt::base_path(&file)?;
+
+    let items = read_todotxt(&file, true)?;
+    let base = read_todotxt(&base_path, true)?;
+    let file_modified = fs::metadata(&file)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .map(DateTime::<Local>::from);
+
+    let merge = todotxt::reconcile(&items, &base, &store.all_tasks()?, file_modified, &tz)?;
+    for mut task in merge.added.iter().cloned() {
+        store.add_task(&mut task)?;
+    }
+    for task in &merge.updated {
+        store.update_task(task)?;
+    }
+
+    let tasks = store.all_tasks()?;
+    if let Some(dir) = base_path.parent() {
+        fs::create_dir_all(dir).map_err(|e| {
+            AppError::from(e).context(format!("Could not create {}", dir.display()))
+        })?;
+    }
+    let written: Vec<Task> = tasks
+        .iter()
+        .filter(|task| merge.writes(task))
+        .cloned()
+        .collect();
+    let remembered: Vec<Task> = tasks
+        .iter()
+        .filter(|task| merge.remembers(task))
+        .cloned()
+        .collect();
+    for (path, tasks, lines) in [
+        (&file, &written, &merge.lines),
+        (&base_path, &remembered, &BTreeMap::new()),
+    ] {
+        let rendered = todotxt::render(tasks, lines, &tz);
+        fs::write(path, rendered).map_err(|e| {
+            AppError::from(e).context(format!("Could not write {}", path.display()))
+        })?;
+    }
+
+    for conflict in &merge.conflicts {
+        let kept = match conflict.kept {
+            Side::Local => "kept the change made here",
+            Side::Remote => "kept the change in the file",
+        };
+        println!(
+            "{}",
+            format_string_with_color(
+                format!(
+                    "conflict: task '{}' ({}) changed here and in the file, {}",
+                    conflict.title, conflict.uuid, kept
+                )
+                .as_str(),
+                Color::Yellow
+            )
+        );
+    }
+    println!(
+        "{}",
+        format_string_with_color(
+            format!(
+                "synced with {}: {} added, {} updated, {} written to the file, {} conflicts",
+                file.display(),
+                merge.added.len(),
+                merge.updated.len(),
+                merge.pushed,
+                merge.conflicts.len()
+            )
+            .as_str(),
+            Color::Green
+        )
+    );
+    Ok(())
+}
+
+/// The items of a todo.txt file, a file that does not exist has none when `missing_ok`.
+fn read_todotxt(path: &Path, missing_ok: bool) -> Result<Vec<TodoItem>, AppError> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if missing_ok && e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => {
+            return Err(AppError::from(e).context(format!("Could not read {}", path.display())))
+        }
+    };
+
+    todotxt::parse(&contents).map_err(|e| e.context(path.display().to_string()))
+}
+
+/// Adds the tasks of the file, skipping the ones whose uuid is already here.
+pub fn handle_import(store: &dyn Repository, import_args: ImportArgs) -> Result<(), AppError> {
+    let items = match import_args.from {
+        TaskFormat::Todotxt => read_todotxt(&import_args.file, false)?,
+    };
+
+    let tz = home_timezone();
+    let mut known: HashSet<String> = store
+        .all_tasks()?
+        .into_iter()
+        .map(|task| task.meta.uuid)
+        .collect();
+    let mut imported = 0;
+    for item in &items {
+        if item.uuid.as_ref().is_some_and(|uuid| known.contains(uuid)) {
+            continue;
+        }
+        let mut task = item.to_task(&tz)?;
+        store.add_task(&mut task)?;
+        known.insert(task.meta.uuid);
+        imported += 1;
+    }
+
+    println!(
+        "{}",
+        format_string_with_color(
+            format!(
+                "imported {} tasks from {}, {} were already here",
+                imported,
+                import_args.file.display(),
+                items.len() - imported
+            )
+            .as_str(),
+            Color::Green
+        )
+    );
+    Ok(())
+}
+
+pub fn handle_export(store: &dyn Repository, export_args: ExportArgs) -> Result<(), AppError> {
+    let rendered = match export_args.to {
+        TaskFormat::Todotxt => {
+            todotxt::render(&store.all_tasks()?, &BTreeMap::new(), &home_timezone())
+        }
+    };
 
-    helper::print_tasks_table(&vec![task])
+    match &export_args.output {
+        None => print!("{}", rendered),
+        Some(path) => {
+            fs::write(path, rendered).map_err(|e| {
+                AppError::from(e).context(format!("Could not write {}", path.display()))
+            })?;
+            println!(
+                "{}",
+                format_string_with_color(
+                    format!("tasks written to {}", path.display()).as_str(),
+                    Color::Green
+                )
+            );
+        }
+    }
+
+    Ok(())
 }
 
-pub fn handle_init_db() -> Result<(), String> {
-    let home_dir = get_home_directory()?;
+/// Takes today's snapshot of the database when `daily_snapshots` is configured.
+pub fn take_daily_snapshot(store: &TaskStore, config: &Config) -> Result<(), AppError> {
+    if let Some(keep) = config.daily_snapshots.filter(|keep| *keep > 0) {
+        store.snapshot_daily(
+            TaskStore::default_backup_dir()?,
+            Local::now().date_naive(),
+            keep,
+        )?;
+    }
+
+    Ok(())
+}
 
-    repository::init_db(home_dir)
+/// `<kind>-<local time>.sqlite`, named so that they sort by when they were taken.
+fn backup_file_name(kind: &str) -> String {
+    format!("{}-{}.sqlite", kind, Local::now().format("%Y%m%d-%H%M%S"))
 }
 
-pub fn handle_analyze(analyze_args: AnalyzeArgs) -> Result<(), String> {
-    analyze_args.validate().map_err(|e| format!("Err: {}", e))?;
+pub fn handle_analyze(store: &dyn Analytics, analyze_args: AnalyzeArgs) -> Result<(), AppError> {
+    analyze_args.validate()?;
 
-    let analysis_volumes = repository::get_analysis(&analyze_args)
-        .map_err(|e| format!("Err: {}", e))
-        .unwrap()
+    match analyze_args.analyze_type {
+        AnalyzeType::Interruptions => return analyze_interruptions(store, &analyze_args),
+        AnalyzeType::Time => return analyze_time(store, &analyze_args),
+        AnalyzeType::Tasks => {}
+    }
+
+    let (start_date, end_date) =
+        analyze_args.date_range(Utc::now().with_timezone(&home_timezone()))?;
+
+    let analysis_volumes = store
+        .task_analysis(
+            analyze_args.group_by,
+            home_timezone(),
+            start_date,
+            end_date,
+            Local::now(),
+        )?
         .into_iter()
         .map(|t| Box::new(t) as Box<dyn TableRow>)
         .collect();
 
-    helper::print_tables(&analysis_volumes).map_err(|e| format!("Error printing tables: {}", e))?;
+    print_rows(&analysis_volumes);
+
+    let flow_analysis = store.flow_analysis(start_date, end_date, Local::now())?;
+    println!();
+    print_rows(&vec![Box::new(flow_analysis) as Box<dyn TableRow>]);
     Ok(())
 }
 
-pub fn handle_done(done_args: DoneArgs) -> Result<(), String> {
-    let task = repository::get_task_by_id(done_args.id)?;
+fn analyze_interruptions(
+    store: &dyn Analytics,
+    analyze_args: &AnalyzeArgs,
+) -> Result<(), AppError> {
+    let (start_date, end_date) =
+        analyze_args.date_range(Utc::now().with_timezone(&home_timezone()))?;
+    let (sessions, hours) = store.interruptions(start_date, end_date)?;
+    if sessions.is_empty() && hours.is_empty() {
+        println!("No work sessions in the range");
+        return Ok(());
+    }
+
+    let categories = CategoryInterruptions::from_sessions(&sessions);
+
+    let tables: [Vec<Box<dyn TableRow>>; 3] = [
+        sessions
+            .into_iter()
+            .map(|s| Box::new(s) as Box<dyn TableRow>)
+            .collect(),
+        hours
+            .into_iter()
+            .map(|h| Box::new(h) as Box<dyn TableRow>)
+            .collect(),
+        categories
+            .into_iter()
+            .map(|c| Box::new(c) as Box<dyn TableRow>)
+            .collect(),
+    ];
+
+    for (i, table) in tables.iter().filter(|t| !t.is_empty()).enumerate() {
+        if i > 0 {
+            println!();
+        }
+        print_rows(table);
+    }
+
+    Ok(())
+}
+
+fn analyze_time(store: &dyn Analytics, analyze_args: &AnalyzeArgs) -> Result<(), AppError> {
+    let (start_date, end_date) =
+        analyze_args.date_range(Utc::now().with_timezone(&home_timezone()))?;
+    let (per_task, per_category) = store.tracked_time(start_date, end_date, Local::now())?;
+    if per_task.is_empty() {
+        println!("No time tracked in the range");
+        return Ok(());
+    }
+
+    for (i, table) in [per_task, per_category].into_iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        let rows = table
+            .into_iter()
+            .map(|t| Box::new(t) as Box<dyn TableRow>)
+            .collect();
+        print_rows(&rows);
+    }
+
+    Ok(())
+}
+
+pub fn handle_report(store: &dyn Analytics, report_args: ReportArgs) -> Result<(), AppError> {
+    report_args.validate()?;
+
+    let (start_date, end_date) = report_args
+        .period
+        .date_range(Utc::now().with_timezone(&home_timezone()))?;
+    let report_data = store.report(start_date, end_date, Local::now())?;
+
+    let template = report::load_template(report_args.format)?;
+    let title = format!("{} report", String::from(report_args.period));
+    let rendered = report::render(&template, report_args.format, &title, &report_data);
+
+    match &report_args.output {
+        None => print!("{}", rendered),
+        Some(path) => {
+            fs::write(path, rendered).map_err(|e| {
+                AppError::from(e).context(format!("Could not write {}", path.display()))
+            })?;
+            println!(
+                "{}",
+                format_string_with_color(
+                    format!("report written to {}", path.display()).as_str(),
+                    Color::Green
+                )
+            );
+        }
+    }
+
+    Ok(())
+}
+
+pub fn handle_timesheet(
+    store: &dyn Analytics,
+    timesheet_args: TimesheetArgs,
+) -> Result<(), AppError> {
+    timesheet_args.validate()?;
+
+    let home = home_timezone();
+    let (start_date, end_date) = timesheet_args.date_range(Utc::now().with_timezone(&home))?;
+    let lines = store.timesheet(home, start_date, end_date, Local::now())?;
+    let rendered = timesheet::render(
+        &lines,
+        timesheet_args.round.as_ref().map(|round| round.to_i64()),
+        timesheet_args.format,
+    );
+
+    match &timesheet_args.output {
+        None => print!("{}", rendered),
+        Some(path) => {
+            fs::write(path, rendered).map_err(|e| {
+                AppError::from(e).context(format!("Could not write {}", path.display()))
+            })?;
+            println!(
+                "{}",
+                format_string_with_color(
+                    format!("timesheet written to {}", path.display()).as_str(),
+                    Color::Green
+                )
+            );
+        }
+    }
+
+    Ok(())
+}
+
+pub fn handle_notify(
+    store: &dyn Repository,
+    config: &Config,
+    notify_args: NotifyArgs,
+) -> Result<(), AppError> {
+    notify_args.validate()?;
+
+    let notifier = Notifier::from_settings(&notify_args, config)?;
+
+    let now = Local::now();
+    let mut failures = Vec::new();
+
+    for task in store.due_reminders(now)? {
+        match notifier.fire(&task) {
+            Ok(_) => store.mark_reminded(task.id, now)?,
+            Err(err) => failures.push(format!("task {}: {}", task.id, err)),
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::Io(format!(
+            "Could not deliver reminders:\n{}",
+            failures.join("\n")
+        )))
+    }
+}
+
+/// Prints the prompt segment, `session` being the one of the background daemon if any.
+pub fn handle_prompt(
+    store: &dyn Repository,
+    session: Option<SessionState>,
+    prompt_args: PromptArgs,
+) -> Result<(), AppError> {
+    let now = Local::now();
+    let (open, overdue, running) = store.prompt_status(now)?;
+    let info = PromptInfo::new(session, running.as_ref(), open, overdue);
+
+    let output = prompt::render(prompt_args.style, prompt_args.format.as_deref(), &info, now);
+    if !output.is_empty() {
+        println!("{}", output);
+    }
+
+    Ok(())
+}
+
+pub fn handle_goals(store: &dyn Repository, goals_args: GoalsArgs) -> Result<(), AppError> {
+    match goals_args.command {
+        None => {
+            let progress = store.goal_progress(Local::now().date_naive())?;
+            if progress.is_empty() {
+                println!("No goals yet, set one with `tasklog goals add`");
+                return Ok(());
+            }
+
+            let rows = progress
+                .into_iter()
+                .map(|p| Box::new(p) as Box<dyn TableRow>)
+                .collect();
+            print_rows(&rows);
+            Ok(())
+        }
+        Some(GoalsCommand::Add(add_args)) => {
+            add_args.validate()?;
+
+            let mut goal = Goal {
+                id: 0,
+                metric: add_args.metric,
+                period: add_args.per,
+                target: add_args.target_value()?,
+            };
+            store.add_goal(&mut goal)?;
+
+            println!(
+                "{}",
+                format_string_with_color(
+                    format!("added goal #{}: {}", goal.id, goal).as_str(),
+                    Color::Green
+                )
+            );
+            Ok(())
+        }
+        Some(GoalsCommand::Remove(remove_args)) => {
+            store.remove_goal(remove_args.id)?;
+            println!("Removed goal #{}", remove_args.id);
+            Ok(())
+        }
+    }
+}
+
+pub fn handle_track(store: &dyn Repository, track_args: TrackArgs) -> Result<(), AppError> {
+    match track_args.command {
+        TrackCommand::Start(start_args) => start_tracking(store, start_args),
+        TrackCommand::Stop => {
+            let mut entry = store
+                .running_time_entry()?
+                .ok_or_else(|| AppError::NotFound("No time entry is running".to_string()))?;
+            store.finish_time_entry(&mut entry, Local::now())?;
+            print_rows(&vec![Box::new(entry) as Box<dyn TableRow>]);
+            Ok(())
+        }
+        TrackCommand::Status => {
+            match store.running_time_entry()? {
+                Some(entry) => print_rows(&vec![Box::new(entry) as Box<dyn TableRow>]),
+                None => println!("No time entry is running"),
+            }
+            Ok(())
+        }
+    }
+}
+
+fn start_tracking(store: &dyn Repository, start_args: TrackStartArgs) -> Result<(), AppError> {
+    start_args.validate()?;
+
+    let task =
+        match start_args.task_id {
+            Some(task_id) => Some(store.task(task_id).map_err(|e| {
+                AppError::from(e).context(format!("Could not link task #{}", task_id))
+            })?),
+            None => None,
+        };
+
+    let mut entry = TimeEntry {
+        id: 0,
+        task_id: start_args.task_id,
+        title: match (&start_args.title, &task) {
+            (Some(title), _) => title.trim().to_string(),
+            (None, Some(task)) => task.title.clone(),
+            (None, None) => {
+                return Err(AppError::Validation(
+                    "A time entry needs a --task or a --title".to_string(),
+                ))
+            }
+        },
+        category: start_args
+            .category
+            .or_else(|| task.as_ref().and_then(|task| task.category.clone())),
+        start_time: Local::now(),
+        end_time: None,
+    };
+    store.start_time_entry(&mut entry)?;
+
+    if start_args.detach {
+        println!(
+            "Started tracking #{} {}, stop it with `tasklog track stop`",
+            entry.id, entry.title
+        );
+        return Ok(());
+    }
+
+    // An entry whose stopwatch cannot be shown is stopped rather than left running unseen.
+    let shown = ui::clear_terminal_screen()
+        .map_err(AppError::from)
+        .and_then(|_| stopwatch_terminal(&entry, task.as_ref()));
+    if let Err(err) = shown {
+        return Err(match store.finish_time_entry(&mut entry, Local::now()) {
+            Ok(()) => err.context(format!("Stopped time entry #{}", entry.id)),
+            Err(_) => err.context(format!(
+                "Time entry #{} is still running, stop it with `tasklog track stop`",
+                entry.id
+            )),
+        });
+    }
+
+    // The entry may have been stopped from another terminal in the meantime.
+    match store.running_time_entry()? {
+        Some(running) if running.id == entry.id => {
+            store.finish_time_entry(&mut entry, Local::now())?
+        }
+        _ => {
+            return Err(AppError::Validation(format!(
+                "Time entry #{} was already stopped",
+                entry.id
+            )))
+        }
+    }
+    print_rows(&vec![Box::new(entry) as Box<dyn TableRow>]);
+    Ok(())
+}
+
+/// Shows the stopwatch of a time entry until `q` or ctrl-c.
+fn stopwatch_terminal(entry: &TimeEntry, task: Option<&Task>) -> Result<(), AppError> {
+    let mut stdout = stdout();
+
+    terminal::enable_raw_mode()?;
+    execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+
+    let (term_width, term_height) = terminal::size()?;
+
+    let mut app_state = AppState {
+        title: entry.title.clone(),
+        category: entry.category.clone(),
+        task: task.map(|task| format!("#{} {}", task.id, task.title)),
+        term_width,
+        term_height,
+        stopwatch: true,
+        ..Default::default()
+    };
+
+    let (event_tx, event_rx) = mpsc::channel::<PomodoroEvent>();
+    let (event_thread_quit_tx, event_thread_quit_rx) = mpsc::channel::<()>();
+
+    let event_handle = thread::spawn(move || {
+        run_event_thread(event_tx, event_thread_quit_rx);
+    });
+
+    let result = run_stopwatch(&mut stdout, &mut app_state, &event_rx);
+
+    let _ = event_thread_quit_tx.send(());
+    let _ = event_handle.join();
+
+    execute!(stdout, terminal::LeaveAlternateScreen, cursor::Show)?;
+    terminal::disable_raw_mode()?;
+
+    result
+}
+
+/// Counts up in whole seconds, redrawing the screen whenever the shown time changes.
+fn run_stopwatch(
+    stdout: &mut Stdout,
+    app_state: &mut AppState,
+    event_rx: &Receiver<PomodoroEvent>,
+) -> Result<(), AppError> {
+    let started = Instant::now();
+    draw_ui(stdout, app_state)?;
+
+    loop {
+        let elapsed = Duration::from_secs(started.elapsed().as_secs());
+        if elapsed != app_state.current_time {
+            app_state.current_time = elapsed;
+            draw_ui(stdout, app_state)?;
+        }
+
+        match event_rx.recv_timeout(Duration::from_millis(200)) {
+            Ok(PomodoroEvent::Key('q')) | Ok(PomodoroEvent::Quit) => return Ok(()),
+            Ok(PomodoroEvent::Resize(new_width, new_height)) => {
+                app_state.term_width = new_width;
+                app_state.term_height = new_height;
+                draw_ui(stdout, app_state)?;
+            }
+            Ok(PomodoroEvent::Key(_)) | Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+    }
+}
+
+pub fn handle_done(store: &dyn Repository, done_args: DoneArgs) -> Result<(), AppError> {
+    let task = store.find_task(&done_args.id)?;
 
     if task.status == TaskStatus::Done {
-        return Err("Task is already done".to_string());
+        return Err(AppError::Validation("Task is already done".to_string()));
     };
 
-    repository::done_task(done_args.id).map_err(|e| format!("Error: {}", e))?;
+    store.complete_task(task.id)?;
 
     println!(
         "{}\n\n",
         format_string_with_color("marked task as done", Color::Green)
     );
 
-    helper::print_tasks_table(&vec![task])
+    ui::print_tasks_table(&vec![task]);
+    Ok(())
+}
+
+pub fn handle_show(store: &dyn Repository, show_args: ShowArgs) -> Result<(), AppError> {
+    let task = store.find_task(&show_args.id)?;
+    let uuid_len = store.uuid_prefix_len()?;
+
+    let time = |at: Option<DateTime<Local>>| {
+        at.map(|at| at.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_else(|| "-".to_string())
+    };
+    let fields = [
+        ("id", task.id.to_string()),
+        (
+            "uuid",
+            format!("{} ({})", task.meta.short_uuid(uuid_len), task.meta.uuid),
+        ),
+        ("title", task.title.clone()),
+        ("status", String::from(task.status)),
+        ("due-date", task.due_date.to_string()),
+        ("priority", String::from(task.priority)),
+        (
+            "category",
+            task.category.clone().unwrap_or_else(|| "-".to_string()),
+        ),
+        ("tags", task.tags.join(" ")),
+        ("remind", time(task.remind_at())),
+        ("created", time(task.meta.created_at)),
+        ("updated", time(task.meta.updated_at)),
+    ];
+
+    for (name, value) in fields {
+        println!(
+            "{} {}",
+            format_string_with_color(&format!("{:<9}", name), Color::Cyan),
+            value
+        );
+    }
+
+    Ok(())
 }
 
-pub fn handle_pomodoro(pomo_task: PomoTask) -> Result<(), String> {
+pub fn handle_edit(store: &dyn Repository, edit_args: EditArgs) -> Result<(), AppError> {
+    edit_args.validate()?;
+
+    let task = edit_args.apply(&store.find_task(&edit_args.id)?);
+    if task.remind_at().is_some_and(|at| at > task.due_date.at) {
+        return Err(AppError::Validation(
+            "Reminder cannot be after the due date".to_string(),
+        ));
+    }
+
+    store.update_task(&task)?;
+
+    println!(
+        "{}\n\n",
+        format_string_with_color("updated the task", Color::Green)
+    );
+
+    ui::print_tasks_table(&vec![task]);
+    Ok(())
+}
+
+/// Runs a pomodoro command, `session` being the one of the background daemon if any.
+pub fn handle_pomodoro(
+    store: &dyn Repository,
+    config: &Config,
+    session: Option<SessionState>,
+    pomo_args: PomoArgs,
+) -> Result<(), AppError> {
+    let request = match (pomo_args.command, pomo_args.session) {
+        (Some(PomoCommand::Start(start_args)), _) => {
+            return start_pomodoro(store, config, start_args.session, start_args.detach)
+        }
+        (None, Some(pomo_task)) => return start_pomodoro(store, config, pomo_task, false),
+        (None, None) => {
+            return Err(AppError::Validation(
+                "A session needs a --title".to_string(),
+            ))
+        }
+        (Some(PomoCommand::Daemon), _) => return daemon::run(),
+        (Some(PomoCommand::Interrupt(interrupt_args)), _) => {
+            return interrupt_pomodoro(store, session, interrupt_args)
+        }
+        (Some(PomoCommand::Log(log_args)), _) => return log_pomodoro(store, log_args),
+        (Some(PomoCommand::Status), _) => DaemonRequest::Status,
+        (Some(PomoCommand::Pause), _) => DaemonRequest::Pause,
+        (Some(PomoCommand::Resume), _) => DaemonRequest::Resume,
+        (Some(PomoCommand::Stop), _) => DaemonRequest::Stop,
+    };
+
+    if let Some(recovered) = daemon::recover()? {
+        println!("{}", recovered);
+    }
+
+    let state = daemon::send(request)?;
+    let now = Local::now();
+    match request {
+        DaemonRequest::Stop => println!(
+            "Stopped session #{} after {} minutes",
+            state.id,
+            state.elapsed_at(now).num_minutes()
+        ),
+        _ => println!("{}", state.summary(now)),
+    }
+
+    Ok(())
+}
+
+/// Logs an interruption of the background session, or of the session running in some terminal.
+fn interrupt_pomodoro(
+    store: &dyn Repository,
+    session: Option<SessionState>,
+    interrupt_args: InterruptArgs,
+) -> Result<(), AppError> {
+    interrupt_args.validate()?;
+
+    let now = Local::now();
+    let session_id = match session {
+        Some(state) => state.id,
+        None => store
+            .running_session(now)?
+            .map(|session| session.id)
+            .ok_or_else(|| AppError::NotFound("No pomodoro session is running".to_string()))?,
+    };
+
+    store.add_interruption(
+        session_id,
+        interrupt_args.kind,
+        interrupt_args.note.trim(),
+        now,
+    )?;
+    println!(
+        "Logged an {} interruption of session #{}",
+        String::from(interrupt_args.kind),
+        session_id
+    );
+
+    Ok(())
+}
+
+/// Records a session that ran without the timer, straight to the database.
+fn log_pomodoro(store: &dyn Repository, log_args: PomoLogArgs) -> Result<(), AppError> {
+    log_args.validate()?;
+
+    let mut session = log_args.session_at(Local::now())?;
+    if let Some(task_id) = session.task_id {
+        store
+            .task(task_id)
+            .map_err(|e| AppError::from(e).context(format!("Could not link task #{}", task_id)))?;
+    }
+
+    store.log_session(&mut session)?;
+    print_rows(&vec![Box::new(session) as Box<dyn TableRow>]);
+    Ok(())
+}
+
+fn start_pomodoro(
+    store: &dyn Repository,
+    config: &Config,
+    pomo_task: PomoTask,
+    detach: bool,
+) -> Result<(), AppError> {
     pomo_task.validate()?;
 
+    if let Some(recovered) = daemon::recover()? {
+        println!("{}", recovered);
+    }
+    if daemon::is_running()? {
+        return Err(AppError::Validation(
+            "A pomodoro session is already running in the background, see `tasklog pomo status`"
+                .to_string(),
+        ));
+    }
+
+    let linked_task =
+        match pomo_task.task_id {
+            Some(task_id) => Some(store.task(task_id).map_err(|e| {
+                AppError::from(e).context(format!("Could not link task #{}", task_id))
+            })?),
+            None => None,
+        };
+
     let mut pomo_value = pomo_task;
-    repository::add_pomodoro(&mut pomo_value)?;
+    store.add_session(&mut pomo_value)?;
+
+    let result = if detach {
+        daemon::start(&pomo_value).map(|_| {
+            println!(
+                "Started session #{} in the background, {} left",
+                pomo_value.id,
+                String::from(pomo_value.duration.clone())
+            )
+        })
+    } else {
+        ui::clear_terminal_screen()
+            .map_err(AppError::from)
+            .and_then(|_| control_terminal(store, &mut pomo_value, linked_task.as_ref(), config))
+    };
 
-    helper::clear_terminal_screen()?;
+    // A session that nothing counts down any more is stopped rather than left running.
+    match result {
+        Err(err) if pomo_value.status == PomoStatus::Running => {
+            pomo_value.end_time = Local::now();
+            pomo_value.status = PomoStatus::Paused;
+            Err(match store.update_session(&pomo_value) {
+                Ok(()) => err.context(format!("Stopped session #{}", pomo_value.id)),
+                Err(_) => err.context(format!("Session #{} could not be stopped", pomo_value.id)),
+            })
+        }
+        result => result,
+    }
+}
 
-    control_terminal(&mut pomo_value)
+/// How a countdown on the pomodoro screen ended.
+enum Countdown {
+    Finished,
+    /// Ended early with `s`, straight on to the next session
+    Skipped,
+    Quit,
 }
 
-pub fn control_terminal(pomo_task: &mut PomoTask) -> Result<(), String> {
+pub fn control_terminal(
+    store: &dyn Repository,
+    pomo_task: &mut PomoTask,
+    linked_task: Option<&Task>,
+    config: &Config,
+) -> Result<(), AppError> {
     let mut stdout = stdout();
 
     // --- Setup Terminal ---
-    terminal::enable_raw_mode().map_err(|e| e.to_string())?;
+    terminal::enable_raw_mode()?;
     // Enter alternate screen to keep main terminal clean. Hide cursor.
-    execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide).map_err(|e| e.to_string())?;
+    execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
 
-    let initial_duration = pomo_task.duration.to_time_duration();
-
-    let (term_width, term_height) = terminal::size().map_err(|e| e.to_string())?;
+    let (term_width, term_height) = terminal::size()?;
 
     let mut app_state = AppState {
         title: pomo_task.title.clone(),
+        category: pomo_task.category.clone(),
+        task: linked_task.map(|task| format!("#{} {}", task.id, task.title)),
         term_width,
         term_height,
-        current_time: initial_duration,
+        current_time: pomo_task.duration.to_time_duration(),
+        duration: pomo_task.duration.to_time_duration(),
+        show_help: false,
+        note: None,
+        stopwatch: false,
         quited: false,
     };
 
-    let (time_update_tx, time_update_rx) = mpsc::channel::<Duration>();
+    // The event thread lives for the whole screen, each countdown runs its own timer thread.
     let (event_tx, event_rx) = mpsc::channel::<PomodoroEvent>();
-
-    let (timer_quit_tx, timer_quit_rx) = mpsc::channel::<()>();
     let (event_thread_quit_tx, event_thread_quit_rx) = mpsc::channel::<()>();
 
-    let timer_handle = {
-        let time_update_tx_clone = time_update_tx.clone();
-        thread::spawn(move || {
-            run_timer_thread(initial_duration, time_update_tx_clone, timer_quit_rx);
-        })
-    };
+    let event_handle = thread::spawn(move || {
+        run_event_thread(event_tx, event_thread_quit_rx);
+    });
 
-    let event_handle = {
-        let event_tx_clone = event_tx.clone();
-        thread::spawn(move || {
-            run_event_thread(event_tx_clone, event_thread_quit_rx);
-        })
-    };
+    let mut alert_errors = Vec::new();
+    let result = run_sessions(
+        store,
+        &mut stdout,
+        &mut app_state,
+        &event_rx,
+        pomo_task,
+        config,
+        &mut alert_errors,
+    );
 
-    draw_ui(&mut stdout, &app_state).map_err(|e| e.to_string())?;
+    let _ = event_thread_quit_tx.send(());
+    let _ = event_handle.join();
+
+    // Restore terminal: Leave alternate screen, show cursor.
+    execute!(stdout, terminal::LeaveAlternateScreen, cursor::Show)?;
+
+    terminal::disable_raw_mode()?;
+
+    for err in alert_errors {
+        eprintln!("{}", format_string_with_color(&err, Color::Yellow));
+    }
+
+    result
+}
+
+/// Counts down the session, and the breaks and work sessions chained from the time's up screen,
+/// recording each one as it ends.
+fn run_sessions(
+    store: &dyn Repository,
+    stdout: &mut Stdout,
+    app_state: &mut AppState,
+    event_rx: &Receiver<PomodoroEvent>,
+    pomo_task: &mut PomoTask,
+    config: &Config,
+    alert_errors: &mut Vec<String>,
+) -> Result<(), AppError> {
+    let alerts = config.pomo_alerts();
+    let extension = DurationField(chrono::Duration::minutes(SESSION_EXTENSION_MINUTES));
+    let work_duration = pomo_task.duration.clone();
+    let work_task_id = pomo_task.task_id;
+    let linked_task = app_state.task.clone();
+    let mut remaining = pomo_task.duration.to_time_duration();
 
     loop {
+        app_state.title = match pomo_task.pomo_type {
+            PomoType::Work => pomo_task.title.clone(),
+            PomoType::Rest => format!("Break - {}", pomo_task.title),
+        };
+        // Breaks are not spent on the task.
+        app_state.task = match pomo_task.pomo_type {
+            PomoType::Work => linked_task.clone(),
+            PomoType::Rest => None,
+        };
+        app_state.duration = pomo_task.duration.to_time_duration();
+
+        let outcome = run_countdown(store, stdout, app_state, event_rx, pomo_task, remaining)?;
+        // Time spent on the end screen is not focus, only what was counted down is.
+        let left = chrono::Duration::from_std(app_state.current_time)
+            .map_err(|e| AppError::Validation(e.to_string()))?;
+        pomo_task.elapsed =
+            DurationField((pomo_task.duration.0 - left).max(chrono::Duration::zero()));
+
+        let choice = match outcome {
+            Countdown::Quit => {
+                app_state.quited = true;
+                pomo_task.end_time = Local::now();
+                pomo_task.status = PomoStatus::Paused;
+                return Ok(store.update_session(pomo_task)?);
+            }
+            Countdown::Skipped => SessionEndChoice::Next,
+            Countdown::Finished => {
+                // Recorded before the time's up screen so the goals on it count this session.
+                pomo_task.end_time = Local::now();
+                pomo_task.status = PomoStatus::Finished;
+                store.update_session(pomo_task)?;
+
+                if let Err(err) = fire_pomo_alerts(&alerts, config, pomo_task) {
+                    alert_errors.push(err.to_string());
+                }
+
+                let goals = match store.goal_progress(Local::now().date_naive()) {
+                    Ok(progress) => progress.iter().map(|p| p.summary()).collect(),
+                    Err(err) => {
+                        alert_errors.push(format!("Could not load the goals: {}", err));
+                        Vec::new()
+                    }
+                };
+
+                let choices = SessionEndChoice::prompt(pomo_task.pomo_type, &extension);
+                wait_for_end_choice(
+                    stdout,
+                    app_state,
+                    event_rx,
+                    &goals,
+                    &choices,
+                    alerts.contains(&PomoAlert::Flash),
+                )?
+            }
+        };
+
+        if choice == SessionEndChoice::Extend {
+            pomo_task.duration = DurationField(pomo_task.duration.0 + extension.0);
+            pomo_task.status = PomoStatus::Running;
+            store.update_session(pomo_task)?;
+            remaining = extension.to_time_duration();
+            continue;
+        }
+
+        // The time is up, so leaving from here still counts the session as finished.
+        pomo_task.end_time = Local::now();
+        pomo_task.status = PomoStatus::Finished;
+        store.update_session(pomo_task)?;
+
+        if choice == SessionEndChoice::Done {
+            return Ok(());
+        }
+
+        let (pomo_type, duration, task_id) = match pomo_task.pomo_type {
+            PomoType::Work => (PomoType::Rest, config.pomo_break(), None),
+            PomoType::Rest => (PomoType::Work, work_duration.clone(), work_task_id),
+        };
+
+        let mut next = PomoTask {
+            pomo_type,
+            title: pomo_task.title.clone(),
+            duration,
+            category: pomo_task.category.clone(),
+            task_id,
+            ..Default::default()
+        };
+        store.add_session(&mut next)?;
+
+        remaining = next.duration.to_time_duration();
+        *pomo_task = next;
+    }
+}
+
+/// A timer thread counting down to a deadline, restarted whenever minutes are added or removed.
+struct Timer {
+    deadline: Instant,
+    updates: Receiver<Duration>,
+    quit: Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+impl Timer {
+    fn start(duration: Duration) -> Timer {
+        let deadline = Instant::now() + duration;
+        let (time_update_tx, time_update_rx) = mpsc::channel::<Duration>();
+        let (timer_quit_tx, timer_quit_rx) = mpsc::channel::<()>();
+
+        let handle = thread::spawn(move || {
+            run_timer_thread(deadline, time_update_tx, timer_quit_rx);
+        });
+
+        Timer {
+            deadline,
+            updates: time_update_rx,
+            quit: timer_quit_tx,
+            handle,
+        }
+    }
+
+    fn remaining(&self) -> Duration {
+        self.deadline.saturating_duration_since(Instant::now())
+    }
+
+    fn stop(self) {
+        let _ = self.quit.send(());
+        let _ = self.handle.join();
+    }
+}
+
+/// Counts `duration` down and redraws the screen on every tick until the time is up, the session
+/// is skipped or the user quits. Handles the keys of the countdown on the way: minutes added or
+/// removed change the duration of the session and notes are logged as its interruptions.
+fn run_countdown(
+    store: &dyn Repository,
+    stdout: &mut Stdout,
+    app_state: &mut AppState,
+    event_rx: &Receiver<PomodoroEvent>,
+    pomo_task: &mut PomoTask,
+    duration: Duration,
+) -> Result<Countdown, AppError> {
+    let minute = Duration::from_secs(60);
+    let mut timer = Timer::start(duration);
+
+    app_state.current_time = duration;
+    app_state.note = None;
+    draw_ui(stdout, app_state)?;
+
+    let outcome = loop {
         // Process incoming messages non-blockingly.
         // Order of checking: events first, then time updates.
 
-        // Check for application events (Resize, Quit)
+        // Check for application events (Resize, Quit, keys)
         match event_rx.try_recv() {
-            Ok(PomodoroEvent::Quit) => {
-                app_state.quited = true;
-                break; // Exit main loop.
-            }
+            Ok(PomodoroEvent::Quit) => break Countdown::Quit,
             Ok(PomodoroEvent::Resize(new_width, new_height)) => {
-                println!("Resizing to {}x{}", new_width, new_height);
                 app_state.term_width = new_width;
                 app_state.term_height = new_height;
-                draw_ui(&mut stdout, &app_state)?;
+                draw_ui(stdout, app_state)?;
+            }
+            Ok(PomodoroEvent::Key(key)) if app_state.note.is_some() => {
+                let (_, note) = app_state
+                    .note
+                    .get_or_insert_with(|| (InterruptionKind::Internal, String::new()));
+                match key {
+                    '\n' => {
+                        if let Some((kind, note)) = app_state.note.take() {
+                            if !note.trim().is_empty() {
+                                store.add_interruption(
+                                    pomo_task.id,
+                                    kind,
+                                    note.trim(),
+                                    Local::now(),
+                                )?;
+                            }
+                        }
+                    }
+                    '\x1b' => app_state.note = None,
+                    '\x08' => {
+                        note.pop();
+                    }
+                    c => note.push(c),
+                }
+                draw_ui(stdout, app_state)?;
             }
+            Ok(PomodoroEvent::Key(key)) => match CountdownAction::from_key(key) {
+                Some(CountdownAction::Quit) => break Countdown::Quit,
+                Some(CountdownAction::Skip) => break Countdown::Skipped,
+                Some(CountdownAction::AddMinute) => {
+                    let left = timer.remaining() + minute;
+                    timer.stop();
+                    pomo_task.duration =
+                        DurationField(pomo_task.duration.0 + chrono::Duration::minutes(1));
+                    app_state.duration += minute;
+                    timer = Timer::start(left);
+                }
+                // Never removes the minute still running.
+                Some(CountdownAction::RemoveMinute) if timer.remaining() > minute => {
+                    let left = timer.remaining() - minute;
+                    timer.stop();
+                    pomo_task.duration =
+                        DurationField(pomo_task.duration.0 - chrono::Duration::minutes(1));
+                    app_state.duration -= minute;
+                    timer = Timer::start(left);
+                }
+                Some(CountdownAction::Note(kind)) => {
+                    app_state.note = Some((kind, String::new()));
+                    draw_ui(stdout, app_state)?;
+                }
+                Some(CountdownAction::ToggleHelp) => {
+                    app_state.show_help = !app_state.show_help;
+                    draw_ui(stdout, app_state)?;
+                }
+                Some(CountdownAction::RemoveMinute) | None => {}
+            },
             Err(mpsc::TryRecvError::Empty) => {
                 // No event, continue.
             }
             Err(mpsc::TryRecvError::Disconnected) => {
                 // Event thread terminated, should also quit.
-                app_state.quited = true;
-                break;
+                break Countdown::Quit;
             }
         }
 
         // Check for time updates from the timer thread.
-        match time_update_rx.try_recv() {
+        match timer.updates.try_recv() {
             Ok(new_time) => {
                 app_state.current_time = new_time;
-                draw_ui(&mut stdout, &app_state)?;
+                draw_ui(stdout, app_state)?;
                 if app_state.current_time == Duration::ZERO {
-                    // Optional: Display "Time's up!" message here before breaking.
-                    // For now, just break the loop.
-                    break;
+                    break Countdown::Finished;
                 }
             }
             Err(mpsc::TryRecvError::Empty) => {
                 // No time update, continue.
             }
             Err(mpsc::TryRecvError::Disconnected) => {
-                // Timer thread terminated. If not due to time up, this might be an issue.
-                // If time is already zero, this is expected.
-                if app_state.current_time != Duration::ZERO {
-                    // Timer thread died unexpectedly.
-                }
-                break;
+                // Timer thread terminated without reaching zero.
+                break Countdown::Quit;
             }
         }
 
         // Small sleep to prevent main loop from busy-waiting excessively.
         // This also determines the responsiveness to channel messages.
         thread::sleep(Duration::from_millis(50));
+    };
+
+    // The exact time left, not the last whole second shown.
+    app_state.current_time = match outcome {
+        Countdown::Finished => Duration::ZERO,
+        _ => timer.remaining(),
+    };
+    timer.stop();
+
+    Ok(outcome)
+}
+
+/// Rings the bell and runs the hook command of a finished session, flashing is left to the time's
+/// up screen.
+fn fire_pomo_alerts(
+    alerts: &[PomoAlert],
+    config: &Config,
+    pomo_task: &PomoTask,
+) -> Result<(), AppError> {
+    if alerts.contains(&PomoAlert::Bell) {
+        let mut stdout = stdout();
+        write!(stdout, "\x07").and_then(|_| stdout.flush())?;
     }
 
-    let _ = timer_quit_tx.send(());
-    let _ = event_thread_quit_tx.send(());
+    if alerts.contains(&PomoAlert::Command) {
+        let command = config.pomo_alert_command.as_ref().ok_or_else(|| {
+            AppError::Validation(
+                "the command alert needs the pomo_alert_command setting".to_string(),
+            )
+        })?;
 
-    // Wait for threads to finish (optional but good practice).
-    let _ = timer_handle.join();
-    let _ = event_handle.join();
+        let (summary, body) = notify::pomo_message(pomo_task);
+        notify::spawn_hook(command, &summary, &body)?;
+    }
 
-    // Restore terminal: Leave alternate screen, show cursor.
-    execute!(stdout, terminal::LeaveAlternateScreen, cursor::Show).map_err(|e| e.to_string())?;
+    Ok(())
+}
 
-    terminal::disable_raw_mode().map_err(|e| e.to_string())?;
+/// Shows the time's up screen until one of the choices is picked, quitting from it counts as done.
+fn wait_for_end_choice(
+    stdout: &mut Stdout,
+    app_state: &mut AppState,
+    event_rx: &Receiver<PomodoroEvent>,
+    goals: &[String],
+    choices: &str,
+    flash: bool,
+) -> Result<SessionEndChoice, AppError> {
+    let mut flashes = if flash { FLASH_TOGGLES } else { 0 };
+    let mut inverted = false;
 
-    pomo_task.end_time = Local::now();
+    draw_end_ui(stdout, app_state, goals, choices, inverted)?;
 
-    if app_state.quited {
-        pomo_task.status = PomoStatus::Paused;
-    } else {
-        pomo_task.status = PomoStatus::Finished
+    loop {
+        match event_rx.recv_timeout(Duration::from_millis(FLASH_INTERVAL_MILLIS)) {
+            Ok(PomodoroEvent::Key(key)) => {
+                if let Some(choice) = SessionEndChoice::from_key(key) {
+                    return Ok(choice);
+                }
+            }
+            Ok(PomodoroEvent::Resize(new_width, new_height)) => {
+                app_state.term_width = new_width;
+                app_state.term_height = new_height;
+                draw_end_ui(stdout, app_state, goals, choices, inverted)?;
+            }
+            Ok(PomodoroEvent::Quit) | Err(mpsc::RecvTimeoutError::Disconnected) => {
+                return Ok(SessionEndChoice::Done);
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) if flashes > 0 => {
+                flashes -= 1;
+                inverted = !inverted;
+                draw_end_ui(stdout, app_state, goals, choices, inverted)?;
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tasklog::memory::MemoryStore;
+    use tasklog::models::{
+        GoalAddArgs, GoalMetric, GoalPeriod, GoalRemoveArgs, InterruptionKind, Priority,
+        RecordMeta, ReportFormat, ReportPeriod, TaskRef,
+    };
+
+    fn add_task(store: &dyn Repository, title: &str) -> u64 {
+        let mut task = Task {
+            title: title.to_string(),
+            ..Default::default()
+        };
+        store.add_task(&mut task).unwrap();
+        task.id
+    }
+
+    #[test]
+    fn test_handle_ls_empty() {
+        let store = MemoryStore::new();
+        let args = LSArgs {
+            limit: 50,
+            days: 1,
+            category: None,
+            priority: None,
+            status: None,
+            ls_type: LSType::Task,
+        };
+
+        // Nothing to list is no error.
+        handle_ls(&store, &args).unwrap();
+        handle_ls(
+            &store,
+            &LSArgs {
+                ls_type: LSType::Pomo,
+                ..args
+            },
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_handle_done() {
+        let store = MemoryStore::new();
+        let id = add_task(&store, "Ship the release");
+
+        handle_done(
+            &store,
+            DoneArgs {
+                id: TaskRef::Id(id),
+            },
+        )
+        .unwrap();
+        assert_eq!(store.task(id).unwrap().status, TaskStatus::Done);
+
+        let done_twice = handle_done(
+            &store,
+            DoneArgs {
+                id: TaskRef::Id(id),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(done_twice.exit_code(), 2);
+        let missing = handle_done(
+            &store,
+            DoneArgs {
+                id: TaskRef::Id(42),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(missing.exit_code(), 3);
+
+        // A prefix of the uuid names the task as well as its id.
+        let other = add_task(&store, "Write the changelog");
+        let prefix = store.task(other).unwrap().meta.short_uuid(8).to_string();
+        handle_done(
+            &store,
+            DoneArgs {
+                id: prefix.parse().unwrap(),
+            },
+        )
+        .unwrap();
+        assert_eq!(store.task(other).unwrap().status, TaskStatus::Done);
+
+        // The prefix `ls` shows works even when it starts with digits alone.
+        let mut digits = Task {
+            title: "Tag the release".to_string(),
+            meta: RecordMeta {
+                uuid: "31532004-1a2b-4c3d-8e4f-000000000000".to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        store.add_task(&mut digits).unwrap();
+        let listed = ListedTask {
+            task: store.task(digits.id).unwrap(),
+            uuid_len: store.uuid_prefix_len().unwrap(),
+        };
+        handle_done(
+            &store,
+            DoneArgs {
+                id: listed.row()[1].parse().unwrap(),
+            },
+        )
+        .unwrap();
+        assert_eq!(store.task(digits.id).unwrap().status, TaskStatus::Done);
+    }
+
+    #[test]
+    fn test_handle_edit() {
+        let store = MemoryStore::new();
+        let id = add_task(&store, "Ship the release");
+        let meta = store.task(id).unwrap().meta;
+        let uuid = meta.uuid.clone();
+        let edit = |id: TaskRef| EditArgs {
+            id,
+            title: None,
+            due_date: None,
+            priority: None,
+            category: None,
+            remind: None,
+        };
+
+        handle_edit(
+            &store,
+            EditArgs {
+                title: Some("Ship the 1.0 release".to_string()),
+                priority: Some(Priority::High),
+                ..edit(meta.short_uuid(6).parse().unwrap())
+            },
+        )
+        .unwrap();
+        let edited = store.task(id).unwrap();
+        assert_eq!(edited.title, "Ship the 1.0 release");
+        assert_eq!(edited.priority, Priority::High);
+        assert_eq!(edited.meta.uuid, uuid);
+
+        let nothing = handle_edit(&store, edit(TaskRef::Id(id))).unwrap_err();
+        assert_eq!(nothing.exit_code(), 2);
+
+        let unknown = if uuid.starts_with("ffff") {
+            "0000"
+        } else {
+            "ffff"
+        };
+        let missing = handle_edit(
+            &store,
+            EditArgs {
+                title: Some("Nothing".to_string()),
+                ..edit(unknown.parse().unwrap())
+            },
+        )
+        .unwrap_err();
+        assert_eq!(missing.exit_code(), 3);
+    }
+
+    #[test]
+    fn test_handle_import_and_export() {
+        let store = MemoryStore::new();
+        let dir = std::env::temp_dir().join(format!("tasklog-todotxt-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("todo.txt");
+        fs::write(&file, "(A) Call mom +family @phone\nx Pay rent\n").unwrap();
+
+        let import = || ImportArgs {
+            from: TaskFormat::Todotxt,
+            file: file.clone(),
+        };
+        handle_import(&store, import()).unwrap();
+        let tasks = store.all_tasks().unwrap();
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].priority, Priority::Urgent);
+        assert_eq!(tasks[0].tags, vec!["phone"]);
+        assert_eq!(tasks[1].status, TaskStatus::Done);
+
+        // Exported lines carry the uuids, importing them again adds nothing.
+        handle_export(
+            &store,
+            ExportArgs {
+                to: TaskFormat::Todotxt,
+                output: Some(file.clone()),
+            },
+        )
+        .unwrap();
+        handle_import(&store, import()).unwrap();
+        assert_eq!(store.all_tasks().unwrap().len(), 2);
+
+        fs::write(&file, "Call due:soon\n").unwrap();
+        assert_eq!(handle_import(&store, import()).unwrap_err().exit_code(), 6);
+
+        fs::remove_dir_all(dir).unwrap();
     }
 
-    repository::update_pomodoro(pomo_task)
+    #[test]
+    fn test_handle_goals() {
+        let store = MemoryStore::new();
+        let add = |target: &str| {
+            handle_goals(
+                &store,
+                GoalsArgs {
+                    command: Some(GoalsCommand::Add(GoalAddArgs {
+                        metric: GoalMetric::Focus,
+                        target: target.to_string(),
+                        per: GoalPeriod::Day,
+                    })),
+                },
+            )
+        };
+        let remove = |id: u64| {
+            handle_goals(
+                &store,
+                GoalsArgs {
+                    command: Some(GoalsCommand::Remove(GoalRemoveArgs { id })),
+                },
+            )
+        };
+
+        add("2h").unwrap();
+        assert!(add("none").is_err());
+        let progress = store.goal_progress(Local::now().date_naive()).unwrap();
+        assert_eq!(progress.len(), 1);
+        assert_eq!(progress[0].goal.target, 2 * 60 * 60);
+
+        remove(progress[0].goal.id).unwrap();
+        assert!(remove(progress[0].goal.id).is_err());
+    }
+
+    #[test]
+    fn test_handle_track_links_the_task() {
+        let store = MemoryStore::new();
+        let id = add_task(&store, "Write the docs");
+        let start = |task_id: u64| {
+            handle_track(
+                &store,
+                TrackArgs {
+                    command: TrackCommand::Start(TrackStartArgs {
+                        task_id: Some(task_id),
+                        title: None,
+                        category: None,
+                        detach: true,
+                    }),
+                },
+            )
+        };
+
+        assert!(start(id + 1).is_err());
+        start(id).unwrap();
+        assert_eq!(
+            store.running_time_entry().unwrap().unwrap().title,
+            "Write the docs"
+        );
+        assert!(start(id).is_err());
+
+        let stop = TrackArgs {
+            command: TrackCommand::Stop,
+        };
+        handle_track(&store, stop).unwrap();
+        assert!(store.running_time_entry().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_handle_pomodoro_interrupt() {
+        let store = MemoryStore::new();
+        let interrupt = |store: &MemoryStore| {
+            let interrupt_args = InterruptArgs {
+                kind: InterruptionKind::External,
+                note: "Phone call".to_string(),
+            };
+            let pomo_args = PomoArgs {
+                command: Some(PomoCommand::Interrupt(interrupt_args)),
+                session: None,
+            };
+            handle_pomodoro(store, &Config::default(), None, pomo_args)
+        };
+
+        // No daemon session and none in a terminal either.
+        assert_eq!(interrupt(&store).unwrap_err().exit_code(), 3);
+
+        let mut session = PomoTask {
+            title: "Write the docs".to_string(),
+            ..Default::default()
+        };
+        store.add_session(&mut session).unwrap();
+        interrupt(&store).unwrap();
+    }
+
+    #[test]
+    fn test_handle_report_in_memory() {
+        // The analyses are SQLite's own, tested against a database in memory.
+        let store = TaskStore::in_memory().unwrap();
+        add_task(&store, "Write the docs");
+        let report_args = ReportArgs {
+            period: ReportPeriod::Week,
+            format: ReportFormat::Md,
+            output: None,
+        };
+
+        handle_report(&store, report_args).unwrap();
+    }
 }
@@ -0,0 +1,21 @@
+//! Tasks, pomodoro sessions, goals and time tracking kept in a local SQLite database.
+//!
+//! [`TaskStore`] is the entry point, the `tasklog` binary is a thin command line over it.
+
+pub mod config;
+pub mod daemon;
+pub mod error;
+mod helper;
+pub mod memory;
+pub mod models;
+pub mod notify;
+pub mod prompt;
+pub mod report;
+mod repository;
+pub mod store;
+pub mod sync;
+pub mod timesheet;
+pub mod todotxt;
+
+pub use error::{AppError, StoreError};
+pub use store::{Analytics, Repository, TaskStore};
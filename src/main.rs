@@ -1,8 +1,6 @@
 mod handlers;
-mod helper;
-mod models;
 mod parser;
-mod repository;
+mod ui;
 
 use crate::parser::execute;
 
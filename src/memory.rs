@@ -0,0 +1,583 @@
+use crate::error::StoreError;
+use crate::models::{
+    new_uuid, unique_prefix_len, Goal, GoalMetric, GoalProgress, InterruptionKind, LSArgs,
+    PomoStatus, PomoTask, PomoType, RecordMeta, Task, TaskStatus, TimeEntry,
+};
+use crate::store::Repository;
+use chrono::{DateTime, Local, NaiveDate};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+/// A backend keeping everything in memory, handy for tests and dry runs. It answers what
+/// [`Repository`] asks like the SQLite backend does. It does not implement
+/// [`Analytics`](crate::store::Analytics), use [`TaskStore::in_memory`](crate::TaskStore::in_memory)
+/// for those.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    records: RefCell<Records>,
+}
+
+#[derive(Debug, Default)]
+struct Records {
+    tasks: Vec<StoredTask>,
+    sessions: Vec<StoredSession>,
+    /// Session id, kind, note and when it was noted
+    interruptions: Vec<(u64, InterruptionKind, String, DateTime<Local>)>,
+    goals: Vec<Goal>,
+    time_entries: Vec<TimeEntry>,
+}
+
+/// A task with the bookkeeping the SQLite backend keeps in columns `Task` has no field for.
+#[derive(Debug)]
+struct StoredTask {
+    task: Task,
+    completed_at: Option<DateTime<Local>>,
+    reminded_at: Option<DateTime<Local>>,
+}
+
+#[derive(Debug)]
+struct StoredSession {
+    session: PomoTask,
+    /// Sessions still running have no end yet
+    ended: bool,
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+}
+
+impl StoredSession {
+    /// Mirrors the SQLite backend, where a running session reads as ending after its elapsed time.
+    fn read(&self) -> PomoTask {
+        let mut session = self.session.clone();
+        if !self.ended {
+            session.end_time = session.elapsed.add_date(&session.start_time);
+        }
+        session
+    }
+
+    /// When the session ends, or would end once its time runs out.
+    fn ends_at(&self) -> DateTime<Local> {
+        match self.ended {
+            true => self.session.end_time,
+            false => self.session.duration.add_date(&self.session.start_time),
+        }
+    }
+}
+
+fn next_id(ids: impl Iterator<Item = u64>) -> u64 {
+    ids.max().unwrap_or(0) + 1
+}
+
+fn new_meta() -> RecordMeta {
+    let now = Local::now();
+    RecordMeta {
+        uuid: new_uuid(),
+        created_at: Some(now),
+        updated_at: Some(now),
+    }
+}
+
+/// Moves `updated_at` as the triggers of the SQLite backend do.
+fn touch(meta: &mut RecordMeta) {
+    meta.updated_at = Some(Local::now());
+}
+
+impl Repository for MemoryStore {
+    fn tasks(
+        &self,
+        filters: &LSArgs,
+        due_before: DateTime<Local>,
+    ) -> Result<Vec<Task>, StoreError> {
+        let records = self.records.borrow();
+
+        Ok(records
+            .tasks
+            .iter()
+            .map(|stored| &stored.task)
+            .filter(|task| task.due_date.at < due_before)
+            .filter(|task| filters.priority.is_none_or(|p| task.priority == p))
+            .filter(|task| {
+                filters
+                    .category
+                    .as_ref()
+                    .is_none_or(|c| task.category.as_ref() == Some(c))
+            })
+            .filter(|task| match filters.status {
+                None => task.status == TaskStatus::Open,
+                Some(TaskStatus::All) => true,
+                Some(status) => task.status == status,
+            })
+            .take(filters.limit)
+            .cloned()
+            .collect())
+    }
+
+    fn add_task(&self, task: &mut Task) -> Result<(), StoreError> {
+        let mut records = self.records.borrow_mut();
+
+        task.id = next_id(records.tasks.iter().map(|stored| stored.task.id));
+        task.meta = RecordMeta {
+            uuid: match task.meta.uuid.as_str() {
+                "" => new_uuid(),
+                uuid => uuid.to_lowercase(),
+            },
+            ..new_meta()
+        };
+        records.tasks.push(StoredTask {
+            task: task.clone(),
+            completed_at: (task.status == TaskStatus::Done).then(Local::now),
+            reminded_at: None,
+        });
+
+        Ok(())
+    }
+
+    fn all_tasks(&self) -> Result<Vec<Task>, StoreError> {
+        let records = self.records.borrow();
+
+        Ok(records
+            .tasks
+            .iter()
+            .map(|stored| stored.task.clone())
+            .collect())
+    }
+
+    fn task(&self, task_id: u64) -> Result<Task, StoreError> {
+        self.records
+            .borrow()
+            .tasks
+            .iter()
+            .find(|stored| stored.task.id == task_id)
+            .map(|stored| stored.task.clone())
+            .ok_or(StoreError::NotFound {
+                record: "task",
+                id: task_id,
+            })
+    }
+
+    fn task_by_uuid(&self, prefix: &str) -> Result<Task, StoreError> {
+        let prefix = prefix.to_lowercase();
+        let records = self.records.borrow();
+        let mut matching = records
+            .tasks
+            .iter()
+            .filter(|stored| stored.task.meta.uuid.starts_with(&prefix));
+
+        match (matching.next(), matching.next()) {
+            (None, _) => Err(StoreError::UnknownUuid {
+                record: "task",
+                prefix,
+            }),
+            (Some(stored), None) => Ok(stored.task.clone()),
+            (Some(_), Some(_)) => Err(StoreError::Invalid(format!(
+                "The uuid prefix {} matches more than one task, give more of it",
+                prefix
+            ))),
+        }
+    }
+
+    fn uuid_prefix_len(&self) -> Result<usize, StoreError> {
+        let records = self.records.borrow();
+        let mut uuids: Vec<String> = records
+            .tasks
+            .iter()
+            .map(|stored| stored.task.meta.uuid.clone())
+            .collect();
+        uuids.sort();
+
+        Ok(unique_prefix_len(&uuids))
+    }
+
+    fn update_task(&self, task: &Task) -> Result<(), StoreError> {
+        let mut records = self.records.borrow_mut();
+        let stored = records
+            .tasks
+            .iter_mut()
+            .find(|stored| stored.task.id == task.id)
+            .ok_or(StoreError::NotFound {
+                record: "task",
+                id: task.id,
+            })?;
+
+        if stored.task.remind_at() != task.remind_at() {
+            stored.reminded_at = None;
+        }
+        stored.completed_at = match task.status {
+            TaskStatus::Done => stored.completed_at.or(Some(Local::now())),
+            _ => None,
+        };
+        stored.task = Task {
+            id: stored.task.id,
+            meta: stored.task.meta.clone(),
+            ..task.clone()
+        };
+        touch(&mut stored.task.meta);
+        Ok(())
+    }
+
+    fn complete_task(&self, task_id: u64) -> Result<(), StoreError> {
+        let mut records = self.records.borrow_mut();
+        let stored = records
+            .tasks
+            .iter_mut()
+            .find(|stored| stored.task.id == task_id)
+            .ok_or(StoreError::NotFound {
+                record: "task",
+                id: task_id,
+            })?;
+
+        stored.task.status = TaskStatus::Done;
+        stored.completed_at = Some(Local::now());
+        touch(&mut stored.task.meta);
+        Ok(())
+    }
+
+    fn due_reminders(&self, now: DateTime<Local>) -> Result<Vec<Task>, StoreError> {
+        let records = self.records.borrow();
+
+        let mut due: Vec<&Task> = records
+            .tasks
+            .iter()
+            .filter(|stored| stored.task.status == TaskStatus::Open && stored.reminded_at.is_none())
+            .map(|stored| &stored.task)
+            .filter(|task| task.remind_at().is_some_and(|at| at <= now))
+            .collect();
+        due.sort_by_key(|task| task.remind_at());
+
+        Ok(due.into_iter().cloned().collect())
+    }
+
+    fn mark_reminded(&self, task_id: u64, reminded_at: DateTime<Local>) -> Result<(), StoreError> {
+        let mut records = self.records.borrow_mut();
+        if let Some(stored) = records
+            .tasks
+            .iter_mut()
+            .find(|stored| stored.task.id == task_id)
+        {
+            stored.reminded_at = Some(reminded_at);
+            touch(&mut stored.task.meta);
+        }
+
+        Ok(())
+    }
+
+    fn prompt_status(
+        &self,
+        now: DateTime<Local>,
+    ) -> Result<(u64, u64, Option<PomoTask>), StoreError> {
+        let (open, overdue) = {
+            let records = self.records.borrow();
+            records
+                .tasks
+                .iter()
+                .filter(|stored| stored.task.status == TaskStatus::Open)
+                .fold((0, 0), |(open, overdue), stored| {
+                    (open + 1, overdue + (stored.task.due_date.at < now) as u64)
+                })
+        };
+
+        Ok((open, overdue, self.running_session(now)?))
+    }
+
+    fn sessions(&self, limit: usize) -> Result<Vec<PomoTask>, StoreError> {
+        let records = self.records.borrow();
+
+        let mut sessions: Vec<PomoTask> = records.sessions.iter().map(|s| s.read()).collect();
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.start_time));
+        sessions.truncate(limit);
+
+        Ok(sessions)
+    }
+
+    fn add_session(&self, session: &mut PomoTask) -> Result<(), StoreError> {
+        let mut records = self.records.borrow_mut();
+
+        session.id = next_id(records.sessions.iter().map(|s| s.session.id));
+        session.start_time = Local::now();
+        session.meta = new_meta();
+        records.sessions.push(StoredSession {
+            session: session.clone(),
+            ended: false,
+        });
+
+        Ok(())
+    }
+
+    fn update_session(&self, session: &PomoTask) -> Result<(), StoreError> {
+        let mut records = self.records.borrow_mut();
+        if let Some(stored) = records
+            .sessions
+            .iter_mut()
+            .find(|s| s.session.id == session.id)
+        {
+            stored.session.status = session.status.clone();
+            stored.session.duration = session.duration.clone();
+            stored.session.elapsed = session.elapsed.clone();
+            stored.session.end_time = session.end_time;
+            stored.ended = true;
+            touch(&mut stored.session.meta);
+        }
+
+        Ok(())
+    }
+
+    fn log_session(&self, session: &mut PomoTask) -> Result<(), StoreError> {
+        let mut records = self.records.borrow_mut();
+
+        let overlapping = records
+            .sessions
+            .iter()
+            .filter(|s| s.session.start_time < session.end_time && s.ends_at() > session.start_time)
+            .min_by_key(|s| s.session.start_time);
+        if let Some(other) = overlapping {
+            return Err(StoreError::Conflict(format!(
+                "The session overlaps session #{} ({})",
+                other.session.id, other.session.title
+            )));
+        }
+
+        session.id = next_id(records.sessions.iter().map(|s| s.session.id));
+        session.meta = new_meta();
+        records.sessions.push(StoredSession {
+            session: session.clone(),
+            ended: true,
+        });
+
+        Ok(())
+    }
+
+    fn running_session(&self, now: DateTime<Local>) -> Result<Option<PomoTask>, StoreError> {
+        let records = self.records.borrow();
+
+        Ok(records
+            .sessions
+            .iter()
+            .map(|s| &s.session)
+            .filter(|s| {
+                s.status == PomoStatus::Running && s.duration.add_date(&s.start_time) >= now
+            })
+            .max_by_key(|s| s.start_time)
+            .cloned())
+    }
+
+    fn add_interruption(
+        &self,
+        session_id: u64,
+        kind: InterruptionKind,
+        note: &str,
+        created_at: DateTime<Local>,
+    ) -> Result<(), StoreError> {
+        self.records.borrow_mut().interruptions.push((
+            session_id,
+            kind,
+            note.to_string(),
+            created_at,
+        ));
+
+        Ok(())
+    }
+
+    fn add_goal(&self, goal: &mut Goal) -> Result<(), StoreError> {
+        let mut records = self.records.borrow_mut();
+
+        goal.id = next_id(records.goals.iter().map(|g| g.id));
+        records.goals.push(goal.clone());
+
+        Ok(())
+    }
+
+    fn remove_goal(&self, goal_id: u64) -> Result<(), StoreError> {
+        let mut records = self.records.borrow_mut();
+        let before = records.goals.len();
+        records.goals.retain(|g| g.id != goal_id);
+
+        if records.goals.len() == before {
+            return Err(StoreError::NotFound {
+                record: "goal",
+                id: goal_id,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn goal_progress(&self, today: NaiveDate) -> Result<Vec<GoalProgress>, StoreError> {
+        let records = self.records.borrow();
+
+        Ok(records
+            .goals
+            .iter()
+            .map(|goal| {
+                let daily = records.daily_totals(goal.metric);
+                GoalProgress::from_daily(goal.clone(), &daily, today)
+            })
+            .collect())
+    }
+
+    fn start_time_entry(&self, entry: &mut TimeEntry) -> Result<(), StoreError> {
+        if let Some(running) = self.running_time_entry()? {
+            return Err(StoreError::Conflict(format!(
+                "Time entry #{} ({}) is still running, stop it with `tasklog track stop`",
+                running.id, running.title
+            )));
+        }
+
+        let mut records = self.records.borrow_mut();
+        entry.id = next_id(records.time_entries.iter().map(|e| e.id));
+        records.time_entries.push(entry.clone());
+
+        Ok(())
+    }
+
+    fn running_time_entry(&self) -> Result<Option<TimeEntry>, StoreError> {
+        Ok(self
+            .records
+            .borrow()
+            .time_entries
+            .iter()
+            .filter(|e| e.end_time.is_none())
+            .max_by_key(|e| e.start_time)
+            .cloned())
+    }
+
+    fn finish_time_entry(
+        &self,
+        entry: &mut TimeEntry,
+        end_time: DateTime<Local>,
+    ) -> Result<(), StoreError> {
+        let mut records = self.records.borrow_mut();
+        match records
+            .time_entries
+            .iter_mut()
+            .find(|e| e.id == entry.id && e.end_time.is_none())
+        {
+            Some(stored) => stored.end_time = Some(end_time),
+            None => {
+                return Err(StoreError::Conflict(format!(
+                    "Time entry #{} was already stopped",
+                    entry.id
+                )))
+            }
+        }
+
+        entry.end_time = Some(end_time);
+        Ok(())
+    }
+}
+
+impl Records {
+    /// The metric's total of every local day that has any, oldest first.
+    fn daily_totals(&self, metric: GoalMetric) -> Vec<(NaiveDate, i64)> {
+        let mut daily: BTreeMap<NaiveDate, i64> = BTreeMap::new();
+        let work = self
+            .sessions
+            .iter()
+            .map(|s| &s.session)
+            .filter(|s| s.pomo_type == PomoType::Work);
+
+        match metric {
+            GoalMetric::Pomodoros => {
+                for session in work.filter(|s| s.status == PomoStatus::Finished) {
+                    *daily.entry(session.start_time.date_naive()).or_default() += 1;
+                }
+            }
+            GoalMetric::Focus => {
+                for session in work.filter(|s| s.elapsed.to_i64() > 0) {
+                    *daily.entry(session.start_time.date_naive()).or_default() +=
+                        session.elapsed.to_i64();
+                }
+            }
+            GoalMetric::Tasks => {
+                for stored in &self.tasks {
+                    if let (TaskStatus::Done, Some(at)) = (stored.task.status, stored.completed_at)
+                    {
+                        *daily.entry(at.date_naive()).or_default() += 1;
+                    }
+                }
+            }
+        }
+
+        daily.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{DurationField, GoalPeriod};
+    use chrono::Duration;
+
+    #[test]
+    fn test_logged_sessions_do_not_overlap() {
+        let store = MemoryStore::new();
+        let start = Local::now() - Duration::hours(3);
+        let mut first = PomoTask {
+            title: "Review".to_string(),
+            start_time: start,
+            end_time: start + Duration::minutes(25),
+            duration: DurationField::from_i64(25 * 60),
+            elapsed: DurationField::from_i64(25 * 60),
+            status: PomoStatus::Finished,
+            ..Default::default()
+        };
+        store.log_session(&mut first).unwrap();
+
+        let mut clash = PomoTask {
+            start_time: start + Duration::minutes(20),
+            end_time: start + Duration::minutes(45),
+            ..first.clone()
+        };
+        assert!(matches!(
+            store.log_session(&mut clash),
+            Err(StoreError::Conflict(_))
+        ));
+
+        let mut after = PomoTask {
+            start_time: start + Duration::minutes(25),
+            end_time: start + Duration::minutes(50),
+            ..first.clone()
+        };
+        store.log_session(&mut after).unwrap();
+        assert_eq!(after.id, 2);
+        assert_eq!(store.sessions(10).unwrap()[0].id, 2);
+    }
+
+    #[test]
+    fn test_goal_progress_counts_finished_work() {
+        let store = MemoryStore::new();
+        let mut goal = Goal {
+            id: 0,
+            metric: GoalMetric::Pomodoros,
+            period: GoalPeriod::Day,
+            target: 2,
+        };
+        store.add_goal(&mut goal).unwrap();
+
+        for status in [
+            PomoStatus::Finished,
+            PomoStatus::Paused,
+            PomoStatus::Finished,
+        ] {
+            let mut session = PomoTask::default();
+            store.add_session(&mut session).unwrap();
+            session.status = status;
+            store.update_session(&session).unwrap();
+        }
+
+        let progress = store.goal_progress(Local::now().date_naive()).unwrap();
+        assert_eq!(progress[0].current, 2);
+
+        store.remove_goal(goal.id).unwrap();
+        assert!(store
+            .goal_progress(Local::now().date_naive())
+            .unwrap()
+            .is_empty());
+        assert!(matches!(
+            store.remove_goal(goal.id),
+            Err(StoreError::NotFound { record: "goal", .. })
+        ));
+    }
+}
@@ -1,10 +1,18 @@
-use chrono::{DateTime, Datelike, Duration, Local, NaiveDate};
+use crate::config::home_timezone;
+use crate::error::AppError;
+use chrono::{
+    DateTime, Datelike, Days, Duration, Local, Months, NaiveDate, NaiveDateTime, NaiveTime,
+    TimeZone, Utc, Weekday,
+};
 use clap::{Args, Parser, Subcommand, ValueEnum};
 use std::fmt::{Debug, Display, Formatter};
 use std::str::FromStr;
 
+/// Cells of the progress bars of the `goals` table
+const GOAL_BAR_WIDTH: usize = 20;
+
 pub trait CommandArgs {
-    fn validate(&self) -> Result<(), String>;
+    fn validate(&self) -> Result<(), AppError>;
 }
 
 pub trait TableRow {
@@ -18,8 +26,14 @@ pub trait TableRow {
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+
+    /// Show what caused an error and how it was classified
+    #[arg(short, long, global = true)]
+    pub verbose: bool,
 }
 
+// Parsed once per run, the size of the largest variant does not matter.
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Subcommand)]
 pub enum Commands {
     /// Initializes the database and required files
@@ -33,22 +47,222 @@ pub enum Commands {
     Analyze(AnalyzeArgs),
     /// Mark a task as done
     Done(DoneArgs),
+    /// Show everything kept about a task
+    Show(ShowArgs),
+    /// Change the title, due date, priority, category or reminder of a task
+    Edit(EditArgs),
     /// add pomodoro sessions
     #[command(visible_alias = "pm")]
-    Pomo(PomoTask),
+    Pomo(PomoArgs),
+    /// Generate a markdown or html status report for a period
+    Report(ReportArgs),
+    /// Fire the due task reminders, meant to run from cron or a systemd timer
+    Notify(NotifyArgs),
+    /// Print the running pomodoro and open task count for a shell prompt or status bar
+    Prompt(PromptArgs),
+    /// Show the progress and streaks of your focus and task goals
+    Goals(GoalsArgs),
+    /// Track open-ended time against a task with a stopwatch
+    Track(TrackArgs),
+    /// Export the tracked hours per day, category and task as csv or markdown
+    Timesheet(TimesheetArgs),
+    /// Check the database for problems and optionally repair them
+    Doctor(DoctorArgs),
+    /// Copy the database to a file while it is in use
+    Backup(BackupArgs),
+    /// Replace the database with a backup, keeping a snapshot of the current one
+    Restore(RestoreArgs),
+    /// Merge the tasks and sessions with other machines through a git repository, or the tasks
+    /// with a todo.txt file
+    Sync(SyncArgs),
+    /// Add the tasks of a file in another format
+    Import(ImportArgs),
+    /// Write every task to a file in another format
+    Export(ExportArgs),
 }
 
 #[derive(Debug, Args)]
 pub struct DoneArgs {
     #[arg(long, short)]
-    /// Task Id to mark as done
-    pub id: usize,
+    /// Task Id, or a unique prefix of its uuid, to mark as done
+    pub id: TaskRef,
+}
+
+#[derive(Debug, Args)]
+pub struct ShowArgs {
+    /// Task Id or a unique prefix of its uuid
+    pub id: TaskRef,
+}
+
+#[derive(Debug, Args)]
+pub struct EditArgs {
+    /// Task Id or a unique prefix of its uuid
+    pub id: TaskRef,
+    /// New title
+    #[arg(short = 't', long)]
+    pub title: Option<String>,
+    /// New due date, in any form `add` takes
+    #[arg(short, long = "due-date", value_parser = parse_due)]
+    pub due_date: Option<DueDate>,
+    #[arg(short = 'p', long, value_enum)]
+    pub priority: Option<Priority>,
+    /// New category, an empty one removes it
+    #[arg(short = 'c', long)]
+    pub category: Option<String>,
+    /// New reminder, in any form `add` takes
+    #[arg(short = 'r', long, value_parser = parse_remind)]
+    pub remind: Option<Remind>,
+}
+
+impl CommandArgs for EditArgs {
+    fn validate(&self) -> Result<(), AppError> {
+        if self.title.is_none()
+            && self.due_date.is_none()
+            && self.priority.is_none()
+            && self.category.is_none()
+            && self.remind.is_none()
+        {
+            return Err(AppError::Validation("Nothing to change, give at least one of --title, --due-date, --priority, --category or --remind".to_string()));
+        }
+        if self.title.as_ref().is_some_and(|t| t.trim().is_empty()) {
+            return Err(AppError::Validation("Title cannot be empty".to_string()));
+        }
+        Ok(())
+    }
+}
+
+impl EditArgs {
+    /// The task with the changes applied.
+    pub fn apply(&self, task: &Task) -> Task {
+        let mut edited = task.clone();
+        if let Some(title) = &self.title {
+            edited.title = title.trim().to_string();
+        }
+        if let Some(due_date) = self.due_date {
+            edited.due_date = due_date;
+        }
+        if let Some(priority) = self.priority {
+            edited.priority = priority;
+        }
+        if let Some(category) = &self.category {
+            edited.category = Some(category.trim().to_string()).filter(|c| !c.is_empty());
+        }
+        if let Some(remind) = self.remind {
+            edited.remind = Some(remind);
+        }
+        edited
+    }
+}
+
+/// Shortest uuid prefix `TaskRef` takes, shorter ones would match too many tasks to be useful
+pub const MIN_UUID_PREFIX: usize = 4;
+/// Shortest uuid prefix shown, even when fewer characters would tell the tasks apart
+const SHORT_UUID_LEN: usize = 6;
+
+/// A task named on the command line, by its id or by a prefix of its uuid. Only digits make an
+/// id, anything else is taken as a uuid prefix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaskRef {
+    Id(u64),
+    Uuid(String),
+}
+
+impl FromStr for TaskRef {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        if let Ok(id) = s.parse::<u64>() {
+            return Ok(TaskRef::Id(id));
+        }
+
+        if s.len() < MIN_UUID_PREFIX || !s.chars().all(|c| c.is_ascii_hexdigit() || c == '-') {
+            return Err(AppError::Validation(format!(
+                "'{}' is neither a task id nor {} or more characters of a uuid",
+                s, MIN_UUID_PREFIX
+            )));
+        }
+        Ok(TaskRef::Uuid(s))
+    }
+}
+
+impl Display for TaskRef {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TaskRef::Id(id) => write!(f, "#{}", id),
+            TaskRef::Uuid(prefix) => write!(f, "{}", prefix),
+        }
+    }
+}
+
+/// What the database keeps about every task and session besides its fields, empty until the
+/// record is stored.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RecordMeta {
+    /// Identifies the record across machines and imports, unlike the id
+    pub uuid: String,
+    pub created_at: Option<DateTime<Local>>,
+    /// Kept current by the database on every change
+    pub updated_at: Option<DateTime<Local>>,
+}
+
+impl RecordMeta {
+    /// The first `len` characters of the uuid, or more up to its first letter. `TaskRef` reads
+    /// a prefix of digits alone as an id.
+    pub fn short_uuid(&self, len: usize) -> &str {
+        let len = match self.uuid.find(|c: char| c.is_ascii_alphabetic()) {
+            Some(letter) => len.max(letter + 1),
+            None => self.uuid.len(),
+        };
+        &self.uuid[..len.min(self.uuid.len())]
+    }
+}
+
+/// A random version 4 uuid, drawn from the random source of the operating system.
+pub(crate) fn new_uuid() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+/// How many characters of the uuids it takes to tell every one of them apart, at least
+/// `SHORT_UUID_LEN`. The uuids have to be sorted.
+pub fn unique_prefix_len(sorted_uuids: &[String]) -> usize {
+    sorted_uuids
+        .windows(2)
+        .map(|pair| {
+            pair[0]
+                .chars()
+                .zip(pair[1].chars())
+                .take_while(|(a, b)| a == b)
+                .count()
+                + 1
+        })
+        .fold(SHORT_UUID_LEN, usize::max)
+}
+
+/// A task listed with the shortest prefix of its uuid that tells it apart from all the others.
+pub struct ListedTask {
+    pub task: Task,
+    pub uuid_len: usize,
+}
+
+impl TableRow for ListedTask {
+    fn headers(&self) -> Vec<&'static str> {
+        let mut headers = self.task.headers();
+        headers.insert(1, "uuid");
+        headers
+    }
+    fn row(&self) -> Vec<String> {
+        let mut row = self.task.row();
+        row.insert(1, self.task.meta.short_uuid(self.uuid_len).to_string());
+        row
+    }
 }
 
 #[derive(Args, Debug)]
 pub struct LSArgs {
     #[arg(short = 'l', long, default_value_t = 50)]
     pub limit: usize,
+    /// List tasks due until the end of the day n days from today (0 for today)
     #[arg(short = 'd', long, default_value_t = 1)]
     pub days: usize,
     #[arg(short = 'c', long)]
@@ -62,17 +276,40 @@ pub struct LSArgs {
 }
 
 impl CommandArgs for LSArgs {
-    fn validate(&self) -> Result<(), String> {
+    fn validate(&self) -> Result<(), AppError> {
         if self.limit > 100 {
-            return Err("Limit cannot be greater than 100".to_string());
+            return Err(AppError::Validation(
+                "Limit cannot be greater than 100".to_string(),
+            ));
         }
         if self.days > 365 {
-            return Err("Days cannot be greater than 365".to_string());
+            return Err(AppError::Validation(
+                "Days cannot be greater than 365".to_string(),
+            ));
         }
         Ok(())
     }
 }
 
+impl LSArgs {
+    /// The last day listed and the instant right after it, the midnight ending that day in `tz`.
+    pub fn due_until<Z: TimeZone>(
+        &self,
+        now: DateTime<Z>,
+    ) -> Result<(NaiveDate, DateTime<Z>), AppError> {
+        let last_day = now.date_naive() + Duration::days(self.days as i64);
+        let tz = now.timezone();
+        let midnight = (last_day + Duration::days(1))
+            .and_hms_opt(0, 0, 0)
+            .and_then(|d| tz.from_local_datetime(&d).earliest())
+            .ok_or_else(|| {
+                AppError::Validation(format!("could not resolve the end of day of {}", last_day))
+            })?;
+
+        Ok((last_day, midnight))
+    }
+}
+
 #[derive(Debug, ValueEnum, Copy, Clone, Default)]
 pub enum LSType {
     Task = 0,
@@ -176,7 +413,7 @@ impl TaskStatus {
     }
 }
 
-#[derive(Args, Debug)]
+#[derive(Args, Debug, Clone)]
 pub struct Task {
     #[clap(skip)]
     pub id: u64,
@@ -186,15 +423,34 @@ pub struct Task {
     /// Task title
     #[arg(short = 't', long)]
     pub title: String,
-    /// Task Due date in the form of "xDateUnit form now" or absolute date value (e.g: 1d, 1m, 1y or YYYY-MM-DD: 2025-10-01)
-    #[arg(short, long = "due-date", value_parser = parse_date, default_value = "1d")]
-    pub due_date: DateTime<Local>,
+    /// Task due date, relative or absolute (e.g: tomorrow, fri, next fri, eow, eom, 4h, 2w, 1m, in 2 weeks, nov 3, 2025-10-01 or "2025-10-01 14:00").
+    /// Values without a time of day make an all-day task
+    #[arg(short, long = "due-date", value_parser = parse_due, default_value = "1d")]
+    pub due_date: DueDate,
     /// Task priority
     #[arg(short = 'p', long, value_enum, default_value_t = Priority::Medium)]
     pub priority: Priority,
     /// Task category
     #[arg(short = 'c', long)]
     pub category: Option<String>,
+    /// When to be reminded, relative to the due date (1h-before, 2d-before) or absolute (e.g: tomorrow, "2025-10-01 09:00")
+    #[arg(short = 'r', long, value_parser = parse_remind)]
+    pub remind: Option<Remind>,
+    /// Task tag, repeat it for more (e.g: --tag phone --tag errands)
+    #[arg(long = "tag", value_name = "TAG")]
+    pub tags: Vec<String>,
+    #[clap(skip)]
+    pub meta: RecordMeta,
+}
+
+impl Task {
+    /// The instant the reminder of this task is due, if it has one.
+    pub fn remind_at(&self) -> Option<DateTime<Local>> {
+        self.remind.map(|remind| match remind {
+            Remind::Before(offset) => self.due_date.at - offset,
+            Remind::At(at) => at,
+        })
+    }
 }
 
 impl Default for Task {
@@ -203,21 +459,44 @@ impl Default for Task {
             id: 0,
             status: TaskStatus::Open,
             title: String::new(),
-            due_date: Local::now() + Duration::days(1),
+            due_date: DueDate::timed(Local::now() + Duration::days(1)),
             priority: Priority::Medium,
             category: None,
+            remind: None,
+            tags: Vec::new(),
+            meta: RecordMeta::default(),
         }
     }
 }
 
 impl CommandArgs for Task {
-    fn validate(&self) -> Result<(), String> {
+    fn validate(&self) -> Result<(), AppError> {
         if self.title.trim().is_empty() {
-            return Err("Title cannot be empty".to_string());
+            return Err(AppError::Validation("Title cannot be empty".to_string()));
+        }
+
+        if self.due_date.at < Local::now() {
+            return Err(AppError::Validation(
+                "Due date cannot be before 2020-01-01".to_string(),
+            ));
+        }
+
+        if let Some(remind_at) = self.remind_at() {
+            if remind_at > self.due_date.at {
+                return Err(AppError::Validation(
+                    "Reminder cannot be after the due date".to_string(),
+                ));
+            }
         }
 
-        if self.due_date < Local::now() {
-            return Err("Due date cannot be before 2020-01-01".to_string());
+        if self
+            .tags
+            .iter()
+            .any(|tag| tag.is_empty() || tag.contains(char::is_whitespace))
+        {
+            return Err(AppError::Validation(
+                "Tags cannot be empty or contain spaces".to_string(),
+            ));
         }
 
         Ok(())
@@ -232,7 +511,7 @@ impl TableRow for Task {
         vec![
             self.id.to_string(),
             self.title.clone(),
-            self.due_date.format("%Y-%m-%d").to_string(),
+            self.due_date.to_string(),
             String::from(self.priority),
             self.category.clone().unwrap_or_else(|| "-".to_string()),
             String::from(self.status),
@@ -242,200 +521,1166 @@ impl TableRow for Task {
 
 #[derive(Args, Debug)]
 pub struct AnalyzeArgs {
+    /// What to analyze
+    #[arg(long = "type", short = 't', value_enum, default_value_t = AnalyzeType::Tasks)]
+    pub analyze_type: AnalyzeType,
     /// Analyze tasks for n days before now
     #[arg(long = "days", short = 'n', default_value_t = 1)]
     pub days: u32,
+    /// Bucket the analysis by day, week, month, category or priority
+    #[arg(long = "group-by", short = 'g', value_enum, default_value_t = GroupBy::Day)]
+    pub group_by: GroupBy,
+    /// Start of the analyzed range (YYYY-MM-DD), overrides --days
+    #[arg(long)]
+    pub from: Option<NaiveDate>,
+    /// End of the analyzed range, inclusive (YYYY-MM-DD), overrides --days
+    #[arg(long)]
+    pub to: Option<NaiveDate>,
 }
 
 impl CommandArgs for AnalyzeArgs {
-    fn validate(&self) -> Result<(), String> {
-        if self.days > 365 {
-            return Err("Days cannot be greater than 365".to_string());
+    fn validate(&self) -> Result<(), AppError> {
+        if self.from.is_none() && self.to.is_none() && self.days > 365 {
+            return Err(AppError::Validation(
+                "Days cannot be greater than 365".to_string(),
+            ));
+        }
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            if from > to {
+                return Err(AppError::Validation(
+                    "--from cannot be after --to".to_string(),
+                ));
+            }
         }
         Ok(())
     }
 }
 
-#[derive(Default, PartialEq, Eq, Debug, ValueEnum, Copy, Clone)]
-pub enum PomoType {
-    Rest = 1,
-    #[default]
-    Work = 0,
-}
+impl AnalyzeArgs {
+    /// Returns the `[start, end)` range of due dates to analyze. Explicit `--from/--to` dates
+    /// cover whole days in the time zone of `now`, a missing bound falls back to `--days` around
+    /// now.
+    pub fn date_range<Z: TimeZone>(
+        &self,
+        now: DateTime<Z>,
+    ) -> Result<(DateTime<Local>, DateTime<Local>), AppError> {
+        let tz = now.timezone();
+        let now = now.with_timezone(&Local);
+        let days = Duration::days(self.days as i64);
+
+        let start = match self.from {
+            Some(from) => start_of_day(from, &tz)?,
+            None => now - days,
+        };
+        let end = match self.to {
+            Some(to) => start_of_day(to + Duration::days(1), &tz)?,
+            None => now + days,
+        };
 
-impl From<String> for PomoType {
-    fn from(s: String) -> Self {
-        match s.to_lowercase().as_str() {
-            "rest" => PomoType::Rest,
-            _ => PomoType::Work,
-        }
+        Ok((start, end))
     }
 }
 
-impl From<PomoType> for String {
-    fn from(p: PomoType) -> Self {
-        match p {
-            PomoType::Rest => "Rest".to_string(),
-            PomoType::Work => "Work".to_string(),
-        }
-    }
+/// The midnight starting the given day in `tz`.
+fn start_of_day<Z: TimeZone>(date: NaiveDate, tz: &Z) -> Result<DateTime<Local>, AppError> {
+    date.and_hms_opt(0, 0, 0)
+        .and_then(|d| tz.from_local_datetime(&d).earliest())
+        .map(|d| d.with_timezone(&Local))
+        .ok_or_else(|| AppError::Validation(format!("could not resolve midnight of {}", date)))
 }
 
-impl PomoType {
-    pub fn from_usize(n: usize) -> PomoType {
-        match n {
-            0 => PomoType::Rest,
-            _ => PomoType::Work,
+#[derive(Args, Debug)]
+pub struct NotifyArgs {
+    /// Channel delivering the reminders, defaults to the `notify` setting or the terminal bell
+    #[arg(long, value_enum)]
+    pub via: Option<NotifyVia>,
+    /// Program and arguments to run for the command channel, overrides `notify_command`
+    #[arg(long)]
+    pub command: Option<String>,
+    /// File the file channel appends to, overrides `notify_file`
+    #[arg(long)]
+    pub file: Option<String>,
+}
+
+impl CommandArgs for NotifyArgs {
+    fn validate(&self) -> Result<(), AppError> {
+        if let Some(command) = &self.command {
+            if command.trim().is_empty() {
+                return Err(AppError::Validation("Command cannot be empty".to_string()));
+            }
         }
+        Ok(())
     }
+}
 
-    pub fn to_usize(&self) -> usize {
+#[derive(Args, Debug)]
+pub struct PromptArgs {
+    /// Output template with the placeholders {pomo_remaining}, {pomo_title}, {pomo_type},
+    /// {pomo_state}, {pomo_icon}, {open_count} and {overdue_count}
+    #[arg(short = 'f', long)]
+    pub format: Option<String>,
+    /// Ready-made output for a status bar or prompt
+    #[arg(short = 's', long, value_enum, default_value_t = PromptStyle::Plain)]
+    pub style: PromptStyle,
+}
+
+#[derive(Debug, ValueEnum, PartialEq, Eq, Copy, Clone, Default)]
+pub enum PromptStyle {
+    /// The format as is
+    #[default]
+    Plain,
+    /// Colored with tmux `#[fg=...]` attributes for `status-right`
+    Tmux,
+    /// Icons for a starship `custom` module
+    Starship,
+    /// A json line for a waybar `custom` module with `return-type` json
+    Waybar,
+}
+
+impl PromptStyle {
+    pub fn default_format(self) -> &'static str {
         match self {
-            PomoType::Rest => 0,
-            PomoType::Work => 1,
+            PromptStyle::Plain => "{pomo_remaining} {open_count}",
+            PromptStyle::Tmux => "{pomo_remaining} {open_count} open",
+            PromptStyle::Starship | PromptStyle::Waybar => {
+                "{pomo_icon} {pomo_remaining} {open_count} open"
+            }
         }
     }
+}
 
-    pub fn is_rest(&self) -> bool {
-        matches!(*self, PomoType::Rest)
-    }
+#[derive(Debug, ValueEnum, PartialEq, Eq, Copy, Clone, Default)]
+pub enum NotifyVia {
+    #[default]
+    Bell,
+    Command,
+    File,
+}
 
-    pub fn is_work(&self) -> bool {
-        matches!(*self, PomoType::Work)
+#[derive(Args, Debug)]
+pub struct ReportArgs {
+    /// Period the report covers, up to and including today
+    #[arg(long, short = 'p', value_enum, default_value_t = ReportPeriod::Week)]
+    pub period: ReportPeriod,
+    /// Report format
+    #[arg(long, short = 'f', value_enum, default_value_t = ReportFormat::Md)]
+    pub format: ReportFormat,
+    /// Write the report to this file instead of stdout
+    #[arg(long, short = 'o')]
+    pub output: Option<std::path::PathBuf>,
+}
+
+impl CommandArgs for ReportArgs {
+    fn validate(&self) -> Result<(), AppError> {
+        if let Some(output) = &self.output {
+            if output.is_dir() {
+                return Err(AppError::Validation(format!(
+                    "{} is a directory",
+                    output.display()
+                )));
+            }
+        }
+        Ok(())
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct DurationField(pub chrono::Duration);
+#[derive(Debug, ValueEnum, PartialEq, Eq, Copy, Clone, Default)]
+pub enum ReportPeriod {
+    Day,
+    #[default]
+    Week,
+    Month,
+}
 
-impl FromStr for DurationField {
-    type Err = String;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let len = s.len();
-        if len < 2 {
-            return Err(
-                "Duration format must be a number followed by a unit (s, m, h)".to_string(),
-            );
+impl ReportPeriod {
+    /// Returns the `[start, end)` range of the day, week (from monday) or month containing `now`,
+    /// bounded by midnights in its time zone.
+    pub fn date_range<Z: TimeZone>(
+        self,
+        now: DateTime<Z>,
+    ) -> Result<(DateTime<Local>, DateTime<Local>), AppError> {
+        let tz = now.timezone();
+        let today = now.date_naive();
+        let (first, next) = match self {
+            ReportPeriod::Day => (today, today + Duration::days(1)),
+            ReportPeriod::Week => {
+                let monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+                (monday, monday + Duration::days(7))
+            }
+            ReportPeriod::Month => {
+                let first = today.with_day(1).unwrap_or(today);
+                let next = first
+                    .checked_add_months(chrono::Months::new(1))
+                    .ok_or_else(|| {
+                        AppError::Validation(format!("could not compute the month after {}", first))
+                    })?;
+                (first, next)
+            }
+        };
+
+        Ok((start_of_day(first, &tz)?, start_of_day(next, &tz)?))
+    }
+}
+
+impl From<ReportPeriod> for String {
+    fn from(p: ReportPeriod) -> Self {
+        match p {
+            ReportPeriod::Day => "Daily".to_string(),
+            ReportPeriod::Week => "Weekly".to_string(),
+            ReportPeriod::Month => "Monthly".to_string(),
         }
+    }
+}
 
-        let (value_str, unit) = s.split_at(len - 1);
-        let value: i64 = value_str
-            .parse()
-            .map_err(|_| format!("Cannot parse '{}' as a number", value_str))?;
+#[derive(Debug, ValueEnum, PartialEq, Eq, Copy, Clone, Default)]
+pub enum ReportFormat {
+    #[default]
+    Md,
+    Html,
+}
 
-        match unit {
-            "s" => Ok(DurationField(chrono::Duration::seconds(value))),
-            "m" => Ok(DurationField(chrono::Duration::minutes(value))),
-            "h" => Ok(DurationField(chrono::Duration::hours(value))),
-            _ => Ok(DurationField(chrono::Duration::milliseconds(value))),
+impl ReportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ReportFormat::Md => "md",
+            ReportFormat::Html => "html",
         }
     }
 }
 
-impl From<DurationField> for String {
-    fn from(d: DurationField) -> Self {
-        let mut s = String::new();
-        if d.0.num_seconds() < 60 {
-            s.push_str(&format!("{}s", d.0.num_seconds()));
-        } else if d.0.num_minutes() < 60 {
-            s.push_str(&format!("{}m", d.0.num_minutes()));
-        } else if d.0.num_hours() < 24 {
-            s.push_str(&format!("{}h", d.0.num_hours()));
-        } else {
-            s.push_str(&format!("{} milliseconds", d.0));
+/// Everything a status report shows for its `[from, to)` range.
+#[derive(Debug, Default)]
+pub struct ReportData {
+    pub from: DateTime<Local>,
+    pub to: DateTime<Local>,
+    pub completed: Vec<Task>,
+    /// Tasks due in the range that are overdue or were completed after their due date
+    pub slipped: Vec<Task>,
+    /// Seconds of work sessions per category, largest first
+    pub focus: Vec<(String, i64)>,
+}
+
+impl ReportData {
+    /// Categories ordered by the number of tasks completed in them.
+    pub fn top_categories(&self, limit: usize) -> Vec<(String, usize)> {
+        let mut counts: Vec<(String, usize)> = Vec::new();
+        for task in &self.completed {
+            let category = task.category.clone().unwrap_or_else(|| "-".to_string());
+            match counts.iter_mut().find(|(c, _)| *c == category) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((category, 1)),
+            }
         }
 
-        s
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        counts.truncate(limit);
+        counts
     }
 }
 
-impl DurationField {
-    pub fn to_i64(&self) -> i64 {
-        self.0.num_seconds()
-    }
+/// `goals` shows the progress of every goal, the subcommands manage them.
+#[derive(Args, Debug)]
+pub struct GoalsArgs {
+    #[command(subcommand)]
+    pub command: Option<GoalsCommand>,
+}
 
-    pub fn from_i64(n: i64) -> Self {
-        DurationField(Duration::seconds(n))
+impl GoalsArgs {
+    /// Whether the command changes the database, listing the goals only reads it.
+    pub fn writes(&self) -> bool {
+        self.command.is_some()
     }
+}
 
-    pub fn add_date(&self, date: &DateTime<Local>) -> DateTime<Local> {
-        *date + self.0
-    }
+#[derive(Subcommand, Debug)]
+pub enum GoalsCommand {
+    /// Set a goal, e.g. `goals add pomodoros 4 --per weekday` or `goals add focus 10h --per week`
+    Add(GoalAddArgs),
+    /// Remove a goal
+    #[command(visible_alias = "rm")]
+    Remove(GoalRemoveArgs),
+}
 
-    pub fn to_time_duration(&self) -> std::time::Duration {
-        std::time::Duration::from_secs(self.0.num_seconds() as u64)
-    }
+#[derive(Args, Debug)]
+pub struct GoalAddArgs {
+    /// What the goal counts
+    #[arg(value_enum)]
+    pub metric: GoalMetric,
+    /// How many pomodoros or tasks, or how much focus time (e.g. 10h), to reach per period
+    pub target: String,
+    /// Period the target has to be reached in
+    #[arg(long, short = 'p', value_enum, default_value_t = GoalPeriod::Day)]
+    pub per: GoalPeriod,
 }
 
-impl From<DurationField> for chrono::Duration {
-    fn from(d: DurationField) -> Self {
-        d.0
+impl CommandArgs for GoalAddArgs {
+    fn validate(&self) -> Result<(), AppError> {
+        self.target_value().map(|_| ())
     }
 }
 
-impl From<chrono::Duration> for DurationField {
-    fn from(d: chrono::Duration) -> Self {
-        DurationField(d)
+impl GoalAddArgs {
+    /// The target as stored, in seconds for focus goals.
+    pub fn target_value(&self) -> Result<i64, AppError> {
+        let target = self.target.trim();
+        let value = match self.metric {
+            GoalMetric::Focus => parse_duration(target)?.to_i64(),
+            GoalMetric::Pomodoros | GoalMetric::Tasks => target.parse::<i64>().map_err(|_| {
+                AppError::Validation(format!("Cannot parse '{}' as a number", target))
+            })?,
+        };
+
+        if value <= 0 {
+            return Err(AppError::Validation(
+                "Target must be greater than 0".to_string(),
+            ));
+        }
+        Ok(value)
     }
 }
 
-impl Default for DurationField {
-    fn default() -> Self {
-        DurationField(chrono::Duration::minutes(25))
-    }
+#[derive(Args, Debug)]
+pub struct GoalRemoveArgs {
+    /// Id of the goal to remove
+    pub id: u64,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Default)]
-pub enum PomoStatus {
-    Paused = 1,
-    Finished = 2,
-    #[default]
-    Running = 0,
+#[derive(Debug, ValueEnum, PartialEq, Eq, Copy, Clone)]
+pub enum GoalMetric {
+    /// Finished work pomodoros
+    Pomodoros,
+    /// Time focused in work sessions
+    Focus,
+    /// Tasks closed
+    Tasks,
 }
 
-impl From<String> for PomoStatus {
-    fn from(s: String) -> Self {
-        match s.to_lowercase().as_str() {
-            "paused" => PomoStatus::Paused,
-            "finished" => PomoStatus::Finished,
-            _ => PomoStatus::Running,
+impl GoalMetric {
+    pub fn from_usize(n: usize) -> GoalMetric {
+        match n {
+            1 => GoalMetric::Focus,
+            2 => GoalMetric::Tasks,
+            _ => GoalMetric::Pomodoros,
+        }
+    }
+
+    pub fn to_usize(self) -> usize {
+        match self {
+            GoalMetric::Pomodoros => 0,
+            GoalMetric::Focus => 1,
+            GoalMetric::Tasks => 2,
+        }
+    }
+
+    /// A count, or seconds of focus as `1h 05m`.
+    pub fn format_value(self, value: i64) -> String {
+        match self {
+            GoalMetric::Focus => crate::report::format_focus(value),
+            GoalMetric::Pomodoros | GoalMetric::Tasks => value.to_string(),
         }
     }
 }
 
-impl From<PomoStatus> for String {
-    fn from(p: PomoStatus) -> Self {
-        match p {
-            PomoStatus::Paused => "Paused".to_string(),
-            PomoStatus::Finished => "Finished".to_string(),
-            PomoStatus::Running => "Running".to_string(),
+impl From<GoalMetric> for String {
+    fn from(m: GoalMetric) -> Self {
+        match m {
+            GoalMetric::Pomodoros => "pomodoros".to_string(),
+            GoalMetric::Focus => "focus".to_string(),
+            GoalMetric::Tasks => "tasks".to_string(),
         }
     }
 }
 
-impl PomoStatus {
-    pub fn from_usize(n: usize) -> PomoStatus {
+#[derive(Debug, ValueEnum, PartialEq, Eq, Copy, Clone, Default)]
+pub enum GoalPeriod {
+    #[default]
+    Day,
+    /// Monday to friday, weekends neither count nor break a streak
+    Weekday,
+    /// Monday to sunday
+    Week,
+}
+
+impl GoalPeriod {
+    pub fn from_usize(n: usize) -> GoalPeriod {
         match n {
-            0 => PomoStatus::Running,
-            1 => PomoStatus::Paused,
-            2 => PomoStatus::Finished,
-            _ => PomoStatus::Running,
+            1 => GoalPeriod::Weekday,
+            2 => GoalPeriod::Week,
+            _ => GoalPeriod::Day,
         }
     }
 
-    pub fn to_usize(&self) -> usize {
+    pub fn to_usize(self) -> usize {
         match self {
-            PomoStatus::Paused => 1,
-            PomoStatus::Finished => 2,
-            PomoStatus::Running => 0,
+            GoalPeriod::Day => 0,
+            GoalPeriod::Weekday => 1,
+            GoalPeriod::Week => 2,
         }
     }
-}
 
-#[derive(Args, Debug)]
-pub struct PomoTask {
-    #[clap(skip)]
-    pub id: u64,
+    /// First day of the period containing `date`.
+    pub fn start(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            GoalPeriod::Day | GoalPeriod::Weekday => date,
+            GoalPeriod::Week => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+        }
+    }
+
+    /// Whether `date` belongs to one of the periods at all.
+    pub fn includes(self, date: NaiveDate) -> bool {
+        match self {
+            GoalPeriod::Weekday => !matches!(date.weekday(), Weekday::Sat | Weekday::Sun),
+            GoalPeriod::Day | GoalPeriod::Week => true,
+        }
+    }
+
+    /// How the period containing today is called.
+    pub fn current(self) -> &'static str {
+        match self {
+            GoalPeriod::Day | GoalPeriod::Weekday => "today",
+            GoalPeriod::Week => "this week",
+        }
+    }
+}
+
+impl From<GoalPeriod> for String {
+    fn from(p: GoalPeriod) -> Self {
+        match p {
+            GoalPeriod::Day => "day".to_string(),
+            GoalPeriod::Weekday => "weekday".to_string(),
+            GoalPeriod::Week => "week".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Goal {
+    pub id: u64,
+    pub metric: GoalMetric,
+    pub period: GoalPeriod,
+    /// How many pomodoros or tasks, or seconds of focus, make the goal
+    pub target: i64,
+}
+
+impl Display for Goal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} per {}",
+            self.metric.format_value(self.target),
+            String::from(self.metric),
+            String::from(self.period)
+        )
+    }
+}
+
+/// How far a goal got in the current period and how many periods in a row reached it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GoalProgress {
+    pub goal: Goal,
+    /// Total of the period containing today
+    pub current: i64,
+    /// Periods in a row that reached the target, up to today. The current period only counts
+    /// once reached, it can still be made up until it ends.
+    pub streak: u64,
+    pub longest_streak: u64,
+}
+
+impl GoalProgress {
+    /// Totals the daily values of the goal's metric into its periods and finds the streaks in them.
+    pub fn from_daily(goal: Goal, daily: &[(NaiveDate, i64)], today: NaiveDate) -> Self {
+        let period = goal.period;
+
+        let mut totals: Vec<(NaiveDate, i64)> = Vec::new();
+        for (date, value) in daily
+            .iter()
+            .filter(|(d, _)| period.includes(*d) && *d <= today)
+        {
+            let start = period.start(*date);
+            match totals.iter_mut().find(|(s, _)| *s == start) {
+                Some((_, total)) => *total += value,
+                None => totals.push((start, *value)),
+            }
+        }
+        let reached = |start: NaiveDate| {
+            totals
+                .iter()
+                .any(|(s, total)| *s == start && *total >= goal.target)
+        };
+
+        // Every period from the first one with data up to the current one.
+        let mut periods: Vec<NaiveDate> = Vec::new();
+        if let Some(first) = totals.iter().map(|(s, _)| *s).min() {
+            let mut day = first;
+            while day <= today {
+                if period.includes(day) && periods.last() != Some(&period.start(day)) {
+                    periods.push(period.start(day));
+                }
+                day += Duration::days(1);
+            }
+        }
+
+        let mut longest_streak = 0;
+        let mut run = 0;
+        for start in &periods {
+            run = if reached(*start) { run + 1 } else { 0 };
+            longest_streak = longest_streak.max(run);
+        }
+
+        let current_start = period.start(today);
+        let streak = periods
+            .iter()
+            .rev()
+            .skip_while(|s| **s == current_start && !reached(**s))
+            .take_while(|s| reached(**s))
+            .count() as u64;
+
+        GoalProgress {
+            current: totals
+                .iter()
+                .find(|(s, _)| *s == current_start && period.includes(today))
+                .map_or(0, |(_, total)| *total),
+            goal,
+            streak,
+            longest_streak,
+        }
+    }
+
+    pub fn is_reached(&self) -> bool {
+        self.current >= self.goal.target
+    }
+
+    /// One line for the time's up screen, e.g. `pomodoros today 3/4`.
+    pub fn summary(&self) -> String {
+        format!(
+            "{} {} {}/{}{}",
+            String::from(self.goal.metric),
+            self.goal.period.current(),
+            self.goal.metric.format_value(self.current),
+            self.goal.metric.format_value(self.goal.target),
+            if self.is_reached() { " ✓" } else { "" }
+        )
+    }
+}
+
+impl TableRow for GoalProgress {
+    fn headers(&self) -> Vec<&'static str> {
+        vec!["Id", "Goal", "Progress", "Done", "Streak", "Longest Streak"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        let ratio = self.current as f64 / self.goal.target.max(1) as f64;
+
+        vec![
+            self.goal.id.to_string(),
+            self.goal.to_string(),
+            ratio_bar(ratio, GOAL_BAR_WIDTH),
+            format!(
+                "{}/{}",
+                self.goal.metric.format_value(self.current),
+                self.goal.metric.format_value(self.goal.target)
+            ),
+            self.streak.to_string(),
+            self.longest_streak.to_string(),
+        ]
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct TrackArgs {
+    #[command(subcommand)]
+    pub command: TrackCommand,
+}
+
+impl TrackArgs {
+    /// Whether the command changes the database, `status` only reads it.
+    pub fn writes(&self) -> bool {
+        !matches!(self.command, TrackCommand::Status)
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TrackCommand {
+    /// Start a time entry and show its stopwatch until you stop it with `q`
+    Start(TrackStartArgs),
+    /// Stop the running time entry
+    Stop,
+    /// Show the running time entry
+    Status,
+}
+
+#[derive(Args, Debug)]
+pub struct TrackStartArgs {
+    /// Id of the task the time is spent on
+    #[arg(long = "task")]
+    pub task_id: Option<u64>,
+    /// What the time is spent on, defaults to the title of the task
+    #[arg(short = 't', long)]
+    pub title: Option<String>,
+    /// Category of the entry, defaults to the category of the task
+    #[arg(short = 'c', long)]
+    pub category: Option<String>,
+    /// Start the entry without the stopwatch and give the terminal back
+    #[arg(long)]
+    pub detach: bool,
+}
+
+impl CommandArgs for TrackStartArgs {
+    fn validate(&self) -> Result<(), AppError> {
+        match (&self.title, self.task_id) {
+            (None, None) => Err(AppError::Validation(
+                "A time entry needs a --task or a --title".to_string(),
+            )),
+            (Some(title), _) if title.trim().is_empty() => {
+                Err(AppError::Validation("Title cannot be empty".to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Time tracked against a task or title, running until it has an end.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeEntry {
+    pub id: u64,
+    pub task_id: Option<u64>,
+    pub title: String,
+    pub category: Option<String>,
+    pub start_time: DateTime<Local>,
+    pub end_time: Option<DateTime<Local>>,
+}
+
+impl TimeEntry {
+    pub fn elapsed_at(&self, now: DateTime<Local>) -> Duration {
+        (self.end_time.unwrap_or(now) - self.start_time).max(Duration::zero())
+    }
+}
+
+impl TableRow for TimeEntry {
+    fn headers(&self) -> Vec<&'static str> {
+        vec![
+            "id",
+            "title",
+            "task",
+            "category",
+            "start-time",
+            "end-time",
+            "tracked",
+        ]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            self.title.clone(),
+            self.task_id
+                .map_or_else(|| "-".to_string(), |id| format!("#{}", id)),
+            self.category.clone().unwrap_or_else(|| "-".to_string()),
+            self.start_time.format("%Y-%m-%d %H:%M:%S").to_string(),
+            self.end_time.map_or_else(
+                || "running".to_string(),
+                |end| end.format("%Y-%m-%d %H:%M:%S").to_string(),
+            ),
+            crate::report::format_focus(self.elapsed_at(Local::now()).num_seconds()),
+        ]
+    }
+}
+
+/// Time tracked in the range for one task or category.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeAnalysis {
+    /// What the buckets are, `Task` or `Category`
+    pub group: &'static str,
+    pub bucket: String,
+    /// Time entries and work sessions in the bucket
+    pub entries: u64,
+    pub seconds: i64,
+    /// Share of all the time tracked in the range
+    pub share: f64,
+}
+
+impl TableRow for TimeAnalysis {
+    fn headers(&self) -> Vec<&'static str> {
+        vec![self.group, "Entries", "Time", "Share (%)"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.bucket.clone(),
+            self.entries.to_string(),
+            crate::report::format_focus(self.seconds),
+            format!("{:.2}%", self.share),
+        ]
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct TimesheetArgs {
+    /// First day of the timesheet (YYYY-MM-DD), defaults to monday of this week
+    #[arg(long)]
+    pub from: Option<NaiveDate>,
+    /// Last day of the timesheet, inclusive (YYYY-MM-DD), defaults to today
+    #[arg(long)]
+    pub to: Option<NaiveDate>,
+    /// Round the time of every line up to a multiple of this (e.g. 15m, 1h)
+    #[arg(long, value_parser = parse_duration)]
+    pub round: Option<DurationField>,
+    /// Timesheet format
+    #[arg(long, short = 'f', value_enum, default_value_t = TimesheetFormat::Csv)]
+    pub format: TimesheetFormat,
+    /// Write the timesheet to this file instead of stdout
+    #[arg(long, short = 'o')]
+    pub output: Option<std::path::PathBuf>,
+}
+
+impl CommandArgs for TimesheetArgs {
+    fn validate(&self) -> Result<(), AppError> {
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            if from > to {
+                return Err(AppError::Validation(
+                    "--from cannot be after --to".to_string(),
+                ));
+            }
+        }
+        if let Some(round) = &self.round {
+            if round.to_i64() <= 0 {
+                return Err(AppError::Validation(
+                    "--round has to be at least a second".to_string(),
+                ));
+            }
+        }
+        if let Some(output) = &self.output {
+            if output.is_dir() {
+                return Err(AppError::Validation(format!(
+                    "{} is a directory",
+                    output.display()
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TimesheetArgs {
+    /// Returns the `[start, end)` range of whole days in the time zone of `now` the timesheet
+    /// covers, by default the week up to and including today.
+    pub fn date_range<Z: TimeZone>(
+        &self,
+        now: DateTime<Z>,
+    ) -> Result<(DateTime<Local>, DateTime<Local>), AppError> {
+        let tz = now.timezone();
+        let today = now.date_naive();
+        let from = self.from.unwrap_or_else(|| {
+            today - Duration::days(today.weekday().num_days_from_monday() as i64)
+        });
+        let to = self.to.unwrap_or(today);
+
+        Ok((
+            start_of_day(from, &tz)?,
+            start_of_day(to + Duration::days(1), &tz)?,
+        ))
+    }
+}
+
+#[derive(Debug, ValueEnum, PartialEq, Eq, Copy, Clone, Default)]
+pub enum TimesheetFormat {
+    #[default]
+    Csv,
+    Md,
+}
+
+#[derive(Args, Debug)]
+pub struct BackupArgs {
+    /// File to write the backup to, defaults to a timestamped file in ~/.tasklog/backups
+    pub path: Option<std::path::PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct RestoreArgs {
+    /// Backup to restore
+    pub file: std::path::PathBuf,
+    /// Restore without asking for confirmation
+    #[arg(long, short)]
+    pub yes: bool,
+}
+
+/// `sync` alone goes through git, its subcommands sync with files of other tools.
+#[derive(Args, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct SyncArgs {
+    #[command(subcommand)]
+    pub command: Option<SyncCommand>,
+    /// Git repository to pull from and push to, a url or a local path (overrides `sync_remote`)
+    #[arg(long, short)]
+    pub remote: Option<String>,
+    /// Only commit to the local sync repository, without pulling or pushing
+    #[arg(long)]
+    pub offline: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SyncCommand {
+    /// Bring the tasks and a todo.txt file up to date with each other's changes
+    Todotxt(TodotxtSyncArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct TodotxtSyncArgs {
+    /// The todo.txt file, created when it does not exist
+    pub file: std::path::PathBuf,
+}
+
+/// Formats tasks are imported from and exported to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum TaskFormat {
+    /// One task per line as todo.txt writes them
+    Todotxt,
+}
+
+#[derive(Args, Debug)]
+pub struct ImportArgs {
+    /// Format of the file
+    #[arg(long, value_enum)]
+    pub from: TaskFormat,
+    /// File to read the tasks from
+    pub file: std::path::PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct ExportArgs {
+    /// Format to write
+    #[arg(long, value_enum)]
+    pub to: TaskFormat,
+    /// Write the tasks to this file instead of stdout
+    #[arg(long, short = 'o')]
+    pub output: Option<std::path::PathBuf>,
+}
+
+/// The records `sync` exchanges, each kept in a file of its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SyncTable {
+    Tasks,
+    Sessions,
+}
+
+impl SyncTable {
+    pub const ALL: [SyncTable; 2] = [SyncTable::Tasks, SyncTable::Sessions];
+
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            SyncTable::Tasks => "tasks.tsv",
+            SyncTable::Sessions => "sessions.tsv",
+        }
+    }
+
+    /// The columns of a record after its uuid and modification time, the title always first.
+    pub fn columns(&self) -> &'static [&'static str] {
+        match self {
+            SyncTable::Tasks => &[
+                "title",
+                "status",
+                "due_date",
+                "all_day",
+                "priority",
+                "category",
+                "tags",
+                "remind_at",
+                "reminded_at",
+                "completed_at",
+                "created_at",
+                "remind_before",
+            ],
+            SyncTable::Sessions => &[
+                "title",
+                "type",
+                "start_time",
+                "end_time",
+                "duration",
+                "elapsed",
+                "status",
+                "category",
+                "task",
+                "created_at",
+            ],
+        }
+    }
+
+    /// What one record is called in messages
+    pub fn record(&self) -> &'static str {
+        match self {
+            SyncTable::Tasks => "task",
+            SyncTable::Sessions => "session",
+        }
+    }
+}
+
+/// A task or session as `sync` exchanges it, its columns as text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncRecord {
+    pub uuid: String,
+    /// When the record was last changed, what decides between two changes of it
+    pub updated_at: String,
+    /// The values of `SyncTable::columns`, `None` for NULL
+    pub fields: Vec<Option<String>>,
+}
+
+impl SyncRecord {
+    pub fn title(&self) -> &str {
+        self.fields
+            .first()
+            .and_then(|title| title.as_deref())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct DoctorArgs {
+    /// Repair what can be repaired, e.g. pending migrations, orphaned rows and stale sessions
+    #[arg(long)]
+    pub fix: bool,
+}
+
+/// One problem a `doctor` check came across.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub message: String,
+    /// Whether `--fix` repaired it
+    pub fixed: bool,
+}
+
+/// The outcome of one `doctor` check, no findings means it passed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnosis {
+    pub check: &'static str,
+    pub findings: Vec<Finding>,
+}
+
+impl Diagnosis {
+    pub fn new(check: &'static str) -> Diagnosis {
+        Diagnosis {
+            check,
+            findings: Vec::new(),
+        }
+    }
+
+    pub fn found(&mut self, message: impl Into<String>, fixed: bool) {
+        self.findings.push(Finding {
+            message: message.into(),
+            fixed,
+        });
+    }
+}
+
+/// Time tracked on one day for a task, or for a title without a task, of a category.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimesheetLine {
+    pub date: NaiveDate,
+    pub category: String,
+    pub task: String,
+    pub seconds: i64,
+}
+
+#[derive(Debug, ValueEnum, PartialEq, Eq, Copy, Clone, Default)]
+pub enum AnalyzeType {
+    /// Completion, lateness and flow of the tasks due in the range
+    #[default]
+    Tasks,
+    /// Interruptions of the work sessions started in the range
+    Interruptions,
+    /// Time tracked per task and category, from time entries and work sessions
+    Time,
+}
+
+#[derive(Debug, ValueEnum, PartialEq, Eq, Copy, Clone, Default)]
+pub enum GroupBy {
+    #[default]
+    Day,
+    Week,
+    Month,
+    Category,
+    Priority,
+}
+
+#[derive(Default, PartialEq, Eq, Debug, ValueEnum, Copy, Clone)]
+pub enum PomoType {
+    Rest = 1,
+    #[default]
+    Work = 0,
+}
+
+impl From<String> for PomoType {
+    fn from(s: String) -> Self {
+        match s.to_lowercase().as_str() {
+            "rest" => PomoType::Rest,
+            _ => PomoType::Work,
+        }
+    }
+}
+
+impl From<PomoType> for String {
+    fn from(p: PomoType) -> Self {
+        match p {
+            PomoType::Rest => "Rest".to_string(),
+            PomoType::Work => "Work".to_string(),
+        }
+    }
+}
+
+impl PomoType {
+    pub fn from_usize(n: usize) -> PomoType {
+        match n {
+            0 => PomoType::Rest,
+            _ => PomoType::Work,
+        }
+    }
+
+    pub fn to_usize(&self) -> usize {
+        match self {
+            PomoType::Rest => 0,
+            PomoType::Work => 1,
+        }
+    }
+
+    pub fn is_rest(&self) -> bool {
+        matches!(*self, PomoType::Rest)
+    }
+
+    pub fn is_work(&self) -> bool {
+        matches!(*self, PomoType::Work)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DurationField(pub chrono::Duration);
+
+impl FromStr for DurationField {
+    type Err = AppError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let len = s.len();
+        if len < 2 {
+            return Err(AppError::Validation(
+                "Duration format must be a number followed by a unit (s, m, h)".to_string(),
+            ));
+        }
+
+        let (value_str, unit) = s.split_at(len - 1);
+        let value: i64 = value_str.parse().map_err(|_| {
+            AppError::Validation(format!("Cannot parse '{}' as a number", value_str))
+        })?;
+
+        match unit {
+            "s" => Ok(DurationField(chrono::Duration::seconds(value))),
+            "m" => Ok(DurationField(chrono::Duration::minutes(value))),
+            "h" => Ok(DurationField(chrono::Duration::hours(value))),
+            _ => Ok(DurationField(chrono::Duration::milliseconds(value))),
+        }
+    }
+}
+
+impl From<DurationField> for String {
+    fn from(d: DurationField) -> Self {
+        let mut s = String::new();
+        if d.0.num_seconds() < 60 {
+            s.push_str(&format!("{}s", d.0.num_seconds()));
+        } else if d.0.num_minutes() < 60 {
+            s.push_str(&format!("{}m", d.0.num_minutes()));
+        } else if d.0.num_hours() < 24 {
+            s.push_str(&format!("{}h", d.0.num_hours()));
+        } else {
+            s.push_str(&format!("{} milliseconds", d.0));
+        }
+
+        s
+    }
+}
+
+impl DurationField {
+    pub fn to_i64(&self) -> i64 {
+        self.0.num_seconds()
+    }
+
+    pub fn from_i64(n: i64) -> Self {
+        DurationField(Duration::seconds(n))
+    }
+
+    pub fn add_date(&self, date: &DateTime<Local>) -> DateTime<Local> {
+        *date + self.0
+    }
+
+    pub fn to_time_duration(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.0.num_seconds() as u64)
+    }
+}
+
+impl From<DurationField> for chrono::Duration {
+    fn from(d: DurationField) -> Self {
+        d.0
+    }
+}
+
+impl From<chrono::Duration> for DurationField {
+    fn from(d: chrono::Duration) -> Self {
+        DurationField(d)
+    }
+}
+
+impl Default for DurationField {
+    fn default() -> Self {
+        DurationField(chrono::Duration::minutes(25))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub enum PomoStatus {
+    Paused = 1,
+    Finished = 2,
+    #[default]
+    Running = 0,
+}
+
+impl From<String> for PomoStatus {
+    fn from(s: String) -> Self {
+        match s.to_lowercase().as_str() {
+            "paused" => PomoStatus::Paused,
+            "finished" => PomoStatus::Finished,
+            _ => PomoStatus::Running,
+        }
+    }
+}
+
+impl From<PomoStatus> for String {
+    fn from(p: PomoStatus) -> Self {
+        match p {
+            PomoStatus::Paused => "Paused".to_string(),
+            PomoStatus::Finished => "Finished".to_string(),
+            PomoStatus::Running => "Running".to_string(),
+        }
+    }
+}
+
+impl PomoStatus {
+    pub fn from_usize(n: usize) -> PomoStatus {
+        match n {
+            0 => PomoStatus::Running,
+            1 => PomoStatus::Paused,
+            2 => PomoStatus::Finished,
+            _ => PomoStatus::Running,
+        }
+    }
+
+    pub fn to_usize(&self) -> usize {
+        match self {
+            PomoStatus::Paused => 1,
+            PomoStatus::Finished => 2,
+            PomoStatus::Running => 0,
+        }
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct PomoTask {
+    #[clap(skip)]
+    pub id: u64,
     #[clap(skip)]
     pub status: PomoStatus,
     #[clap(skip)]
@@ -449,10 +1694,194 @@ pub struct PomoTask {
     /// Session category
     #[arg(short = 'c', long)]
     pub category: Option<String>,
+    /// Id of the task the session works on
+    #[arg(long = "task")]
+    pub task_id: Option<u64>,
+    /// Time actually counted down, short of `duration` when the session was quit or skipped
+    #[clap(skip)]
+    pub elapsed: DurationField,
     #[clap(skip)]
     pub start_time: DateTime<Local>,
     #[clap(skip)]
     pub end_time: DateTime<Local>,
+    #[clap(skip)]
+    pub meta: RecordMeta,
+}
+
+/// `pomo -t <title>` keeps running a session in the foreground, the subcommands control sessions
+/// running in the background.
+#[derive(Args, Debug)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+pub struct PomoArgs {
+    #[command(subcommand)]
+    pub command: Option<PomoCommand>,
+    #[command(flatten)]
+    pub session: Option<PomoTask>,
+}
+
+impl PomoArgs {
+    /// Whether the command records a session or an interruption. `status`, `pause` and `resume`
+    /// only talk to the daemon, which records the session itself when it finishes.
+    pub fn writes(&self) -> bool {
+        match &self.command {
+            Some(PomoCommand::Start(_))
+            | Some(PomoCommand::Stop)
+            | Some(PomoCommand::Interrupt(_))
+            | Some(PomoCommand::Log(_)) => true,
+            Some(PomoCommand::Status)
+            | Some(PomoCommand::Pause)
+            | Some(PomoCommand::Resume)
+            | Some(PomoCommand::Daemon) => false,
+            None => self.session.is_some(),
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PomoCommand {
+    /// Start a session, in this terminal unless --detach is given
+    Start(PomoStartArgs),
+    /// Show the session running in the background
+    Status,
+    /// Pause the background session
+    Pause,
+    /// Resume the paused background session
+    Resume,
+    /// Stop the background session and record it
+    Stop,
+    /// Log an interruption of the running session
+    Interrupt(InterruptArgs),
+    /// Record a past session that ran without the timer
+    Log(PomoLogArgs),
+    /// Runs the background session, spawned by `pomo start --detach`
+    #[command(hide = true)]
+    Daemon,
+}
+
+#[derive(Args, Debug)]
+pub struct InterruptArgs {
+    /// Whether the interruption came from yourself or from someone else
+    #[arg(short = 'k', long, value_enum, default_value_t = InterruptionKind::Internal)]
+    pub kind: InterruptionKind,
+    /// What interrupted the session
+    pub note: String,
+}
+
+impl CommandArgs for InterruptArgs {
+    fn validate(&self) -> Result<(), AppError> {
+        if self.note.trim().is_empty() {
+            return Err(AppError::Validation("Note cannot be empty".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Interruptions as the pomodoro technique counts them.
+#[derive(Debug, ValueEnum, PartialEq, Eq, Copy, Clone, Default)]
+pub enum InterruptionKind {
+    /// An urge of your own to do something else
+    #[default]
+    Internal,
+    /// Someone or something else asking for attention
+    External,
+}
+
+impl InterruptionKind {
+    pub fn from_usize(n: usize) -> InterruptionKind {
+        match n {
+            1 => InterruptionKind::External,
+            _ => InterruptionKind::Internal,
+        }
+    }
+
+    pub fn to_usize(self) -> usize {
+        match self {
+            InterruptionKind::Internal => 0,
+            InterruptionKind::External => 1,
+        }
+    }
+}
+
+impl From<InterruptionKind> for String {
+    fn from(k: InterruptionKind) -> Self {
+        match k {
+            InterruptionKind::Internal => "internal".to_string(),
+            InterruptionKind::External => "external".to_string(),
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct PomoStartArgs {
+    #[command(flatten)]
+    pub session: PomoTask,
+    /// Run the countdown in a background process and give the terminal back
+    #[arg(long)]
+    pub detach: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct PomoLogArgs {
+    /// Session title
+    #[arg(short = 't', long)]
+    pub title: String,
+    /// When the session started, a time of today (09:00) or a date and time (2026-06-15 09:00)
+    #[arg(short = 's', long)]
+    pub start: String,
+    /// When the session ended, like --start
+    #[arg(short = 'e', long)]
+    pub end: String,
+    /// Session category
+    #[arg(short = 'c', long)]
+    pub category: Option<String>,
+    /// Id of the task the session worked on
+    #[arg(long = "task")]
+    pub task_id: Option<u64>,
+    /// Whether the session was work or a break
+    #[arg(long = "type", value_enum, default_value_t = PomoType::Work)]
+    pub pomo_type: PomoType,
+}
+
+impl CommandArgs for PomoLogArgs {
+    fn validate(&self) -> Result<(), AppError> {
+        if self.title.trim().is_empty() {
+            return Err(AppError::Validation("Title cannot be empty".to_string()));
+        }
+        Ok(())
+    }
+}
+
+impl PomoLogArgs {
+    /// The finished session running from `--start` to `--end`, both resolved against `now`.
+    pub fn session_at(&self, now: DateTime<Local>) -> Result<PomoTask, AppError> {
+        let start_time = parse_time_at(&self.start, now)?;
+        let end_time = parse_time_at(&self.end, now)?;
+
+        if end_time <= start_time {
+            return Err(AppError::Validation(
+                "--end has to be after --start".to_string(),
+            ));
+        }
+        if end_time > now {
+            return Err(AppError::Validation(
+                "Cannot log a session ending in the future".to_string(),
+            ));
+        }
+
+        let length = DurationField(end_time - start_time);
+        Ok(PomoTask {
+            status: PomoStatus::Finished,
+            pomo_type: self.pomo_type,
+            title: self.title.trim().to_string(),
+            duration: length.clone(),
+            category: self.category.clone(),
+            task_id: self.task_id,
+            elapsed: length,
+            start_time,
+            end_time,
+            ..Default::default()
+        })
+    }
 }
 
 impl Default for PomoTask {
@@ -463,21 +1892,24 @@ impl Default for PomoTask {
             title: "".to_string(),
             duration: DurationField::default(),
             category: None,
+            task_id: None,
+            elapsed: DurationField::from_i64(0),
             start_time: Local::now(),
             end_time: Local::now() + Duration::minutes(25),
             status: PomoStatus::Running,
+            meta: RecordMeta::default(),
         }
     }
 }
 
 impl CommandArgs for PomoTask {
-    fn validate(&self) -> Result<(), String> {
+    fn validate(&self) -> Result<(), AppError> {
         if self.duration.0.is_zero() {
-            return Err("Duration cannot be 0".to_string());
+            return Err(AppError::Validation("Duration cannot be 0".to_string()));
         }
 
         if self.title.trim().is_empty() {
-            return Err("Title cannot be empty".to_string());
+            return Err(AppError::Validation("Title cannot be empty".to_string()));
         }
 
         Ok(())
@@ -489,7 +1921,8 @@ impl TableRow for PomoTask {
         vec![
             "id",
             "title",
-            "duration",
+            "planned",
+            "focused",
             "category",
             "status",
             "type",
@@ -502,6 +1935,7 @@ impl TableRow for PomoTask {
             self.id.to_string(),
             self.title.clone(),
             String::from(self.duration.clone()),
+            String::from(self.elapsed.clone()),
             self.category.clone().unwrap_or_else(|| "-".to_string()),
             String::from(self.status.clone()),
             String::from(self.pomo_type),
@@ -519,10 +1953,12 @@ pub struct PomoLogsArgs {
 }
 
 impl CommandArgs for PomoLogsArgs {
-    fn validate(&self) -> Result<(), String> {
+    fn validate(&self) -> Result<(), AppError> {
         if let Some(since) = &self.since {
             if since < &Local::now().naive_utc().date() {
-                return Err("Since date cannot be before 2020-01-01".to_string());
+                return Err(AppError::Validation(
+                    "Since date cannot be before 2020-01-01".to_string(),
+                ));
             };
         };
 
@@ -592,44 +2028,360 @@ impl Display for Priority {
     }
 }
 
-pub fn parse_date(s: &str) -> Result<DateTime<Local>, String> {
-    let len = s.len();
-    if len < 2 {
-        return Err(
-            "argument format must be a number followed by a unit (d, M, y) like 10d for 10 days"
-                .to_string(),
-        );
+const DATE_FORMAT_HELP: &str = "expected today, tomorrow, eow, eom, a weekday (fri, next fri), \
+an offset (4h, 2d, 2w, 1m, 1y, in 2 weeks), a month and day (nov 3) \
+or an absolute date (2026-11-03, 2026-11-03 14:00)";
+
+/// When a task is due: an exact instant for timed tasks, or the end of the day in the home time
+/// zone for all-day tasks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DueDate {
+    pub at: DateTime<Local>,
+    pub all_day: bool,
+}
+
+impl DueDate {
+    pub fn timed(at: DateTime<Local>) -> Self {
+        DueDate { at, all_day: false }
     }
 
-    let (value_str, unit) = s.split_at(len - 1);
-    let value: u32 = value_str
-        .parse()
-        .map_err(|_| format!("Cannot parse '{}' as a number", value_str))?;
+    /// An all-day due date on the given day of `tz`.
+    pub fn all_day_on<Z: TimeZone>(date: NaiveDate, tz: &Z) -> Result<Self, AppError> {
+        Ok(DueDate {
+            at: end_of_day(date, tz)?.with_timezone(&Local),
+            all_day: true,
+        })
+    }
 
-    let val = value as i64;
-    match unit {
-        "d" => Ok(Local::now() + Duration::days(val)),
-        "m" => Ok(Local::now() + Duration::days(val * 30)),
-        "y" => {
-            let date = Local::now();
-            match date.with_year(date.year() + (value as i32)) {
-                Some(d) => Ok(d),
-                None => Err(format!("could not parse date {}", s)),
-            }
+    /// Formats the due date in `tz`, with the time only for timed tasks.
+    pub fn format_in<Z: TimeZone>(&self, tz: &Z) -> String
+    where
+        Z::Offset: Display,
+    {
+        let at = self.at.with_timezone(tz);
+        if self.all_day {
+            at.format("%Y-%m-%d").to_string()
+        } else {
+            at.format("%Y-%m-%d %H:%M").to_string()
         }
-        _ => {
-            // check if possible to parse it like YYYY-MM-DD
-            let date = DateTime::parse_from_str(s, "%Y-%m-%d").map_err(|e| e.to_string())?;
+    }
+}
+
+impl Display for DueDate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.format_in(&home_timezone()))
+    }
+}
+
+/// When a task reminder fires.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Remind {
+    /// Some time before the due date
+    Before(Duration),
+    At(DateTime<Local>),
+}
+
+/// Clap value parser for reminders: `<offset>-before` like `1h-before`, `30m-before` or
+/// `2d-before`, or any due date accepted by [`parse_due`].
+pub fn parse_remind(s: &str) -> Result<Remind, AppError> {
+    let input = s.trim().to_lowercase();
+
+    match input
+        .strip_suffix("-before")
+        .or(input.strip_suffix(" before"))
+    {
+        Some(offset) => {
+            let split = offset.find(|c: char| !c.is_ascii_digit()).ok_or_else(|| {
+                AppError::Validation(format!("missing unit in reminder '{}' (m, h, d or w)", s))
+            })?;
+            let (value, unit) = offset.split_at(split);
+            let value: i64 = value.parse().map_err(|_| {
+                AppError::Validation(format!("Cannot parse '{}' as a number", value))
+            })?;
+
+            let offset = match unit.trim() {
+                "m" | "min" | "minutes" => Duration::minutes(value),
+                "h" | "hour" | "hours" => Duration::hours(value),
+                "d" | "day" | "days" => Duration::days(value),
+                "w" | "week" | "weeks" => Duration::weeks(value),
+                _ => {
+                    return Err(AppError::Validation(format!(
+                        "unknown unit in reminder '{}' (m, h, d or w)",
+                        s
+                    )))
+                }
+            };
+
+            Ok(Remind::Before(offset))
+        }
+        None => parse_due(s).map(|due| Remind::At(due.at)),
+    }
+}
+
+/// Clap value parser for due dates, resolving days in the home time zone.
+pub fn parse_due(s: &str) -> Result<DueDate, AppError> {
+    parse_due_at(s, Utc::now().with_timezone(&home_timezone()))
+}
 
-            Ok(date.with_timezone(&Local))
+/// Parses a due date relative to `now`. Values naming a day, including day, week, month and
+/// year offsets, are all-day; hour offsets and values with a time of day are timed.
+pub fn parse_due_at<Z: TimeZone>(s: &str, now: DateTime<Z>) -> Result<DueDate, AppError> {
+    let tz = now.timezone();
+    let (date, names_day) = parse_date_parts(s, now)?;
+
+    if names_day {
+        DueDate::all_day_on(date.date_naive(), &tz)
+    } else {
+        Ok(DueDate::timed(date.with_timezone(&Local)))
+    }
+}
+
+pub fn parse_date(s: &str) -> Result<DateTime<Local>, AppError> {
+    parse_date_at(s, Local::now())
+}
+
+/// Parses a date relative to `now`. Offsets (`4h`, `2w`, `in 3 days`) keep the time of
+/// `now`, while values naming a day (`tomorrow`, `fri`, `nov 3`, `2026-11-03`) resolve to the
+/// end of that day.
+pub fn parse_date_at<Z: TimeZone>(s: &str, now: DateTime<Z>) -> Result<DateTime<Z>, AppError> {
+    parse_date_parts(s, now).map(|(date, _)| date)
+}
+
+/// Parses a point in time: a time of day (`09:00`) on the day of `now`, or any date of
+/// [`parse_date_at`] that does not name a whole day.
+pub fn parse_time_at<Z: TimeZone>(s: &str, now: DateTime<Z>) -> Result<DateTime<Z>, AppError> {
+    if let Ok(time) = NaiveTime::parse_from_str(s.trim(), "%H:%M") {
+        return now
+            .timezone()
+            .from_local_datetime(&now.date_naive().and_time(time))
+            .earliest()
+            .ok_or_else(|| {
+                AppError::Validation(format!("{} does not exist in the local time zone", s))
+            });
+    }
+
+    match parse_date_parts(s, now)? {
+        (_, true) => Err(AppError::Validation(format!(
+            "'{}' names a day, expected a time (09:00) or a date and time (2026-06-15 09:00)",
+            s
+        ))),
+        (date, false) => Ok(date),
+    }
+}
+
+/// Parses like [`parse_date_at`], also telling whether the value names a day rather than a
+/// time of day.
+fn parse_date_parts<Z: TimeZone>(
+    s: &str,
+    now: DateTime<Z>,
+) -> Result<(DateTime<Z>, bool), AppError> {
+    let input = s.trim().to_lowercase();
+    let input = input.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if input.is_empty() {
+        return Err(AppError::Validation(format!(
+            "empty date, {}",
+            DATE_FORMAT_HELP
+        )));
+    }
+
+    let tz = now.timezone();
+    let today = now.date_naive();
+    let day = |date: NaiveDate| end_of_day(date, &tz).map(|d| (d, true));
+
+    match input.as_str() {
+        "today" => return day(today),
+        "tomorrow" => return day(today + Duration::days(1)),
+        "eow" => {
+            let days_left = 6 - today.weekday().num_days_from_monday() as i64;
+            return day(today + Duration::days(days_left));
+        }
+        "eom" => {
+            let next_month = today
+                .with_day(1)
+                .and_then(|d| d.checked_add_months(Months::new(1)))
+                .ok_or_else(|| {
+                    AppError::Validation(format!("could not compute the end of month of {}", today))
+                })?;
+            return day(next_month - Duration::days(1));
+        }
+        _ => {}
+    }
+
+    if let Some(offset) = input.strip_prefix("in ") {
+        let (value, unit) = offset.split_once(' ').ok_or_else(|| {
+            AppError::Validation(format!(
+                "could not parse date '{}', {}",
+                s, DATE_FORMAT_HELP
+            ))
+        })?;
+        return add_offset(now, value, unit, s);
+    }
+
+    if let Some(weekday) = input.strip_prefix("next ") {
+        let weekday = parse_weekday(weekday).ok_or_else(|| {
+            AppError::Validation(format!(
+                "could not parse weekday in '{}', {}",
+                s, DATE_FORMAT_HELP
+            ))
+        })?;
+        return day(next_weekday(today, weekday, false));
+    }
+
+    if let Some(weekday) = parse_weekday(&input) {
+        return day(next_weekday(today, weekday, true));
+    }
+
+    if let Some(split) = input.find(|c: char| !c.is_ascii_digit()) {
+        let (value, unit) = input.split_at(split);
+        if !value.is_empty() && unit.chars().all(|c| c.is_ascii_alphabetic()) {
+            return add_offset(now, value, unit, s);
+        }
+    }
+
+    for format in ["%Y-%m-%d %H:%M", "%Y-%m-%dt%H:%M", "%Y-%m-%d %H:%M:%S"] {
+        if let Ok(date_time) = NaiveDateTime::parse_from_str(&input, format) {
+            return tz
+                .from_local_datetime(&date_time)
+                .earliest()
+                .map(|d| (d, false))
+                .ok_or_else(|| {
+                    AppError::Validation(format!("{} does not exist in the local time zone", s))
+                });
+        }
+    }
+
+    if let Ok(date) = NaiveDate::parse_from_str(&input, "%Y-%m-%d") {
+        return day(date);
+    }
+
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(s.trim()) {
+        return Ok((date_time.with_timezone(&tz), false));
+    }
+
+    if let Some(date) = parse_month_day(&input, today) {
+        return day(date);
+    }
+
+    Err(AppError::Validation(format!(
+        "could not parse date '{}', {}",
+        s, DATE_FORMAT_HELP
+    )))
+}
+
+/// Adds `value` units to `now`, telling whether the unit is a whole day or longer.
+fn add_offset<Z: TimeZone>(
+    now: DateTime<Z>,
+    value: &str,
+    unit: &str,
+    input: &str,
+) -> Result<(DateTime<Z>, bool), AppError> {
+    let value: u32 = value
+        .parse()
+        .map_err(|_| AppError::Validation(format!("Cannot parse '{}' as a number", value)))?;
+    let val = value as i64;
+
+    let (date, names_day) = match unit {
+        "h" | "hour" | "hours" => (Some(now + Duration::hours(val)), false),
+        // Calendar days, a day with a daylight saving change is not 24 hours long.
+        "d" | "day" | "days" => (now.checked_add_days(Days::new(value as u64)), true),
+        "w" | "week" | "weeks" => (now.checked_add_days(Days::new(value as u64 * 7)), true),
+        "m" | "month" | "months" => (now.checked_add_months(Months::new(value)), true),
+        "y" | "year" | "years" => (
+            value
+                .checked_mul(12)
+                .and_then(|months| now.checked_add_months(Months::new(months))),
+            true,
+        ),
+        _ => {
+            return Err(AppError::Validation(format!(
+                "unknown unit '{}' in '{}', {}",
+                unit, input, DATE_FORMAT_HELP
+            )))
         }
+    };
+
+    date.map(|d| (d, names_day))
+        .ok_or_else(|| AppError::Validation(format!("could not parse date {}", input)))
+}
+
+/// The last second of the given day in `tz`.
+pub fn end_of_day<Z: TimeZone>(date: NaiveDate, tz: &Z) -> Result<DateTime<Z>, AppError> {
+    date.and_hms_opt(23, 59, 59)
+        .and_then(|d| tz.from_local_datetime(&d).latest())
+        .ok_or_else(|| {
+            AppError::Validation(format!("could not resolve the end of day of {}", date))
+        })
+}
+
+fn parse_weekday(s: &str) -> Option<Weekday> {
+    match s {
+        "mon" | "monday" => Some(Weekday::Mon),
+        "tue" | "tues" | "tuesday" => Some(Weekday::Tue),
+        "wed" | "wednesday" => Some(Weekday::Wed),
+        "thu" | "thur" | "thurs" | "thursday" => Some(Weekday::Thu),
+        "fri" | "friday" => Some(Weekday::Fri),
+        "sat" | "saturday" => Some(Weekday::Sat),
+        "sun" | "sunday" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+/// The first `weekday` after `today`, or `today` itself when it matches and `include_today` is set.
+fn next_weekday(today: NaiveDate, weekday: Weekday, include_today: bool) -> NaiveDate {
+    let mut days = (7 + weekday.num_days_from_monday() as i64
+        - today.weekday().num_days_from_monday() as i64)
+        % 7;
+    if days == 0 && !include_today {
+        days = 7;
     }
+    today + Duration::days(days)
+}
+
+/// Parses `nov 3`, `3 nov` or `november 3` as the next such day on or after `today`.
+fn parse_month_day(s: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let (first, second) = s.split_once(' ')?;
+    let (month, day) = match (parse_month(first), parse_month(second)) {
+        (Some(month), None) => (month, second),
+        (None, Some(month)) => (month, first),
+        _ => return None,
+    };
+    let day: u32 = day.parse().ok()?;
+
+    let this_year = NaiveDate::from_ymd_opt(today.year(), month, day);
+    match this_year {
+        Some(date) if date >= today => Some(date),
+        _ => NaiveDate::from_ymd_opt(today.year() + 1, month, day),
+    }
+}
+
+fn parse_month(s: &str) -> Option<u32> {
+    const MONTHS: [&str; 12] = [
+        "january",
+        "february",
+        "march",
+        "april",
+        "may",
+        "june",
+        "july",
+        "august",
+        "september",
+        "october",
+        "november",
+        "december",
+    ];
+
+    MONTHS
+        .iter()
+        .position(|m| *m == s || (s.len() == 3 && m.starts_with(s)))
+        .map(|i| i as u32 + 1)
+        .or_else(|| (s == "sept").then_some(9))
 }
 
-pub fn parse_duration(duration_str: &str) -> Result<DurationField, String> {
+pub fn parse_duration(duration_str: &str) -> Result<DurationField, AppError> {
     DurationField::from_str(duration_str)
 }
 
+#[derive(Clone, Copy, Debug)]
 pub enum Color {
     Red,
     Green,
@@ -637,88 +2389,394 @@ pub enum Color {
     Cyan,
 }
 
-pub fn format_string_with_color(str: &str, color: Color) -> String {
-    match color {
-        Color::Red => format!("\x1b[91m{}\x1b[0m", str),
-        Color::Green => format!("\x1b[92m{}\x1b[0m", str),
-        Color::Yellow => format!("\x1b[93m{}\x1b[0m", str),
-        Color::Cyan => format!("\x1b[96m{}\x1b[0m", str),
+pub fn format_string_with_color(str: &str, color: Color) -> String {
+    match color {
+        Color::Red => format!("\x1b[91m{}\x1b[0m", str),
+        Color::Green => format!("\x1b[92m{}\x1b[0m", str),
+        Color::Yellow => format!("\x1b[93m{}\x1b[0m", str),
+        Color::Cyan => format!("\x1b[96m{}\x1b[0m", str),
+    }
+}
+
+/// A bar of `width` cells filled to `ratio`, capped at full, followed by the percentage.
+pub fn ratio_bar(ratio: f64, width: usize) -> String {
+    let ratio = ratio.clamp(0.0, 1.0);
+    let filled = (ratio * width as f64).round() as usize;
+
+    format!(
+        "{}{} {:>3.0}%",
+        "█".repeat(filled),
+        "░".repeat(width - filled),
+        ratio * 100.0
+    )
+}
+
+#[derive(Clone, Debug)]
+pub enum PomodoroEvent {
+    Resize(u16, u16),
+    /// A key pressed other than ctrl-c, enter is sent as `'\n'`, backspace as `'\x08'` and escape
+    /// as `'\x1b'`
+    Key(char),
+    Quit,
+}
+
+/// What a key does while the countdown runs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CountdownAction {
+    AddMinute,
+    RemoveMinute,
+    /// End the session now and go straight to the break, or back to work from a break
+    Skip,
+    /// Start typing a note of an interruption
+    Note(InterruptionKind),
+    ToggleHelp,
+    Quit,
+}
+
+impl CountdownAction {
+    pub fn from_key(key: char) -> Option<CountdownAction> {
+        match key {
+            '+' | '=' => Some(CountdownAction::AddMinute),
+            '-' => Some(CountdownAction::RemoveMinute),
+            's' => Some(CountdownAction::Skip),
+            'n' => Some(CountdownAction::Note(InterruptionKind::Internal)),
+            'e' => Some(CountdownAction::Note(InterruptionKind::External)),
+            '?' | 'h' => Some(CountdownAction::ToggleHelp),
+            'q' => Some(CountdownAction::Quit),
+            _ => None,
+        }
+    }
+}
+
+/// What to do once a session's time is up.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SessionEndChoice {
+    /// Keep the session going for a few more minutes
+    Extend,
+    /// Record the session and start a break, or a work session after a break
+    Next,
+    /// Record the session and leave
+    Done,
+}
+
+impl SessionEndChoice {
+    pub fn from_key(key: char) -> Option<SessionEndChoice> {
+        match key.to_ascii_lowercase() {
+            'e' | '+' => Some(SessionEndChoice::Extend),
+            'b' | 'w' => Some(SessionEndChoice::Next),
+            'd' | 'q' | '\n' => Some(SessionEndChoice::Done),
+            _ => None,
+        }
+    }
+
+    /// The choices line of the time's up screen of a session of the given type.
+    pub fn prompt(pomo_type: PomoType, extend: &DurationField) -> String {
+        let next = if pomo_type.is_work() {
+            "[b] break"
+        } else {
+            "[w] work"
+        };
+        format!("[e] +{}  {}  [d] done", String::from(extend.clone()), next)
+    }
+}
+
+/// Ways a finished pomodoro gets your attention.
+#[derive(Debug, ValueEnum, PartialEq, Eq, Copy, Clone)]
+pub enum PomoAlert {
+    /// Ring the terminal bell
+    Bell,
+    /// Flash the time's up box
+    Flash,
+    /// Run the `pomo_alert_command` hook
+    Command,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct AppState {
+    pub title: String,
+    /// Category of the session
+    pub category: Option<String>,
+    /// The task the session works on
+    pub task: Option<String>,
+    pub term_width: u16,
+    pub term_height: u16,
+    /// Time left on the countdown
+    pub current_time: std::time::Duration,
+    /// Full length of the session, including extensions
+    pub duration: std::time::Duration,
+    /// Whether the key help replaces the digits
+    pub show_help: bool,
+    /// The kind and note of the interruption being typed
+    pub note: Option<(InterruptionKind, String)>,
+    /// Whether `current_time` counts up from the start of a time entry instead of down
+    pub stopwatch: bool,
+    pub quited: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct TaskAnalysis {
+    pub bucket: String,
+    pub total: u64,
+    pub done: u64,
+    pub open: u64,
+    /// Open tasks whose due date has already passed
+    pub overdue: u64,
+    /// Done tasks completed after their due date
+    pub done_late: u64,
+    pub completion_rate: f64,
+    /// Share of the done tasks completed on time, if any is done
+    pub on_time_rate: Option<f64>,
+    /// Average days from creation to completion of the done tasks, if any
+    pub avg_lead_time: Option<f64>,
+}
+
+impl TableRow for TaskAnalysis {
+    fn headers(&self) -> Vec<&'static str> {
+        vec![
+            "Bucket",
+            "Total Tasks",
+            "Done Tasks",
+            "Open Tasks",
+            "Overdue Tasks",
+            "Done Late",
+            "Completion Rate (%)",
+            "On Time Rate (%)",
+            "Avg Lead Time (days)",
+        ]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.bucket.clone(),
+            self.total.to_string(),
+            self.done.to_string(),
+            self.open.to_string(),
+            self.overdue.to_string(),
+            self.done_late.to_string(),
+            format!("{:.2}%", self.completion_rate),
+            self.on_time_rate
+                .map_or_else(|| "-".to_string(), |r| format!("{:.2}%", r)),
+            self.avg_lead_time
+                .map_or_else(|| "-".to_string(), |d| format!("{:.2}", d)),
+        ]
+    }
+}
+
+/// Flow metrics of a range: how fast tasks get done and how old the open ones are.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FlowAnalysis {
+    pub completed: usize,
+    /// Tasks completed per week over the range
+    pub throughput: f64,
+    pub cycle_time_p50: Option<f64>,
+    pub cycle_time_p90: Option<f64>,
+    pub open: usize,
+    pub open_age_avg: Option<f64>,
+    pub open_age_max: Option<f64>,
+}
+
+impl FlowAnalysis {
+    /// Builds the metrics from the cycle times (days from creation to completion) of the tasks
+    /// completed within `range_days` and the ages in days of the currently open tasks.
+    pub fn from_samples(cycle_times: Vec<f64>, open_ages: Vec<f64>, range_days: f64) -> Self {
+        let mut cycle_times = cycle_times;
+        cycle_times.sort_by(|a, b| a.total_cmp(b));
+
+        let throughput = if range_days > 0.0 {
+            cycle_times.len() as f64 * 7.0 / range_days
+        } else {
+            0.0
+        };
+
+        let open_age_avg = if open_ages.is_empty() {
+            None
+        } else {
+            Some(open_ages.iter().sum::<f64>() / open_ages.len() as f64)
+        };
+
+        FlowAnalysis {
+            completed: cycle_times.len(),
+            throughput,
+            cycle_time_p50: percentile(&cycle_times, 50),
+            cycle_time_p90: percentile(&cycle_times, 90),
+            open: open_ages.len(),
+            open_age_avg,
+            open_age_max: open_ages.iter().copied().reduce(f64::max),
+        }
     }
 }
 
-#[derive(Clone, Debug)]
-pub enum PomodoroEvent {
-    Resize(u16, u16),
-    Quit,
+/// Nearest-rank percentile of already sorted values.
+pub fn percentile(sorted: &[f64], p: usize) -> Option<f64> {
+    if sorted.is_empty() {
+        return None;
+    }
+
+    let rank = (p * sorted.len()).div_ceil(100).max(1);
+    Some(sorted[rank.min(sorted.len()) - 1])
 }
 
-#[derive(Clone, Debug, Default)]
-pub struct AppState {
-    pub title: String,
-    pub term_width: u16,
-    pub term_height: u16,
-    pub current_time: std::time::Duration,
-    pub quited: bool,
+impl TableRow for FlowAnalysis {
+    fn headers(&self) -> Vec<&'static str> {
+        vec![
+            "Completed",
+            "Throughput (per week)",
+            "Cycle Time p50 (days)",
+            "Cycle Time p90 (days)",
+            "Open Tasks",
+            "Avg Open Age (days)",
+            "Oldest Open (days)",
+        ]
+    }
+
+    fn row(&self) -> Vec<String> {
+        let days = |d: Option<f64>| d.map_or_else(|| "-".to_string(), |d| format!("{:.2}", d));
+
+        vec![
+            self.completed.to_string(),
+            format!("{:.2}", self.throughput),
+            days(self.cycle_time_p50),
+            days(self.cycle_time_p90),
+            self.open.to_string(),
+            days(self.open_age_avg),
+            days(self.open_age_max),
+        ]
+    }
 }
 
-#[derive(Debug, Clone)]
-pub struct DailyAnalysis {
-    pub date: String,
-    pub total: u64,
-    pub pending: u64,
-    pub delivered_over_due: u64,
-    pub burn_down_rate: f64,
+/// Interruptions logged during one work session.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionInterruptions {
+    pub id: u64,
+    pub title: String,
+    pub start_time: DateTime<Local>,
+    pub category: Option<String>,
+    pub internal: u64,
+    pub external: u64,
 }
 
-impl TableRow for DailyAnalysis {
+impl TableRow for SessionInterruptions {
     fn headers(&self) -> Vec<&'static str> {
         vec![
-            "Date",
-            "Total Tasks",
-            "Pending Tasks",
-            "Delivered Over Due",
-            "Burn Down Rate (%)",
+            "Session", "Title", "Start", "Category", "Internal", "External",
+        ]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            self.title.clone(),
+            self.start_time.format("%Y-%m-%d %H:%M").to_string(),
+            self.category.clone().unwrap_or_else(|| "-".to_string()),
+            self.internal.to_string(),
+            self.external.to_string(),
         ]
     }
+}
+
+/// Interruptions logged in one hour of the day, in local time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HourInterruptions {
+    pub hour: String,
+    pub internal: u64,
+    pub external: u64,
+}
+
+impl TableRow for HourInterruptions {
+    fn headers(&self) -> Vec<&'static str> {
+        vec!["Hour", "Internal", "External", "Total"]
+    }
 
     fn row(&self) -> Vec<String> {
         vec![
-            self.date.clone(),
-            format!("{:.2}", self.total),
-            format!("{:.2}", self.pending),
-            format!("{:.2}", self.delivered_over_due),
-            format!("{:.2}%", self.burn_down_rate),
+            format!("{}:00", self.hour),
+            self.internal.to_string(),
+            self.external.to_string(),
+            (self.internal + self.external).to_string(),
         ]
     }
 }
 
-#[derive(Debug, Clone, Default)]
-pub struct PomoAnalysis {
-    pub date: String,
-    pub total_sessions: u32,
-    pub total_duration: DurationField,
-    pub average_duration: DurationField,
-    pub done_to_pause_ratio: f64,
+/// Focus quality of the work sessions of a category.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CategoryInterruptions {
+    pub category: String,
+    pub sessions: u64,
+    pub internal: u64,
+    pub external: u64,
+    /// Sessions without a single interruption
+    pub clean: u64,
+}
+
+impl CategoryInterruptions {
+    /// Totals the sessions per category, the most interrupted categories first.
+    pub fn from_sessions(sessions: &[SessionInterruptions]) -> Vec<CategoryInterruptions> {
+        let mut categories: Vec<CategoryInterruptions> = Vec::new();
+
+        for session in sessions {
+            let name = session.category.clone().unwrap_or_else(|| "-".to_string());
+            let index = match categories.iter().position(|c| c.category == name) {
+                Some(index) => index,
+                None => {
+                    categories.push(CategoryInterruptions {
+                        category: name,
+                        sessions: 0,
+                        internal: 0,
+                        external: 0,
+                        clean: 0,
+                    });
+                    categories.len() - 1
+                }
+            };
+
+            let category = &mut categories[index];
+            category.sessions += 1;
+            category.internal += session.internal;
+            category.external += session.external;
+            if session.internal + session.external == 0 {
+                category.clean += 1;
+            }
+        }
+
+        categories.sort_by(|a, b| {
+            b.per_session()
+                .total_cmp(&a.per_session())
+                .then_with(|| a.category.cmp(&b.category))
+        });
+        categories
+    }
+
+    pub fn per_session(&self) -> f64 {
+        if self.sessions == 0 {
+            return 0.0;
+        }
+        (self.internal + self.external) as f64 / self.sessions as f64
+    }
 }
 
-impl TableRow for PomoAnalysis {
+impl TableRow for CategoryInterruptions {
     fn headers(&self) -> Vec<&'static str> {
         vec![
-            "Date",
-            "Total Sessions",
-            "Total Duration",
-            "Average Duration",
-            "Done to Pause Ratio",
+            "Category",
+            "Sessions",
+            "Internal",
+            "External",
+            "Per Session",
+            "Clean Sessions (%)",
         ]
     }
 
     fn row(&self) -> Vec<String> {
         vec![
-            self.date.clone(),
-            self.total_sessions.to_string(),
-            String::from(self.total_duration.clone()),
-            String::from(self.average_duration.clone()),
-            format!("{:.2}", self.done_to_pause_ratio),
+            self.category.clone(),
+            self.sessions.to_string(),
+            self.internal.to_string(),
+            self.external.to_string(),
+            format!("{:.2}", self.per_session()),
+            format!(
+                "{:.2}%",
+                self.clean as f64 * 100.0 / self.sessions.max(1) as f64
+            ),
         ]
     }
 }
@@ -733,9 +2791,12 @@ mod tests {
             id: 1,
             status: Default::default(),
             title: "My Task".to_string(),
-            due_date: Local::now(),
+            due_date: DueDate::timed(Local::now()),
             priority: Priority::High,
             category: Some("Work".to_string()),
+            remind: None,
+            tags: Vec::new(),
+            meta: RecordMeta::default(),
         };
 
         assert_eq!(task.id, 1);
@@ -744,15 +2805,60 @@ mod tests {
         assert_eq!(task.category, Some("Work".to_string()));
     }
 
+    #[test]
+    fn test_task_ref_parsing() {
+        assert_eq!("42".parse::<TaskRef>().unwrap(), TaskRef::Id(42));
+        assert_eq!(
+            "3F2a-9".parse::<TaskRef>().unwrap(),
+            TaskRef::Uuid("3f2a-9".to_string())
+        );
+        assert!("3f2".parse::<TaskRef>().is_err());
+        assert!("abcz".parse::<TaskRef>().is_err());
+        assert_eq!(TaskRef::Id(42).to_string(), "#42");
+    }
+
+    #[test]
+    fn test_unique_prefix_len() {
+        let uuids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(unique_prefix_len(&[]), 6);
+        assert_eq!(unique_prefix_len(&uuids(&["0a", "1b"])), 6);
+        assert_eq!(
+            unique_prefix_len(&uuids(&["1234abcd-1", "1234abcd-2", "9999"])),
+            10
+        );
+
+        let meta = RecordMeta {
+            uuid: "1234abcd-1".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(meta.short_uuid(6), "1234ab");
+        assert_eq!(meta.short_uuid(20), "1234abcd-1");
+
+        // A prefix of digits alone would be taken for an id.
+        let digits = RecordMeta {
+            uuid: "31532004-1a2b-4c3d-8e4f-000000000000".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(digits.short_uuid(6), "31532004-1a");
+        assert!(matches!(
+            digits.short_uuid(6).parse::<TaskRef>(),
+            Ok(TaskRef::Uuid(_))
+        ));
+    }
+
     #[test]
     fn test_task_default_due_date() {
         let task = Task {
             id: 2,
             status: Default::default(),
             title: "Another Task".to_string(),
-            due_date: Local::now(),
+            due_date: DueDate::timed(Local::now()),
             priority: Priority::Medium,
             category: None,
+            remind: None,
+            tags: Vec::new(),
+            meta: RecordMeta::default(),
         };
         //  The default value is parsed from "1d", so we need a way to calculate what that date should be
         //  We can't directly compare with "1d"
@@ -760,7 +2866,7 @@ mod tests {
         let expected_due_date = now + chrono::Duration::seconds(1);
 
         // Check if the due date is within a small time window to account for test execution time
-        let time_diff = task.due_date.timestamp() - expected_due_date.timestamp();
+        let time_diff = task.due_date.at.timestamp() - expected_due_date.timestamp();
         assert!(time_diff.abs() < 2); // Allow a 2-second difference
 
         assert_eq!(task.category, None);
@@ -775,8 +2881,11 @@ mod tests {
             title: "Work Session".to_string(),
             duration: DurationField(Duration::seconds(25 * 60)), // 25 minutes
             category: Some("Project A".to_string()),
+            task_id: None,
+            elapsed: DurationField::from_i64(0),
             start_time: Local::now(),
             end_time: Local::now(),
+            meta: RecordMeta::default(),
         };
 
         assert_eq!(task.id, 1);
@@ -785,6 +2894,658 @@ mod tests {
         assert_eq!(task.category, Some("Project A".to_string()));
     }
 
+    #[test]
+    fn test_percentile_nearest_rank() {
+        let values: Vec<f64> = (1..=10).map(|v| v as f64).collect();
+        assert_eq!(percentile(&values, 50), Some(5.0));
+        assert_eq!(percentile(&values, 90), Some(9.0));
+        assert_eq!(percentile(&values, 100), Some(10.0));
+        assert_eq!(percentile(&values[..1], 90), Some(1.0));
+        assert_eq!(percentile(&[], 50), None);
+    }
+
+    #[test]
+    fn test_flow_analysis_from_samples() {
+        let flow = FlowAnalysis::from_samples(vec![3.0, 1.0, 2.0, 10.0], vec![4.0, 2.0], 14.0);
+
+        assert_eq!(flow.completed, 4);
+        assert_eq!(flow.throughput, 2.0);
+        assert_eq!(flow.cycle_time_p50, Some(2.0));
+        assert_eq!(flow.cycle_time_p90, Some(10.0));
+        assert_eq!(flow.open, 2);
+        assert_eq!(flow.open_age_avg, Some(3.0));
+        assert_eq!(flow.open_age_max, Some(4.0));
+    }
+
+    #[test]
+    fn test_analyze_range_in_the_home_timezone() {
+        let args = AnalyzeArgs {
+            analyze_type: AnalyzeType::Tasks,
+            days: 1,
+            group_by: GroupBy::Day,
+            from: Some(NaiveDate::from_ymd_opt(2026, 6, 10).unwrap()),
+            to: Some(NaiveDate::from_ymd_opt(2026, 6, 11).unwrap()),
+        };
+        let utc = |s: &str| DateTime::parse_from_rfc3339(s).unwrap();
+        let now = utc("2026-06-12T12:00:00+00:00").with_timezone(&chrono_tz::Asia::Tokyo);
+
+        let (start, end) = args.date_range(now).unwrap();
+        assert_eq!(start, utc("2026-06-09T15:00:00+00:00"));
+        assert_eq!(end, utc("2026-06-11T15:00:00+00:00"));
+
+        let args = AnalyzeArgs {
+            from: None,
+            to: None,
+            ..args
+        };
+        let (start, end) = args.date_range(now).unwrap();
+        assert_eq!(start, utc("2026-06-11T12:00:00+00:00"));
+        assert_eq!(end, utc("2026-06-13T12:00:00+00:00"));
+    }
+
+    #[test]
+    fn test_timesheet_range_in_the_home_timezone() {
+        let args = TimesheetArgs {
+            from: None,
+            to: None,
+            round: None,
+            format: TimesheetFormat::Csv,
+            output: None,
+        };
+        let utc = |s: &str| DateTime::parse_from_rfc3339(s).unwrap();
+        // Thursday morning in Tokyo, still Wednesday in UTC.
+        let now = utc("2026-06-10T22:00:00+00:00").with_timezone(&chrono_tz::Asia::Tokyo);
+
+        assert_eq!(
+            args.date_range(now).unwrap(),
+            (
+                utc("2026-06-07T15:00:00+00:00").with_timezone(&Local),
+                utc("2026-06-11T15:00:00+00:00").with_timezone(&Local)
+            )
+        );
+    }
+
+    #[test]
+    fn test_report_period_ranges() {
+        let at = |d: &str| {
+            chrono::NaiveDateTime::parse_from_str(d, "%Y-%m-%d %H:%M")
+                .unwrap()
+                .and_local_timezone(Local)
+                .unwrap()
+        };
+        let now = at("2026-06-11 15:30");
+
+        assert_eq!(
+            ReportPeriod::Day.date_range(now).unwrap(),
+            (at("2026-06-11 00:00"), at("2026-06-12 00:00"))
+        );
+        assert_eq!(
+            ReportPeriod::Week.date_range(now).unwrap(),
+            (at("2026-06-08 00:00"), at("2026-06-15 00:00"))
+        );
+        assert_eq!(
+            ReportPeriod::Month.date_range(now).unwrap(),
+            (at("2026-06-01 00:00"), at("2026-07-01 00:00"))
+        );
+
+        // Already July in Tokyo.
+        let utc = |s: &str| DateTime::parse_from_rfc3339(s).unwrap();
+        let now = utc("2026-06-30T20:00:00+00:00").with_timezone(&chrono_tz::Asia::Tokyo);
+        assert_eq!(
+            ReportPeriod::Month.date_range(now).unwrap(),
+            (
+                utc("2026-06-30T15:00:00+00:00").with_timezone(&Local),
+                utc("2026-07-31T15:00:00+00:00").with_timezone(&Local)
+            )
+        );
+    }
+
+    #[test]
+    fn test_report_top_categories() {
+        let done = |category: Option<&str>| Task {
+            category: category.map(|c| c.to_string()),
+            status: TaskStatus::Done,
+            ..Default::default()
+        };
+        let report = ReportData {
+            completed: vec![
+                done(Some("b")),
+                done(Some("a")),
+                done(None),
+                done(Some("a")),
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            report.top_categories(2),
+            vec![("a".to_string(), 2), ("-".to_string(), 1)]
+        );
+    }
+
+    // 2026-06-10 is a wednesday, far from any daylight saving transition.
+    fn fixed_now() -> DateTime<Local> {
+        local("2026-06-10 15:30:00")
+    }
+
+    fn local(s: &str) -> DateTime<Local> {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
+            .unwrap()
+            .and_local_timezone(Local)
+            .unwrap()
+    }
+
+    fn assert_parses(input: &str, expected: &str) {
+        assert_eq!(
+            parse_date_at(input, fixed_now()).unwrap(),
+            local(expected),
+            "parsing '{}'",
+            input
+        );
+    }
+
+    #[test]
+    fn test_parse_date_keywords() {
+        assert_parses("today", "2026-06-10 23:59:59");
+        assert_parses("Tomorrow", "2026-06-11 23:59:59");
+        assert_parses("  tomorrow ", "2026-06-11 23:59:59");
+        assert_parses("eow", "2026-06-14 23:59:59");
+        assert_parses("eom", "2026-06-30 23:59:59");
+    }
+
+    #[test]
+    fn test_parse_date_end_of_month_and_week_edges() {
+        let sunday = local("2026-06-14 10:00:00");
+        assert_eq!(
+            parse_date_at("eow", sunday).unwrap(),
+            local("2026-06-14 23:59:59")
+        );
+        let february = local("2028-02-03 10:00:00");
+        assert_eq!(
+            parse_date_at("eom", february).unwrap(),
+            local("2028-02-29 23:59:59")
+        );
+        let december = local("2026-12-31 10:00:00");
+        assert_eq!(
+            parse_date_at("eom", december).unwrap(),
+            local("2026-12-31 23:59:59")
+        );
+    }
+
+    #[test]
+    fn test_parse_date_weekdays() {
+        assert_parses("fri", "2026-06-12 23:59:59");
+        assert_parses("friday", "2026-06-12 23:59:59");
+        assert_parses("next fri", "2026-06-12 23:59:59");
+        assert_parses("mon", "2026-06-15 23:59:59");
+        assert_parses("tues", "2026-06-16 23:59:59");
+        // the current weekday is today, `next` skips to the following week
+        assert_parses("wed", "2026-06-10 23:59:59");
+        assert_parses("next wed", "2026-06-17 23:59:59");
+        assert_parses("next   sunday", "2026-06-14 23:59:59");
+    }
+
+    #[test]
+    fn test_parse_date_compact_offsets() {
+        assert_parses("4h", "2026-06-10 19:30:00");
+        assert_parses("1d", "2026-06-11 15:30:00");
+        assert_parses("2w", "2026-06-24 15:30:00");
+        assert_parses("1m", "2026-07-10 15:30:00");
+        assert_parses("8m", "2027-02-10 15:30:00");
+        assert_parses("1y", "2027-06-10 15:30:00");
+        assert_parses("0d", "2026-06-10 15:30:00");
+    }
+
+    #[test]
+    fn test_parse_date_calendar_accurate_months() {
+        let end_of_january = local("2027-01-31 09:00:00");
+        assert_eq!(
+            parse_date_at("1m", end_of_january).unwrap(),
+            local("2027-02-28 09:00:00")
+        );
+        let leap_day = local("2028-02-29 09:00:00");
+        assert_eq!(
+            parse_date_at("1y", leap_day).unwrap(),
+            local("2029-02-28 09:00:00")
+        );
+    }
+
+    #[test]
+    fn test_parse_date_day_offsets_across_dst() {
+        let tz = chrono_tz::Europe::Berlin;
+        // The clocks go back an hour on the night of October 25th 2026.
+        let now = tz.with_ymd_and_hms(2026, 10, 25, 0, 30, 0).unwrap();
+
+        assert_eq!(
+            parse_date_at("1d", now).unwrap(),
+            tz.with_ymd_and_hms(2026, 10, 26, 0, 30, 0).unwrap()
+        );
+        assert_eq!(
+            parse_date_at("1w", now).unwrap(),
+            tz.with_ymd_and_hms(2026, 11, 1, 0, 30, 0).unwrap()
+        );
+        assert_eq!(
+            parse_due_at("1d", now).unwrap(),
+            DueDate::all_day_on(NaiveDate::from_ymd_opt(2026, 10, 26).unwrap(), &tz).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_date_worded_offsets() {
+        assert_parses("in 2 weeks", "2026-06-24 15:30:00");
+        assert_parses("in 1 week", "2026-06-17 15:30:00");
+        assert_parses("in 3 days", "2026-06-13 15:30:00");
+        assert_parses("in 5 hours", "2026-06-10 20:30:00");
+        assert_parses("in 2 months", "2026-08-10 15:30:00");
+        assert_parses("In 1 Year", "2027-06-10 15:30:00");
+    }
+
+    #[test]
+    fn test_parse_date_absolute() {
+        assert_parses("2026-11-03", "2026-11-03 23:59:59");
+        assert_parses("2026-11-03 14:00", "2026-11-03 14:00:00");
+        assert_parses("2026-11-03T14:00", "2026-11-03 14:00:00");
+        assert_parses("2026-11-03 14:00:30", "2026-11-03 14:00:30");
+        assert_eq!(
+            parse_date_at("2026-11-03T14:00:00+00:00", fixed_now()).unwrap(),
+            DateTime::parse_from_rfc3339("2026-11-03T14:00:00+00:00")
+                .unwrap()
+                .with_timezone(&Local)
+        );
+    }
+
+    #[test]
+    fn test_parse_date_month_and_day() {
+        assert_parses("nov 3", "2026-11-03 23:59:59");
+        assert_parses("3 nov", "2026-11-03 23:59:59");
+        assert_parses("November 3", "2026-11-03 23:59:59");
+        assert_parses("sept 1", "2026-09-01 23:59:59");
+        assert_parses("jun 10", "2026-06-10 23:59:59");
+        // already passed this year
+        assert_parses("jun 9", "2027-06-09 23:59:59");
+        assert_parses("jan 15", "2027-01-15 23:59:59");
+    }
+
+    #[test]
+    fn test_parse_due_all_day_and_timed() {
+        let tz = chrono_tz::Asia::Tokyo;
+        // 2026-06-10 23:30 in Tokyo, still the 10th there but already the 10th afternoon in UTC
+        let now = tz.with_ymd_and_hms(2026, 6, 10, 23, 30, 0).unwrap();
+        let end_of = |y, m, d| tz.with_ymd_and_hms(y, m, d, 23, 59, 59).unwrap();
+
+        let today = parse_due_at("today", now).unwrap();
+        assert!(today.all_day);
+        assert_eq!(today.at, end_of(2026, 6, 10));
+
+        let in_two_days = parse_due_at("2d", now).unwrap();
+        assert!(in_two_days.all_day);
+        assert_eq!(in_two_days.at, end_of(2026, 6, 12));
+
+        let in_two_hours = parse_due_at("2h", now).unwrap();
+        assert!(!in_two_hours.all_day);
+        assert_eq!(
+            in_two_hours.at,
+            tz.with_ymd_and_hms(2026, 6, 11, 1, 30, 0).unwrap()
+        );
+
+        let meeting = parse_due_at("2026-06-12 14:00", now).unwrap();
+        assert!(!meeting.all_day);
+        assert_eq!(
+            meeting.at,
+            tz.with_ymd_and_hms(2026, 6, 12, 14, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_due_date_format_in_home_timezone() {
+        let tokyo = chrono_tz::Asia::Tokyo;
+        let berlin = chrono_tz::Europe::Berlin;
+        let date = NaiveDate::from_ymd_opt(2026, 6, 12).unwrap();
+
+        let all_day = DueDate::all_day_on(date, &tokyo).unwrap();
+        assert_eq!(all_day.format_in(&tokyo), "2026-06-12");
+
+        let timed = DueDate::timed(
+            tokyo
+                .with_ymd_and_hms(2026, 6, 12, 14, 0, 0)
+                .unwrap()
+                .with_timezone(&Local),
+        );
+        assert_eq!(timed.format_in(&tokyo), "2026-06-12 14:00");
+        assert_eq!(timed.format_in(&berlin), "2026-06-12 07:00");
+    }
+
+    #[test]
+    fn test_ls_due_until_day_boundary() {
+        let tz = chrono_tz::America::Los_Angeles;
+        let now = tz.with_ymd_and_hms(2026, 6, 10, 22, 0, 0).unwrap();
+        let args = LSArgs {
+            limit: 50,
+            days: 0,
+            category: None,
+            priority: None,
+            status: None,
+            ls_type: LSType::Task,
+        };
+
+        let (last_day, until) = args.due_until(now).unwrap();
+        assert_eq!(last_day, NaiveDate::from_ymd_opt(2026, 6, 10).unwrap());
+        assert_eq!(until, tz.with_ymd_and_hms(2026, 6, 11, 0, 0, 0).unwrap());
+
+        let args = LSArgs { days: 2, ..args };
+        let (_, until) = args.due_until(now).unwrap();
+        assert_eq!(until, tz.with_ymd_and_hms(2026, 6, 13, 0, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn test_parse_remind() {
+        assert_eq!(
+            parse_remind("1h-before").unwrap(),
+            Remind::Before(Duration::hours(1))
+        );
+        assert_eq!(
+            parse_remind("30m-before").unwrap(),
+            Remind::Before(Duration::minutes(30))
+        );
+        assert_eq!(
+            parse_remind("2 days before").unwrap(),
+            Remind::Before(Duration::days(2))
+        );
+        assert!(matches!(
+            parse_remind("2026-11-03 09:00"),
+            Ok(Remind::At(_))
+        ));
+        assert!(parse_remind("h-before").is_err());
+        assert!(parse_remind("1y-before").is_err());
+        assert!(parse_remind("whenever").is_err());
+    }
+
+    #[test]
+    fn test_task_remind_at() {
+        let due = local("2026-06-12 14:00:00");
+        let task = Task {
+            due_date: DueDate::timed(due),
+            remind: Some(Remind::Before(Duration::hours(1))),
+            ..Default::default()
+        };
+        assert_eq!(task.remind_at(), Some(local("2026-06-12 13:00:00")));
+
+        let task = Task {
+            remind: Some(Remind::At(local("2026-06-12 09:00:00"))),
+            ..task
+        };
+        assert_eq!(task.remind_at(), Some(local("2026-06-12 09:00:00")));
+    }
+
+    #[test]
+    fn test_task_reminder_after_due_date_is_invalid() {
+        let due = Local::now() + Duration::days(2);
+        let task = Task {
+            title: "Write report".to_string(),
+            due_date: DueDate::timed(due),
+            remind: Some(Remind::At(due + Duration::hours(1))),
+            ..Default::default()
+        };
+        assert!(task.validate().is_err());
+
+        let task = Task {
+            remind: Some(Remind::Before(Duration::hours(1))),
+            ..task
+        };
+        assert!(task.validate().is_ok());
+    }
+
+    #[test]
+    fn test_parse_date_errors() {
+        for input in [
+            "",
+            "   ",
+            "d",
+            "10x",
+            "in",
+            "in two weeks",
+            "in 2 fortnights",
+            "next",
+            "next month",
+            "nov",
+            "nov 31",
+            "feb 30",
+            "novem 3",
+            "2026-13-01",
+            "2026-11-03 25:00",
+            "yesterday",
+        ] {
+            assert!(
+                parse_date_at(input, fixed_now()).is_err(),
+                "'{}' should not parse",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_category_interruptions_from_sessions() {
+        let session =
+            |id: u64, category: Option<&str>, internal: u64, external: u64| SessionInterruptions {
+                id,
+                title: "Session".to_string(),
+                start_time: Local::now(),
+                category: category.map(String::from),
+                internal,
+                external,
+            };
+
+        let categories = CategoryInterruptions::from_sessions(&[
+            session(1, Some("work"), 1, 0),
+            session(2, Some("work"), 0, 0),
+            session(3, None, 2, 2),
+            session(4, Some("study"), 0, 0),
+        ]);
+
+        let names: Vec<&str> = categories.iter().map(|c| c.category.as_str()).collect();
+        assert_eq!(names, vec!["-", "work", "study"]);
+        assert_eq!(categories[0].per_session(), 4.0);
+        assert_eq!(categories[1].sessions, 2);
+        assert_eq!(categories[1].clean, 1);
+        assert_eq!(categories[1].row()[4], "0.50");
+        assert_eq!(categories[1].row()[5], "50.00%");
+        assert_eq!(categories[2].row()[5], "100.00%");
+    }
+
+    #[test]
+    fn test_countdown_action_keys() {
+        assert_eq!(
+            CountdownAction::from_key('+'),
+            Some(CountdownAction::AddMinute)
+        );
+        assert_eq!(
+            CountdownAction::from_key('-'),
+            Some(CountdownAction::RemoveMinute)
+        );
+        assert_eq!(CountdownAction::from_key('s'), Some(CountdownAction::Skip));
+        assert_eq!(
+            CountdownAction::from_key('n'),
+            Some(CountdownAction::Note(InterruptionKind::Internal))
+        );
+        assert_eq!(
+            CountdownAction::from_key('e'),
+            Some(CountdownAction::Note(InterruptionKind::External))
+        );
+        assert_eq!(
+            CountdownAction::from_key('?'),
+            Some(CountdownAction::ToggleHelp)
+        );
+        assert_eq!(CountdownAction::from_key('q'), Some(CountdownAction::Quit));
+        assert_eq!(CountdownAction::from_key('x'), None);
+    }
+
+    #[test]
+    fn test_session_end_choice_keys() {
+        assert_eq!(
+            SessionEndChoice::from_key('e'),
+            Some(SessionEndChoice::Extend)
+        );
+        assert_eq!(
+            SessionEndChoice::from_key('B'),
+            Some(SessionEndChoice::Next)
+        );
+        assert_eq!(
+            SessionEndChoice::from_key('w'),
+            Some(SessionEndChoice::Next)
+        );
+        assert_eq!(
+            SessionEndChoice::from_key('\n'),
+            Some(SessionEndChoice::Done)
+        );
+        assert_eq!(SessionEndChoice::from_key('x'), None);
+
+        let extend = DurationField(Duration::minutes(5));
+        assert_eq!(
+            SessionEndChoice::prompt(PomoType::Work, &extend),
+            "[e] +5m  [b] break  [d] done"
+        );
+        assert_eq!(
+            SessionEndChoice::prompt(PomoType::Rest, &extend),
+            "[e] +5m  [w] work  [d] done"
+        );
+    }
+
+    fn day(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_goal_progress_streaks() {
+        let goal = Goal {
+            id: 1,
+            metric: GoalMetric::Pomodoros,
+            period: GoalPeriod::Weekday,
+            target: 2,
+        };
+        let mut daily = vec![
+            (day("2026-06-08"), 1),
+            (day("2026-06-09"), 2),
+            (day("2026-06-10"), 2),
+            (day("2026-06-11"), 3),
+            (day("2026-06-12"), 2),
+            // a weekend pomodoro does not count for weekday goals
+            (day("2026-06-14"), 1),
+            (day("2026-06-15"), 1),
+        ];
+
+        // Monday is not done yet, the weekend does not break the streak.
+        let progress = GoalProgress::from_daily(goal.clone(), &daily, day("2026-06-15"));
+        assert_eq!(progress.current, 1);
+        assert_eq!(progress.streak, 4);
+        assert_eq!(progress.longest_streak, 4);
+        assert_eq!(progress.summary(), "pomodoros today 1/2");
+
+        daily.push((day("2026-06-15"), 1));
+        let progress = GoalProgress::from_daily(goal.clone(), &daily, day("2026-06-15"));
+        assert_eq!(progress.streak, 5);
+        assert_eq!(progress.summary(), "pomodoros today 2/2 ✓");
+
+        // Tuesday went by without reaching it.
+        let progress = GoalProgress::from_daily(goal.clone(), &daily, day("2026-06-17"));
+        assert_eq!(progress.current, 0);
+        assert_eq!(progress.streak, 0);
+        assert_eq!(progress.longest_streak, 5);
+
+        // Every day counts for daily goals, so the weekend breaks it.
+        let daily_goal = Goal {
+            period: GoalPeriod::Day,
+            ..goal
+        };
+        let progress = GoalProgress::from_daily(daily_goal, &daily, day("2026-06-15"));
+        assert_eq!(progress.streak, 1);
+        assert_eq!(progress.longest_streak, 4);
+    }
+
+    #[test]
+    fn test_weekly_goal_progress() {
+        let goal = Goal {
+            id: 2,
+            metric: GoalMetric::Focus,
+            period: GoalPeriod::Week,
+            target: 10 * 3600,
+        };
+        assert_eq!(goal.to_string(), "10h 00m focus per week");
+
+        let daily = vec![
+            (day("2026-06-01"), 4 * 3600),
+            (day("2026-06-08"), 6 * 3600),
+            (day("2026-06-14"), 4 * 3600),
+            (day("2026-06-16"), 3600 + 1800),
+        ];
+        let progress = GoalProgress::from_daily(goal, &daily, day("2026-06-18"));
+        assert_eq!(progress.current, 3600 + 1800);
+        assert_eq!(progress.streak, 1);
+        assert_eq!(progress.longest_streak, 1);
+        assert_eq!(progress.summary(), "focus this week 1h 30m/10h 00m");
+        assert_eq!(
+            progress.row()[2],
+            format!("{}{}  15%", "█".repeat(3), "░".repeat(17))
+        );
+    }
+
+    #[test]
+    fn test_goal_target_value() {
+        let args = |metric, target: &str| GoalAddArgs {
+            metric,
+            target: target.to_string(),
+            per: GoalPeriod::Day,
+        };
+
+        assert_eq!(args(GoalMetric::Pomodoros, "4").target_value().unwrap(), 4);
+        assert_eq!(
+            args(GoalMetric::Focus, "10h").target_value().unwrap(),
+            36_000
+        );
+        assert!(args(GoalMetric::Tasks, "five").validate().is_err());
+        assert!(args(GoalMetric::Tasks, "0").validate().is_err());
+        assert!(args(GoalMetric::Focus, "4").validate().is_err());
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let now = fixed_now();
+        assert_eq!(
+            parse_time_at("09:00", now).unwrap(),
+            local("2026-06-10 09:00:00")
+        );
+        assert_eq!(
+            parse_time_at("2026-06-09 17:30", now).unwrap(),
+            local("2026-06-09 17:30:00")
+        );
+        assert!(parse_time_at("2026-06-09", now).is_err());
+        assert!(parse_time_at("25:00", now).is_err());
+    }
+
+    #[test]
+    fn test_pomo_log_session() {
+        let args = |start: &str, end: &str| PomoLogArgs {
+            title: " Review ".to_string(),
+            start: start.to_string(),
+            end: end.to_string(),
+            category: Some("work".to_string()),
+            task_id: Some(3),
+            pomo_type: PomoType::Work,
+        };
+
+        let session = args("09:00", "09:50").session_at(fixed_now()).unwrap();
+        assert_eq!(session.title, "Review");
+        assert_eq!(session.status, PomoStatus::Finished);
+        assert_eq!(session.start_time, local("2026-06-10 09:00:00"));
+        assert_eq!(session.end_time, local("2026-06-10 09:50:00"));
+        assert_eq!(session.duration.to_i64(), 50 * 60);
+        assert_eq!(session.elapsed.to_i64(), 50 * 60);
+        assert_eq!(session.task_id, Some(3));
+
+        assert!(args("09:50", "09:00").session_at(fixed_now()).is_err());
+        assert!(args("09:00", "09:00").session_at(fixed_now()).is_err());
+        assert!(args("11:00", "23:00").session_at(fixed_now()).is_err());
+    }
+
     #[test]
     fn test_pomo_task_default_duration() {
         let task = PomoTask {
@@ -794,10 +3555,40 @@ mod tests {
             title: "Break Time".to_string(),
             duration: DurationField(Duration::seconds(25 * 60)), //Should be the default
             category: None,
+            task_id: None,
+            elapsed: DurationField::from_i64(0),
             start_time: Local::now(),
             end_time: Local::now(),
+            meta: RecordMeta::default(),
         };
         assert_eq!(task.duration.0, Duration::seconds(25 * 60));
         assert_eq!(task.category, None);
     }
+
+    #[test]
+    fn test_commands_that_write() {
+        let writes = |args: &[&str]| {
+            let cli = <Cli as clap::Parser>::try_parse_from(
+                std::iter::once("tasklog").chain(args.iter().copied()),
+            )
+            .unwrap();
+            match cli.command {
+                Commands::Pomo(args) => args.writes(),
+                Commands::Goals(args) => args.writes(),
+                Commands::Track(args) => args.writes(),
+                command => panic!("unexpected command {:?}", command),
+            }
+        };
+
+        assert!(writes(&["pomo", "-t", "Write"]));
+        assert!(writes(&["pomo", "start", "-t", "Write"]));
+        assert!(writes(&["pomo", "stop"]));
+        assert!(!writes(&["pomo", "status"]));
+        assert!(!writes(&["pomo", "pause"]));
+        assert!(!writes(&["pomo", "daemon"]));
+        assert!(!writes(&["goals"]));
+        assert!(writes(&["goals", "rm", "1"]));
+        assert!(!writes(&["track", "status"]));
+        assert!(writes(&["track", "stop"]));
+    }
 }
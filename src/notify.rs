@@ -0,0 +1,290 @@
+use crate::config::Config;
+use crate::error::AppError;
+use crate::helper::get_home_directory;
+use crate::models::{NotifyArgs, NotifyVia, PomoTask, PomoType, Task};
+use chrono::Local;
+use std::fs::OpenOptions;
+use std::io::{stdout, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// Where due reminders are delivered.
+#[derive(Debug, PartialEq)]
+pub enum Notifier {
+    /// Rings the terminal bell and prints the reminder
+    Bell,
+    /// Runs a program with the reminder summary and body as its last two arguments
+    Command(Vec<String>),
+    /// Appends the reminder as a line of the file
+    File(PathBuf),
+}
+
+impl Notifier {
+    /// Picks the channel from the command line, falling back to the config and then the bell.
+    pub fn from_settings(args: &NotifyArgs, config: &Config) -> Result<Notifier, AppError> {
+        let via = args.via.or(config.notify).unwrap_or_default();
+
+        match via {
+            NotifyVia::Bell => Ok(Notifier::Bell),
+            NotifyVia::Command => {
+                let command = args
+                    .command
+                    .as_ref()
+                    .or(config.notify_command.as_ref())
+                    .ok_or_else(|| {
+                        AppError::Validation(
+                            "the command channel needs --command or the notify_command setting"
+                                .to_string(),
+                        )
+                    })?;
+
+                let parts = split_command(command)?;
+                if parts.is_empty() {
+                    return Err(AppError::Validation(
+                        "notify command cannot be empty".to_string(),
+                    ));
+                }
+
+                Ok(Notifier::Command(parts))
+            }
+            NotifyVia::File => {
+                let file = args
+                    .file
+                    .as_ref()
+                    .or(config.notify_file.as_ref())
+                    .ok_or_else(|| {
+                        AppError::Validation(
+                            "the file channel needs --file or the notify_file setting".to_string(),
+                        )
+                    })?;
+
+                let path = match file.strip_prefix("~/") {
+                    Some(rest) => PathBuf::from(get_home_directory()?).join(rest),
+                    None => PathBuf::from(file),
+                };
+
+                Ok(Notifier::File(path))
+            }
+        }
+    }
+
+    pub fn fire(&self, task: &Task) -> Result<(), AppError> {
+        let (summary, body) = reminder_message(task);
+
+        match self {
+            Notifier::Bell => {
+                let mut stdout = stdout();
+                writeln!(stdout, "\x07{}: {}", summary, body)
+                    .and_then(|_| stdout.flush())
+                    .map_err(AppError::from)
+            }
+            Notifier::Command(parts) => {
+                let status = Command::new(&parts[0])
+                    .args(&parts[1..])
+                    .arg(&summary)
+                    .arg(&body)
+                    .status()
+                    .map_err(|e| {
+                        AppError::from(e).context(format!("could not run {}", parts[0]))
+                    })?;
+
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(AppError::Io(format!("{} exited with {}", parts[0], status)))
+                }
+            }
+            Notifier::File(path) => {
+                let mut file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|e| {
+                        AppError::from(e).context(format!("could not open {}", path.display()))
+                    })?;
+
+                writeln!(file, "{}\t{}\t{}", Local::now().to_rfc3339(), summary, body).map_err(
+                    |e| AppError::from(e).context(format!("could not write {}", path.display())),
+                )
+            }
+        }
+    }
+}
+
+/// Splits a configured command into its program and arguments at whitespace, like a shell would
+/// for a simple command line: single or double quotes keep the text between them together as
+/// (part of) one argument, and a backslash outside single quotes takes the next character as is.
+pub fn split_command(command: &str) -> Result<Vec<String>, AppError> {
+    let mut parts = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = command.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('\''), c) => current.get_or_insert_with(String::new).push(c),
+            (_, '\\') => {
+                let escaped = chars.next().ok_or_else(|| {
+                    AppError::Validation(format!("command ends with a backslash: {}", command))
+                })?;
+                current.get_or_insert_with(String::new).push(escaped);
+            }
+            (Some(_), c) => current.get_or_insert_with(String::new).push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => parts.extend(current.take()),
+            (None, c) => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if quote.is_some() {
+        return Err(AppError::Validation(format!(
+            "command has an unterminated quote: {}",
+            command
+        )));
+    }
+    parts.extend(current);
+
+    Ok(parts)
+}
+
+/// Starts a hook command with the summary and body as its last two arguments without waiting for
+/// it, its output is discarded so it cannot scribble over a full screen ui. A thread waits for it
+/// instead, so it does not linger as a zombie for as long as the session runs.
+pub fn spawn_hook(command: &str, summary: &str, body: &str) -> Result<(), AppError> {
+    let parts = split_command(command)?;
+    let (program, args) = parts
+        .split_first()
+        .ok_or_else(|| AppError::Validation("hook command cannot be empty".to_string()))?;
+
+    Command::new(program)
+        .args(args)
+        .arg(summary)
+        .arg(body)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(|mut child| {
+            thread::spawn(move || child.wait());
+        })
+        .map_err(|e| AppError::from(e).context(format!("could not run {}", program)))
+}
+
+/// The summary and body lines describing a task reminder.
+pub fn reminder_message(task: &Task) -> (String, String) {
+    let summary = format!("Task #{} due {}", task.id, task.due_date);
+    let body = match &task.category {
+        Some(category) => format!("{} [{}]", task.title, category),
+        None => task.title.clone(),
+    };
+
+    (summary, body)
+}
+
+/// The summary and body lines describing a finished pomodoro session.
+pub fn pomo_message(pomo_task: &PomoTask) -> (String, String) {
+    let summary = match pomo_task.pomo_type {
+        PomoType::Work => "Pomodoro finished",
+        PomoType::Rest => "Break finished",
+    };
+    let body = match &pomo_task.category {
+        Some(category) => format!("{} [{}]", pomo_task.title, category),
+        None => pomo_task.title.clone(),
+    };
+
+    (summary.to_string(), body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn args(via: Option<NotifyVia>) -> NotifyArgs {
+        NotifyArgs {
+            via,
+            command: None,
+            file: None,
+        }
+    }
+
+    #[test]
+    fn test_notifier_from_settings() {
+        let config = Config {
+            notify: Some(NotifyVia::Command),
+            notify_command: Some("notify-send -a tasklog".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            Notifier::from_settings(&args(None), &config).unwrap(),
+            Notifier::Command(vec![
+                "notify-send".to_string(),
+                "-a".to_string(),
+                "tasklog".to_string()
+            ])
+        );
+        assert_eq!(
+            Notifier::from_settings(&args(Some(NotifyVia::Bell)), &config).unwrap(),
+            Notifier::Bell
+        );
+        assert_eq!(
+            Notifier::from_settings(&args(None), &Config::default()).unwrap(),
+            Notifier::Bell
+        );
+        assert!(Notifier::from_settings(&args(Some(NotifyVia::File)), &config).is_err());
+    }
+
+    #[test]
+    fn test_split_command() {
+        assert_eq!(
+            split_command("notify-send -a \"Task Log\"  -u 'low'").unwrap(),
+            vec!["notify-send", "-a", "Task Log", "-u", "low"]
+        );
+        assert_eq!(
+            split_command(r#"say "it's" '"done"' a\ b "" x"y"z"#).unwrap(),
+            vec!["say", "it's", "\"done\"", "a b", "", "xyz"]
+        );
+        assert_eq!(split_command("   ").unwrap(), Vec::<String>::new());
+        assert!(split_command("notify-send -a \"Task Log").is_err());
+        assert!(split_command("notify-send \\").is_err());
+    }
+
+    #[test]
+    fn test_spawn_hook() {
+        assert!(spawn_hook("true", "Pomodoro finished", "Write report").is_ok());
+        assert!(spawn_hook("   ", "Pomodoro finished", "Write report").is_err());
+        assert!(spawn_hook("'true' \"a b\"", "Pomodoro finished", "Write report").is_ok());
+        assert!(spawn_hook("true \"a b", "Pomodoro finished", "Write report").is_err());
+        assert!(spawn_hook("tasklog-no-such-hook", "a", "b").is_err());
+    }
+
+    #[test]
+    fn test_file_notifier_appends() {
+        let path = std::env::temp_dir().join(format!("tasklog-notify-{}.log", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let task = Task {
+            id: 4,
+            title: "Submit invoice".to_string(),
+            category: Some("admin".to_string()),
+            ..Default::default()
+        };
+        let notifier = Notifier::File(path.clone());
+        notifier.fire(&task).unwrap();
+        notifier.fire(&task).unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("Task #4 due"));
+        assert!(lines[0].ends_with("\tSubmit invoice [admin]"));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
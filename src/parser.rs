@@ -1,54 +1,144 @@
+use std::error::Error;
+use std::path::PathBuf;
 use std::process::exit;
 
 use super::handlers;
-use super::models::{format_string_with_color, Cli, Color, Commands};
 use clap::Parser;
+use tasklog::config::Config;
+use tasklog::daemon;
+use tasklog::models::{format_string_with_color, Cli, Color, Commands};
+use tasklog::{AppError, TaskStore};
 
 pub fn execute() {
     let cli = Cli::parse();
 
-    match cli.command {
-        Commands::Init => match handlers::handle_init_db() {
-            Ok(_) => println!("Database initialized successfully."),
-            Err(err) => {
-                eprintln!("{}", format_string_with_color(err.as_str(), Color::Red));
-                exit(1)
-            }
-        },
-        Commands::LS(args) => match handlers::handle_ls(&args) {
-            Ok(_) => {}
-            Err(err) => {
-                eprintln!("{}", format_string_with_color(err.as_str(), Color::Red));
-                exit(1)
-            }
-        },
-        Commands::Add(task) => match handlers::handel_add_task(task) {
-            Ok(_) => {}
-            Err(err) => {
-                eprintln!("{}", format_string_with_color(err.as_str(), Color::Red));
-                exit(1)
-            }
-        },
-        Commands::Analyze(args) => match handlers::handle_analyze(args) {
-            Ok(_) => {}
-            Err(err) => {
-                eprintln!("{}", format_string_with_color(err.as_str(), Color::Red));
-                exit(1)
-            }
-        },
-        Commands::Done(args) => match handlers::handle_done(args) {
-            Ok(_) => {}
-            Err(err) => {
-                eprintln!("{}", format_string_with_color(err.as_str(), Color::Red));
-                exit(1)
-            }
-        },
-        Commands::Pomo(args) => match handlers::handle_pomodoro(args) {
-            Ok(_) => {}
-            Err(err) => {
-                eprintln!("{}", format_string_with_color(err.as_str(), Color::Red));
-                exit(1)
-            }
-        },
+    let result = match cli.command {
+        Commands::Init => db_path()
+            .and_then(|path| handlers::handle_init_db(&path))
+            .map(|_| println!("Database initialized successfully.")),
+        Commands::LS(args) => open_store().and_then(|store| handlers::handle_ls(&store, &args)),
+        Commands::Add(task) => {
+            open_store_to_write().and_then(|store| handlers::handel_add_task(&store, task))
+        }
+        Commands::Analyze(args) => {
+            open_store().and_then(|store| handlers::handle_analyze(&store, args))
+        }
+        Commands::Done(args) => {
+            open_store_to_write().and_then(|store| handlers::handle_done(&store, args))
+        }
+        Commands::Show(args) => open_store().and_then(|store| handlers::handle_show(&store, args)),
+        Commands::Edit(args) => {
+            open_store_to_write().and_then(|store| handlers::handle_edit(&store, args))
+        }
+        Commands::Pomo(args) => open_store_for(args.writes()).and_then(|store| {
+            handlers::handle_pomodoro(&store, &Config::load()?, daemon::load_state()?, args)
+        }),
+        Commands::Report(args) => {
+            open_store().and_then(|store| handlers::handle_report(&store, args))
+        }
+        Commands::Notify(args) => open_store_to_write()
+            .and_then(|store| handlers::handle_notify(&store, &Config::load()?, args)),
+        Commands::Prompt(args) => open_store_to_read().and_then(|store| match store {
+            Some(store) => handlers::handle_prompt(&store, daemon::load_state()?, args),
+            None => Ok(()),
+        }),
+        Commands::Goals(args) => {
+            open_store_for(args.writes()).and_then(|store| handlers::handle_goals(&store, args))
+        }
+        Commands::Track(args) => {
+            open_store_for(args.writes()).and_then(|store| handlers::handle_track(&store, args))
+        }
+        Commands::Doctor(args) => db_path().and_then(|path| handlers::handle_doctor(&path, args)),
+        Commands::Backup(args) => {
+            open_store().and_then(|store| handlers::handle_backup(&store, args))
+        }
+        Commands::Restore(args) => {
+            open_store_to_write().and_then(|mut store| handlers::handle_restore(&mut store, args))
+        }
+        Commands::Sync(args) => open_store_to_write()
+            .and_then(|store| handlers::handle_sync(&store, &Config::load()?, args)),
+        Commands::Import(args) => {
+            open_store_to_write().and_then(|store| handlers::handle_import(&store, args))
+        }
+        Commands::Export(args) => {
+            open_store().and_then(|store| handlers::handle_export(&store, args))
+        }
+        Commands::Timesheet(args) => {
+            open_store().and_then(|store| handlers::handle_timesheet(&store, args))
+        }
+    };
+
+    if let Err(err) = result {
+        report_error(&err, cli.verbose);
+        exit(err.exit_code())
+    }
+}
+
+fn db_path() -> Result<PathBuf, AppError> {
+    Ok(TaskStore::default_path()?)
+}
+
+/// Opens the database every command but `init` and `doctor` works on, creating it on first use
+/// so running `init` beforehand is optional.
+fn open_store() -> Result<TaskStore, AppError> {
+    Ok(TaskStore::init(db_path()?)?)
+}
+
+/// Opens the database as it is for `prompt`, which runs on every shell prompt and only reads it.
+/// Before there is a database there is nothing to show.
+fn open_store_to_read() -> Result<Option<TaskStore>, AppError> {
+    Ok(TaskStore::open_read_only(db_path()?)?)
+}
+
+/// Opens the database for a command that changes it, taking the daily snapshot first. One that
+/// fails is reported and does not hold up the command.
+fn open_store_to_write() -> Result<TaskStore, AppError> {
+    let store = open_store()?;
+    let snapshot = Config::load().and_then(|config| handlers::take_daily_snapshot(&store, &config));
+    if let Err(err) = snapshot {
+        eprintln!(
+            "{}",
+            format_string_with_color(
+                format!("daily snapshot skipped: {}", err).as_str(),
+                Color::Yellow
+            )
+        );
+    }
+
+    Ok(store)
+}
+
+/// Opens the database for a command that may or may not change it, depending on its arguments.
+fn open_store_for(writes: bool) -> Result<TaskStore, AppError> {
+    if writes {
+        open_store_to_write()
+    } else {
+        open_store()
+    }
+}
+
+/// Prints the error in red with a hint of what to do about it, `verbose` adds its causes and
+/// exit code.
+fn report_error(err: &AppError, verbose: bool) {
+    eprintln!(
+        "{}",
+        format_string_with_color(err.to_string().as_str(), Color::Red)
+    );
+
+    if verbose {
+        let mut source = err.source();
+        while let Some(cause) = source {
+            eprintln!("  caused by: {}", cause);
+            source = cause.source();
+        }
+        eprintln!("  exit code: {}", err.exit_code());
+        eprintln!("  details: {:?}", err);
+    }
+
+    if let Some(hint) = err.hint() {
+        eprintln!(
+            "{}",
+            format_string_with_color(format!("hint: {}", hint).as_str(), Color::Yellow)
+        );
     }
 }
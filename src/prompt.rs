@@ -0,0 +1,254 @@
+use crate::daemon::SessionState;
+use crate::models::{PomoTask, PomoType, PromptStyle};
+use chrono::{DateTime, Local};
+
+/// What `prompt` shows: the running pomodoro session, if any, and the open task counts.
+pub struct PromptInfo {
+    pub session: Option<SessionState>,
+    pub open: u64,
+    pub overdue: u64,
+}
+
+impl PromptInfo {
+    /// Shows the session of the background daemon, or else the one running in the foreground of
+    /// another terminal.
+    pub fn new(
+        background: Option<SessionState>,
+        foreground: Option<&PomoTask>,
+        open: u64,
+        overdue: u64,
+    ) -> PromptInfo {
+        PromptInfo {
+            session: background.or_else(|| foreground.map(SessionState::new)),
+            open,
+            overdue,
+        }
+    }
+
+    /// Nothing running and nothing left to do, the prompt stays empty.
+    pub fn is_idle(&self) -> bool {
+        self.session.is_none() && self.open == 0
+    }
+
+    /// The css class of a waybar module, also picking the tmux color.
+    fn class(&self) -> &'static str {
+        match &self.session {
+            Some(session) if session.is_paused() => "paused",
+            Some(session) if session.pomo_type == PomoType::Rest => "break",
+            Some(_) => "work",
+            None => "tasks",
+        }
+    }
+}
+
+/// Renders the prompt in the given style, the style's default format is used when none is given.
+pub fn render(
+    style: PromptStyle,
+    format: Option<&str>,
+    info: &PromptInfo,
+    now: DateTime<Local>,
+) -> String {
+    if info.is_idle() {
+        return String::new();
+    }
+
+    let format = format.unwrap_or(style.default_format());
+
+    match style {
+        PromptStyle::Plain | PromptStyle::Starship => fill(format, info, now, |s| s.to_string()),
+        PromptStyle::Tmux => {
+            // A `#` in a title would start a tmux format of its own.
+            let text = fill(format, info, now, |s| s.replace('#', "##"));
+            match info.class() {
+                "paused" => format!("#[fg=yellow]{}#[default]", text),
+                "break" => format!("#[fg=green]{}#[default]", text),
+                "work" => format!("#[fg=red]{}#[default]", text),
+                _ => text,
+            }
+        }
+        PromptStyle::Waybar => {
+            let mut tooltip = match &info.session {
+                Some(session) => session.summary(now) + "\n",
+                None => String::new(),
+            };
+            tooltip.push_str(&format!(
+                "{} open tasks, {} overdue",
+                info.open, info.overdue
+            ));
+
+            let percentage = match &info.session {
+                Some(session) if session.duration.to_i64() > 0 => {
+                    session
+                        .elapsed_at(now)
+                        .num_seconds()
+                        .min(session.duration.to_i64())
+                        * 100
+                        / session.duration.to_i64()
+                }
+                _ => 0,
+            };
+
+            format!(
+                "{{\"text\": {}, \"tooltip\": {}, \"class\": \"{}\", \"percentage\": {}}}",
+                json_string(&fill(format, info, now, |s| s.to_string())),
+                json_string(&tooltip),
+                info.class(),
+                percentage
+            )
+        }
+    }
+}
+
+/// Replaces the placeholders of the format, the pomodoro ones are empty without a session. The
+/// whitespace they leave behind is collapsed.
+fn fill(
+    format: &str,
+    info: &PromptInfo,
+    now: DateTime<Local>,
+    escape: impl Fn(&str) -> String,
+) -> String {
+    let (remaining, title, pomo_type, state, icon) = match &info.session {
+        Some(session) => {
+            let seconds = session.remaining_at(now).num_seconds();
+            let (pomo_type, icon) = match session.pomo_type {
+                PomoType::Work => ("work", "🍅"),
+                PomoType::Rest => ("break", "☕"),
+            };
+            let (state, icon) = if session.is_paused() {
+                ("paused", "⏸")
+            } else {
+                ("running", icon)
+            };
+
+            (
+                format!("{:02}:{:02}", seconds / 60, seconds % 60),
+                escape(&session.title),
+                pomo_type,
+                state,
+                icon,
+            )
+        }
+        None => (String::new(), String::new(), "", "", ""),
+    };
+
+    format
+        .replace("{pomo_remaining}", &remaining)
+        .replace("{pomo_title}", &title)
+        .replace("{pomo_type}", pomo_type)
+        .replace("{pomo_state}", state)
+        .replace("{pomo_icon}", icon)
+        .replace("{open_count}", &info.open.to_string())
+        .replace("{overdue_count}", &info.overdue.to_string())
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+fn json_string(s: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::DurationField;
+    use chrono::Duration;
+
+    fn info(open: u64, with_session: bool) -> (PromptInfo, DateTime<Local>) {
+        let start = Local::now();
+        let session = SessionState::new(&PomoTask {
+            id: 3,
+            title: "Fix #12".to_string(),
+            duration: DurationField(Duration::minutes(25)),
+            start_time: start,
+            ..Default::default()
+        });
+
+        let info = PromptInfo {
+            session: with_session.then_some(session),
+            open,
+            overdue: 1,
+        };
+        (info, start + Duration::minutes(5))
+    }
+
+    #[test]
+    fn test_render_idle_and_plain() {
+        let (idle, now) = info(0, false);
+        assert_eq!(render(PromptStyle::Plain, None, &idle, now), "");
+        assert_eq!(render(PromptStyle::Waybar, None, &idle, now), "");
+
+        let (tasks_only, now) = info(4, false);
+        assert_eq!(render(PromptStyle::Plain, None, &tasks_only, now), "4");
+
+        let (busy, now) = info(4, true);
+        assert_eq!(render(PromptStyle::Plain, None, &busy, now), "20:00 4");
+        assert_eq!(
+            render(
+                PromptStyle::Plain,
+                Some("{pomo_type}/{pomo_state}: {pomo_title} ({overdue_count} late)"),
+                &busy,
+                now
+            ),
+            "work/running: Fix #12 (1 late)"
+        );
+    }
+
+    #[test]
+    fn test_foreground_session_without_a_daemon() {
+        let start = Local::now();
+        let foreground = PomoTask {
+            id: 7,
+            title: "Read".to_string(),
+            duration: DurationField(Duration::minutes(25)),
+            start_time: start,
+            ..Default::default()
+        };
+        let now = start + Duration::minutes(10);
+
+        let shown = PromptInfo::new(None, Some(&foreground), 0, 0);
+        assert!(!shown.is_idle());
+        assert_eq!(
+            render(
+                PromptStyle::Plain,
+                Some("{pomo_title} {pomo_remaining}"),
+                &shown,
+                now
+            ),
+            "Read 15:00"
+        );
+
+        let (busy, _) = info(0, true);
+        let shown = PromptInfo::new(busy.session, Some(&foreground), 0, 0);
+        assert_eq!(shown.session.map(|session| session.id), Some(3));
+    }
+
+    #[test]
+    fn test_render_status_bar_styles() {
+        let (busy, now) = info(4, true);
+
+        assert_eq!(
+            render(PromptStyle::Tmux, Some("{pomo_title}"), &busy, now),
+            "#[fg=red]Fix ##12#[default]"
+        );
+        assert_eq!(
+            render(PromptStyle::Starship, None, &busy, now),
+            "🍅 20:00 4 open"
+        );
+        assert_eq!(
+            render(PromptStyle::Waybar, None, &busy, now),
+            "{\"text\": \"🍅 20:00 4 open\", \"tooltip\": \"Work session #3 Fix #12: 20:00 left\\n4 open tasks, 1 overdue\", \"class\": \"work\", \"percentage\": 20}"
+        );
+    }
+}
@@ -0,0 +1,379 @@
+use crate::error::AppError;
+use crate::helper::get_config_directory;
+use crate::models::{ReportData, ReportFormat, Task};
+use chrono::{Duration, Local};
+use std::fs;
+
+const DEFAULT_MD_TEMPLATE: &str = include_str!("templates/report.md");
+const DEFAULT_HTML_TEMPLATE: &str = include_str!("templates/report.html");
+
+const TOP_CATEGORIES: usize = 5;
+const CHART_BAR_WIDTH: usize = 30;
+const SVG_LABEL_WIDTH: usize = 140;
+const SVG_BAR_WIDTH: usize = 320;
+const SVG_ROW_HEIGHT: usize = 26;
+
+/// Loads `templates/report.{md,html}` from the config directory, falling back to the built-in
+/// template when the user has not overridden it.
+pub fn load_template(format: ReportFormat) -> Result<String, AppError> {
+    let path = format!(
+        "{}/templates/report.{}",
+        get_config_directory()?,
+        format.extension()
+    );
+
+    match fs::read_to_string(&path) {
+        Ok(template) => Ok(template),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(match format {
+            ReportFormat::Md => DEFAULT_MD_TEMPLATE.to_string(),
+            ReportFormat::Html => DEFAULT_HTML_TEMPLATE.to_string(),
+        }),
+        Err(err) => Err(AppError::Io(format!(
+            "Could not read template {}: {}",
+            path, err
+        ))),
+    }
+}
+
+/// Fills the `{{placeholders}}` of the template with the sections of the report.
+pub fn render(template: &str, format: ReportFormat, title: &str, report: &ReportData) -> String {
+    let sections = match format {
+        ReportFormat::Md => markdown_sections(report),
+        ReportFormat::Html => html_sections(report),
+    };
+
+    let escape = |s: &str| match format {
+        ReportFormat::Md => s.to_string(),
+        ReportFormat::Html => escape_html(s),
+    };
+
+    let mut rendered = template
+        .replace("{{title}}", &escape(title))
+        .replace("{{from}}", &report.from.format("%Y-%m-%d").to_string())
+        .replace(
+            "{{to}}",
+            &(report.to - Duration::days(1))
+                .format("%Y-%m-%d")
+                .to_string(),
+        )
+        .replace(
+            "{{generated_at}}",
+            &Local::now().format("%Y-%m-%d %H:%M").to_string(),
+        );
+
+    for (placeholder, section) in sections {
+        rendered = rendered.replace(placeholder, &section);
+    }
+
+    rendered
+}
+
+fn markdown_sections(report: &ReportData) -> Vec<(&'static str, String)> {
+    let task_table = |tasks: &Vec<Task>| {
+        if tasks.is_empty() {
+            return "_None_".to_string();
+        }
+
+        let mut table = "| id | title | due-date | priority | category | status |\n".to_string();
+        table.push_str("|----|-------|----------|----------|----------|--------|\n");
+        for task in tasks {
+            table.push_str(&format!(
+                "| {} | {} | {} | {} | {} | {} |\n",
+                task.id,
+                escape_md_cell(&task.title),
+                task.due_date,
+                task.priority,
+                escape_md_cell(task.category.as_deref().unwrap_or("-")),
+                String::from(task.status),
+            ));
+        }
+        table.trim_end().to_string()
+    };
+
+    let total_focus: i64 = report.focus.iter().map(|(_, s)| s).sum();
+    let max_focus = report.focus.iter().map(|(_, s)| *s).max().unwrap_or(0);
+
+    let (focus_time, focus_chart) = if report.focus.is_empty() {
+        ("_None_".to_string(), String::new())
+    } else {
+        let mut table = "| category | time | share |\n|----------|------|-------|\n".to_string();
+        let mut chart = "```\n".to_string();
+        let label_width = report.focus.iter().map(|(c, _)| c.len()).max().unwrap_or(0);
+
+        for (category, seconds) in &report.focus {
+            table.push_str(&format!(
+                "| {} | {} | {:.0}% |\n",
+                escape_md_cell(category),
+                format_focus(*seconds),
+                share(*seconds, total_focus)
+            ));
+            chart.push_str(&format!(
+                "{:<width$} {} {}\n",
+                category,
+                "█".repeat(scale(*seconds, max_focus, CHART_BAR_WIDTH)),
+                format_focus(*seconds),
+                width = label_width
+            ));
+        }
+        table.push_str(&format!(
+            "| **total** | **{}** | |",
+            format_focus(total_focus)
+        ));
+        chart.push_str("```");
+
+        (table, chart)
+    };
+
+    let top_categories = report.top_categories(TOP_CATEGORIES);
+    let top_categories = if top_categories.is_empty() {
+        "_None_".to_string()
+    } else {
+        top_categories
+            .iter()
+            .enumerate()
+            .map(|(i, (category, count))| format!("{}. {} ({} done)", i + 1, category, count))
+            .collect::<Vec<String>>()
+            .join("\n")
+    };
+
+    vec![
+        ("{{completed_tasks}}", task_table(&report.completed)),
+        ("{{slipped_tasks}}", task_table(&report.slipped)),
+        ("{{focus_time}}", focus_time),
+        ("{{focus_chart}}", focus_chart),
+        ("{{top_categories}}", top_categories),
+    ]
+}
+
+fn html_sections(report: &ReportData) -> Vec<(&'static str, String)> {
+    let empty = || "<p class=\"empty\">None</p>".to_string();
+
+    let task_table = |tasks: &Vec<Task>| {
+        if tasks.is_empty() {
+            return empty();
+        }
+
+        let mut table = "<table>\n<tr><th>id</th><th>title</th><th>due-date</th><th>priority</th><th>category</th><th>status</th></tr>\n".to_string();
+        for task in tasks {
+            table.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                task.id,
+                escape_html(&task.title),
+                task.due_date,
+                task.priority,
+                escape_html(task.category.as_deref().unwrap_or("-")),
+                String::from(task.status),
+            ));
+        }
+        table.push_str("</table>");
+        table
+    };
+
+    let total_focus: i64 = report.focus.iter().map(|(_, s)| s).sum();
+
+    let focus_time = if report.focus.is_empty() {
+        empty()
+    } else {
+        let mut table =
+            "<table>\n<tr><th>category</th><th>time</th><th>share</th></tr>\n".to_string();
+        for (category, seconds) in &report.focus {
+            table.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{:.0}%</td></tr>\n",
+                escape_html(category),
+                format_focus(*seconds),
+                share(*seconds, total_focus)
+            ));
+        }
+        table.push_str(&format!(
+            "<tr><th>total</th><th>{}</th><th></th></tr>\n</table>",
+            format_focus(total_focus)
+        ));
+        table
+    };
+
+    let top_categories = report.top_categories(TOP_CATEGORIES);
+    let top_categories = if top_categories.is_empty() {
+        empty()
+    } else {
+        let items = top_categories
+            .iter()
+            .map(|(category, count)| format!("<li>{} ({} done)</li>", escape_html(category), count))
+            .collect::<Vec<String>>()
+            .join("\n");
+        format!("<ol>\n{}\n</ol>", items)
+    };
+
+    vec![
+        ("{{completed_tasks}}", task_table(&report.completed)),
+        ("{{slipped_tasks}}", task_table(&report.slipped)),
+        ("{{focus_time}}", focus_time),
+        ("{{focus_chart}}", svg_bar_chart(&report.focus)),
+        ("{{top_categories}}", top_categories),
+    ]
+}
+
+/// Horizontal bar chart of the focus seconds per category as an inline svg.
+fn svg_bar_chart(focus: &[(String, i64)]) -> String {
+    if focus.is_empty() {
+        return String::new();
+    }
+
+    let max_focus = focus.iter().map(|(_, s)| *s).max().unwrap_or(0);
+    let width = SVG_LABEL_WIDTH + SVG_BAR_WIDTH + 80;
+    let height = focus.len() * SVG_ROW_HEIGHT;
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" role=\"img\">\n",
+        width, height, width, height
+    );
+
+    for (i, (category, seconds)) in focus.iter().enumerate() {
+        let y = i * SVG_ROW_HEIGHT;
+        let bar = scale(*seconds, max_focus, SVG_BAR_WIDTH).max(1);
+        svg.push_str(&format!(
+            "<text x=\"0\" y=\"{}\">{}</text>\n<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#4c8bf5\"/>\n<text x=\"{}\" y=\"{}\">{}</text>\n",
+            y + 17,
+            escape_html(category),
+            SVG_LABEL_WIDTH,
+            y + 4,
+            bar,
+            SVG_ROW_HEIGHT - 8,
+            SVG_LABEL_WIDTH + bar + 6,
+            y + 17,
+            format_focus(*seconds),
+        ));
+    }
+
+    svg.push_str("</svg>");
+    svg
+}
+
+fn scale(value: i64, max: i64, width: usize) -> usize {
+    if max <= 0 {
+        return 0;
+    }
+    (value.max(0) as f64 / max as f64 * width as f64).round() as usize
+}
+
+fn share(value: i64, total: i64) -> f64 {
+    if total <= 0 {
+        return 0.0;
+    }
+    value as f64 * 100.0 / total as f64
+}
+
+/// Formats seconds as `1h 05m`, or `25m` below an hour.
+pub fn format_focus(seconds: i64) -> String {
+    let minutes = seconds.max(0) / 60;
+    if minutes < 60 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    }
+}
+
+/// Escapes the pipes of a markdown table cell, which would otherwise end the cell.
+pub(crate) fn escape_md_cell(s: &str) -> String {
+    s.replace('|', "\\|")
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Priority, TaskStatus};
+
+    fn sample_report() -> ReportData {
+        let from = Local::now();
+        ReportData {
+            from,
+            to: from + Duration::days(7),
+            completed: vec![Task {
+                id: 7,
+                title: "Ship <report>".to_string(),
+                status: TaskStatus::Done,
+                priority: Priority::High,
+                category: Some("work".to_string()),
+                ..Default::default()
+            }],
+            slipped: vec![],
+            focus: vec![("work".to_string(), 5400), ("mail".to_string(), 900)],
+        }
+    }
+
+    #[test]
+    fn test_format_focus() {
+        assert_eq!(format_focus(0), "0m");
+        assert_eq!(format_focus(25 * 60), "25m");
+        assert_eq!(format_focus(65 * 60), "1h 05m");
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let report = sample_report();
+        let md = render(
+            DEFAULT_MD_TEMPLATE,
+            ReportFormat::Md,
+            "Weekly report",
+            &report,
+        );
+
+        assert!(md.starts_with("# Weekly report"));
+        assert!(md.contains("| 7 | Ship <report> |"));
+        assert!(md.contains("## Slipped tasks\n\n_None_"));
+        assert!(md.contains("| work | 1h 30m | 86% |"));
+        assert!(md.contains(&format!("work {} 1h 30m", "█".repeat(CHART_BAR_WIDTH))));
+        assert!(md.contains("1. work (1 done)"));
+        assert!(!md.contains("{{"));
+
+        // Pipes in a cell would split it in two.
+        let mut report = sample_report();
+        report.slipped = vec![Task {
+            id: 8,
+            title: "Pick a | b".to_string(),
+            category: Some("ops|infra".to_string()),
+            ..Default::default()
+        }];
+        report.focus = vec![("ops|infra".to_string(), 600)];
+        let md = render(DEFAULT_MD_TEMPLATE, ReportFormat::Md, "Piped", &report);
+        assert!(md.contains("| 8 | Pick a \\| b |"));
+        assert!(md.contains("| ops\\|infra | Open |"));
+        assert!(md.contains("| ops\\|infra | 10m | 100% |"));
+    }
+
+    #[test]
+    fn test_render_html_escapes_and_embeds_svg() {
+        let report = sample_report();
+        let html = render(
+            DEFAULT_HTML_TEMPLATE,
+            ReportFormat::Html,
+            "Weekly <report>",
+            &report,
+        );
+
+        assert!(html.contains("<title>Weekly &lt;report&gt;</title>"));
+        assert!(html.contains("<td>Ship &lt;report&gt;</td>"));
+        assert!(html.contains("<svg"));
+        assert!(html.contains(&format!("width=\"{}\"", SVG_BAR_WIDTH)));
+        assert!(!html.contains("{{"));
+    }
+
+    #[test]
+    fn test_render_custom_template() {
+        let report = sample_report();
+        let out = render(
+            "{{title}}: {{top_categories}}",
+            ReportFormat::Md,
+            "Standup",
+            &report,
+        );
+
+        assert_eq!(out, "Standup: 1. work (1 done)");
+    }
+}
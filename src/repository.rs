@@ -1,14 +1,20 @@
-use crate::models::{AnalyzeArgs, DurationField, PomoStatus};
-use crate::{
-    helper::get_home_directory,
-    models::{DailyAnalysis, LSArgs, PomoTask, PomoType, Priority, Task, TaskStatus},
+use crate::error::StoreError;
+use crate::models::{
+    new_uuid, unique_prefix_len, FlowAnalysis, LSArgs, PomoTask, PomoType, Priority, RecordMeta,
+    SyncRecord, SyncTable, Task, TaskAnalysis, TaskStatus,
 };
-use chrono::{DateTime, Local};
-use rusqlite::{named_params, params, Connection, ToSql};
-use std::fs;
-
-const DB_FILE_PATH: &str = "/.tasklog";
-const DB_FILE_NAME: &str = "/db.sqlite";
+use crate::models::{
+    Diagnosis, DueDate, DurationField, Goal, GoalMetric, GoalPeriod, GoalProgress, GroupBy,
+    HourInterruptions, InterruptionKind, PomoStatus, Remind, ReportData, SessionInterruptions,
+    TimeAnalysis, TimeEntry, TimesheetLine,
+};
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, SubsecRound, TimeZone, Utc};
+use rusqlite::functions::FunctionFlags;
+use rusqlite::types::Value;
+use rusqlite::{named_params, params, params_from_iter, Connection, ToSql};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::panic::UnwindSafe;
 
 const CREATE_TASKS_TABLE: &str = r#"
     CREATE TABLE IF NOT EXISTS tasks (
@@ -46,124 +52,596 @@ const CREATE_POMODORO_TABLE: &str = r#"
     CREATE INDEX IF NOT EXISTS idx_pomodoro_quited ON pomodoro (quited);
 "#;
 
+/// A random version 4 uuid as an SQL expression, for the records the uuid migration and its
+/// triggers give one to. A macro so that `concat!` can splice it into the migration text.
+macro_rules! new_uuid_sql {
+    () => {
+        "lower(hex(randomblob(4)) || '-' || hex(randomblob(2)) || '-4' || substr(hex(randomblob(2)), 2) || '-' || substr('89ab', 1 + abs(random()) % 4, 1) || substr(hex(randomblob(2)), 2) || '-' || hex(randomblob(6)))"
+    };
+}
+
+/// Schema changes applied on top of the initial tables, in order. `PRAGMA user_version` records
+/// how many of them a database has already seen.
+const MIGRATIONS: &[&str] = &[
+    r#"
+    ALTER TABLE tasks ADD COLUMN completed_at TIMESTAMP;
+    UPDATE tasks SET completed_at = updated_at WHERE status = 1;
+    CREATE INDEX IF NOT EXISTS idx_tasks_completed_at ON tasks (completed_at);
+"#,
+    r#"
+    ALTER TABLE tasks ADD COLUMN all_day INTEGER NOT NULL DEFAULT 0;
+"#,
+    r#"
+    ALTER TABLE tasks ADD COLUMN remind_at TIMESTAMP;
+    ALTER TABLE tasks ADD COLUMN reminded_at TIMESTAMP;
+    CREATE INDEX IF NOT EXISTS idx_tasks_remind_at ON tasks (remind_at);
+"#,
+    r#"
+    CREATE INDEX IF NOT EXISTS idx_tasks_status_due_date ON tasks (status, due_date);
+"#,
+    r#"
+    ALTER TABLE pomodoro ADD COLUMN task_id INTEGER REFERENCES tasks (id);
+    CREATE INDEX IF NOT EXISTS idx_pomodoro_task_id ON pomodoro (task_id);
+"#,
+    r#"
+    CREATE TABLE IF NOT EXISTS pomodoro_interruptions (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        pomodoro_id INTEGER NOT NULL REFERENCES pomodoro (id),
+        note TEXT NOT NULL,
+        created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+    );
+    CREATE INDEX IF NOT EXISTS idx_pomodoro_interruptions_pomodoro_id
+        ON pomodoro_interruptions (pomodoro_id);
+"#,
+    r#"
+    ALTER TABLE pomodoro_interruptions ADD COLUMN kind INTEGER NOT NULL DEFAULT 0;
+    CREATE INDEX IF NOT EXISTS idx_pomodoro_interruptions_created_at
+        ON pomodoro_interruptions (created_at);
+"#,
+    r#"
+    ALTER TABLE pomodoro ADD COLUMN elapsed INTEGER NOT NULL DEFAULT 0;
+    UPDATE pomodoro
+        SET elapsed = MIN(duration, MAX(0, CAST(ROUND((julianday(end_time) - julianday(start_time)) * 86400) AS INTEGER)))
+        WHERE end_time IS NOT NULL;
+"#,
+    r#"
+    CREATE TABLE IF NOT EXISTS goals (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        metric INTEGER NOT NULL,
+        period INTEGER NOT NULL,
+        target INTEGER NOT NULL,
+        created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+    );
+"#,
+    r#"
+    CREATE TABLE IF NOT EXISTS time_entries (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        task_id INTEGER REFERENCES tasks (id),
+        title TEXT NOT NULL,
+        category TEXT,
+        start_time TIMESTAMP NOT NULL,
+        end_time TIMESTAMP,
+        created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+    );
+    CREATE INDEX IF NOT EXISTS idx_time_entries_task_id ON time_entries (task_id);
+    CREATE INDEX IF NOT EXISTS idx_time_entries_end_time ON time_entries (end_time);
+"#,
+    // Random version 4 uuids, stable across machines unlike the ids.
+    concat!(
+        r#"
+    ALTER TABLE tasks ADD COLUMN uuid TEXT;
+    ALTER TABLE pomodoro ADD COLUMN uuid TEXT;
+    UPDATE tasks SET uuid = "#,
+        new_uuid_sql!(),
+        r#" WHERE uuid IS NULL;
+    UPDATE pomodoro SET uuid = "#,
+        new_uuid_sql!(),
+        r#" WHERE uuid IS NULL;
+    CREATE UNIQUE INDEX IF NOT EXISTS idx_tasks_uuid ON tasks (uuid);
+    CREATE UNIQUE INDEX IF NOT EXISTS idx_pomodoro_uuid ON pomodoro (uuid);
+    CREATE TRIGGER IF NOT EXISTS tasks_uuid AFTER INSERT ON tasks WHEN NEW.uuid IS NULL
+    BEGIN
+        UPDATE tasks SET uuid = "#,
+        new_uuid_sql!(),
+        r#" WHERE id = NEW.id;
+    END;
+    CREATE TRIGGER IF NOT EXISTS pomodoro_uuid AFTER INSERT ON pomodoro WHEN NEW.uuid IS NULL
+    BEGIN
+        UPDATE pomodoro SET uuid = "#,
+        new_uuid_sql!(),
+        r#" WHERE id = NEW.id;
+    END;
+"#
+    ),
+    // Every change moves updated_at, unless the change sets it itself as sync does.
+    r#"
+    CREATE TRIGGER IF NOT EXISTS tasks_updated_at AFTER UPDATE ON tasks
+        WHEN NEW.updated_at IS OLD.updated_at
+    BEGIN
+        UPDATE tasks SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = NEW.id;
+    END;
+    CREATE TRIGGER IF NOT EXISTS pomodoro_updated_at AFTER UPDATE ON pomodoro
+        WHEN NEW.updated_at IS OLD.updated_at
+    BEGIN
+        UPDATE pomodoro SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = NEW.id;
+    END;
+"#,
+    // Space separated, NULL for none.
+    r#"
+    ALTER TABLE tasks ADD COLUMN tags TEXT;
+"#,
+    // Seconds before the due date of a reminder given relative to it, NULL for one at a fixed
+    // time. remind_at is kept too for the reminders due to be found by their time.
+    r#"
+    ALTER TABLE tasks ADD COLUMN remind_before INTEGER;
+"#,
+    // The running session `prompt` looks up on every render.
+    r#"
+    CREATE INDEX IF NOT EXISTS idx_pomodoro_status_start_time ON pomodoro (status, start_time);
+"#,
+];
+
 const GET_TASK_BY_ID: &str = r#"
-    SELECT id, status, title, due_date, priority, category FROM tasks
+    SELECT id, status, title, due_date, priority, category, all_day, remind_at, tags,
+        uuid, created_at, updated_at, remind_before FROM tasks
         WHERE id = :id"#;
 
 const GET_TASKS: &str = r#"
-    SELECT id, status, title, due_date, priority, category FROM tasks
-        WHERE due_date <= :due_date {{where_category}} {{where_priority}} {{where_status}}
+    SELECT id, status, title, due_date, priority, category, all_day, remind_at, tags,
+        uuid, created_at, updated_at, remind_before FROM tasks
+        WHERE julianday(due_date) < julianday(:due_before) {{where_category}} {{where_priority}} {{where_status}}
         ORDER BY created_at
         LIMIT :limit"#;
 
+const GET_TASKS_BY_UUID_PREFIX: &str = r#"
+    SELECT id, status, title, due_date, priority, category, all_day, remind_at, tags,
+        uuid, created_at, updated_at, remind_before FROM tasks
+        WHERE uuid LIKE :prefix || '%'
+        LIMIT 2"#;
+
+const GET_ALL_TASKS: &str = r#"
+    SELECT id, status, title, due_date, priority, category, all_day, remind_at, tags,
+        uuid, created_at, updated_at, remind_before FROM tasks
+        ORDER BY id"#;
+
+const GET_TASK_UUIDS: &str = r#"SELECT uuid FROM tasks WHERE uuid IS NOT NULL ORDER BY uuid"#;
+
 const INSERT_TASK: &str = r#"
-    INSERT INTO tasks (status, title, due_date, priority, category)
-        VALUES (:status, :title, :due_date, :priority, :category)
+    INSERT INTO tasks (status, title, due_date, priority, category, all_day, remind_at,
+            remind_before, uuid, created_at, updated_at, tags, completed_at)
+        VALUES (:status, :title, :due_date, :priority, :category, :all_day, :remind_at,
+            :remind_before, :uuid, :created_at, :created_at, :tags,
+            CASE WHEN :status = 1 THEN CURRENT_TIMESTAMP END)
 "#;
 
-const DONE_TASK: &str = r#"UPDATE tasks SET status = 1 WHERE id = :id"#;
+/// A new reminder fires again even if the old one already did, a task reopened loses its
+/// completion time
+const UPDATE_TASK: &str = r#"
+    UPDATE tasks
+    SET
+        status = :status,
+        completed_at = CASE WHEN :status = 1 THEN COALESCE(completed_at, CURRENT_TIMESTAMP) END,
+        title = :title,
+        tags = :tags,
+        due_date = :due_date,
+        all_day = :all_day,
+        priority = :priority,
+        category = :category,
+        reminded_at = CASE WHEN remind_at IS :remind_at THEN reminded_at END,
+        remind_at = :remind_at,
+        remind_before = :remind_before
+    WHERE id = :id"#;
+
+const DONE_TASK: &str = r#"
+    UPDATE tasks
+    SET status = 1, completed_at = CURRENT_TIMESTAMP
+    WHERE id = :id"#;
 
 const UPDATE_POMODORO: &str = r#"
     UPDATE pomodoro
     SET
         status = :status,
+        duration = :duration,
+        elapsed = :elapsed,
         end_time = :end_date
     WHERE id = :id"#;
 
 const INSERT_POMO: &str = r#"
-INSERT INTO pomodoro (type, title, start_time, duration, status, category)
-    VALUES (:type, :title, :start_time, :duration, :status, :category)
+INSERT INTO pomodoro (type, title, start_time, duration, status, category, task_id, uuid,
+        created_at, updated_at)
+    VALUES (:type, :title, :start_time, :duration, :status, :category, :task_id, :uuid,
+        :created_at, :created_at)
     RETURNING id
 "#;
 
+const INSERT_LOGGED_POMO: &str = r#"
+INSERT INTO pomodoro (type, title, start_time, end_time, duration, elapsed, status, category, task_id,
+        uuid, created_at, updated_at)
+    VALUES (:type, :title, :start_time, :end_time, :duration, :elapsed, :status, :category, :task_id,
+        :uuid, :created_at, :created_at)
+    RETURNING id
+"#;
+
+/// The first session overlapping `[:start_time, :end_time)`, sessions still running end when their
+/// time runs out
+const GET_OVERLAPPING_POMODORO: &str = r#"
+    SELECT id, title FROM pomodoro
+        WHERE julianday(start_time) < julianday(:end_time)
+            AND COALESCE(julianday(end_time), julianday(start_time, '+' || duration || ' seconds'))
+                > julianday(:start_time)
+        ORDER BY start_time
+        LIMIT 1"#;
+
+const INSERT_INTERRUPTION: &str = r#"
+    INSERT INTO pomodoro_interruptions (pomodoro_id, kind, note, created_at)
+        VALUES (:pomodoro_id, :kind, :note, :created_at)"#;
+
+/// The latest session still running whose time has not run out at `:now`
+const GET_RUNNING_POMODORO: &str = r#"
+    SELECT
+        id, type, title, start_time, end_time, duration, status, category, task_id, elapsed, uuid,
+        created_at, updated_at
+    FROM pomodoro
+        WHERE status = 0
+            AND julianday(start_time, '+' || duration || ' seconds') >= julianday(:now)
+        ORDER BY start_time DESC
+        LIMIT 1"#;
+
+/// Internal and external interruptions of each work session started in the range
+const GET_SESSION_INTERRUPTIONS: &str = r#"
+    SELECT p.id, p.title, p.start_time, p.category,
+        COALESCE(SUM(i.kind = 0), 0), COALESCE(SUM(i.kind = 1), 0)
+    FROM pomodoro p
+        LEFT JOIN pomodoro_interruptions i ON i.pomodoro_id = p.id
+    WHERE p.type = 1
+        AND julianday(p.start_time) >= julianday(:start_date)
+        AND julianday(p.start_time) < julianday(:end_date)
+    GROUP BY p.id
+    ORDER BY p.start_time"#;
+
+const GET_INTERRUPTIONS_BY_HOUR: &str = r#"
+    SELECT strftime('%H', created_at, 'localtime') AS hour, SUM(kind = 0), SUM(kind = 1)
+    FROM pomodoro_interruptions
+    WHERE julianday(created_at) >= julianday(:start_date)
+        AND julianday(created_at) < julianday(:end_date)
+    GROUP BY hour
+    ORDER BY hour"#;
+
 const GET_POMODORO_LIST: &str = r#"
     SELECT
-        id, type, title, start_time, end_time, duration, status, category
+        id, type, title, start_time, end_time, duration, status, category, task_id, elapsed, uuid,
+        created_at, updated_at
     FROM pomodoro
     ORDER BY start_time DESC
     limit :limit
 "#;
 
-/// Count All the tasks that are done and ones that are pending
+/// Count the done, open and late tasks per `{{bucket}}` of their due date
 const GET_TASK_ANALYTICS: &str = r#"
-WITH due_dates AS (SELECT
-    DATE(due_date) AS date,
-    COUNT(*) as total, 
-    COUNT (CASE WHEN status = 0 THEN 1 ELSE null END) AS pending,
-    COUNT (CASE WHEN (status = 1 and DATE(updated_at) >= DATE(due_date)) THEN 1 ELSE null END) AS delivered_over_due
+WITH buckets AS (SELECT
+    {{bucket}} AS bucket,
+    MIN(priority) AS priority,
+    COUNT(*) AS total,
+    COUNT (CASE WHEN status = 1 THEN 1 ELSE null END) AS done,
+    COUNT (CASE WHEN status = 0 THEN 1 ELSE null END) AS open,
+    COUNT (CASE WHEN (status = 0 AND julianday(due_date) < julianday(:now)) THEN 1 ELSE null END) AS overdue,
+    COUNT (CASE WHEN (status = 1 AND julianday(completed_at) > julianday(due_date)) THEN 1 ELSE null END) AS done_late,
+    AVG (CASE WHEN status = 1 THEN julianday(completed_at) - julianday(created_at) ELSE null END) AS avg_lead_time
     FROM tasks
-    WHERE due_date >= :start_date AND due_date <=  :end_date
-    GROUP BY DATE(due_date)
+    WHERE julianday(due_date) >= julianday(:start_date) AND julianday(due_date) < julianday(:end_date)
+    GROUP BY {{bucket}}
     )
-SELECT 
-    dd.date,
-    dd.total, 
-    dd.delivered_over_due, 
-    dd.pending,
-    ( (dd.total - dd.pending - dd.delivered_over_due) * 100.00 ) / dd.total as burn_down_rate
-FROM 
-    due_dates dd 
-ORDER BY 
-    dd.date 
-DESC
+SELECT
+    b.bucket,
+    b.total,
+    b.done,
+    b.open,
+    b.overdue,
+    b.done_late,
+    ( b.done * 100.00 ) / b.total AS completion_rate,
+    CASE WHEN b.done > 0 THEN ( (b.done - b.done_late) * 100.00 ) / b.done ELSE null END AS on_time_rate,
+    b.avg_lead_time
+FROM
+    buckets b
+ORDER BY
+    {{order_by}}
 "#;
 
-// NOTE: The 'Connection' as Ok value type of Result can become more generic later
-pub fn init_db(home_dir: String) -> Result<(), String> {
-    let mut path = home_dir + DB_FILE_PATH;
-
-    if let Ok(ok) = fs::exists(&path) {
-        if !ok {
-            if let Err(err) = fs::create_dir_all(&path) {
-                return Err(err.to_string());
-            }
-        }
-    }
+/// Days from creation to completion of every task completed in the range
+const GET_TASK_CYCLE_TIMES: &str = r#"
+    SELECT julianday(completed_at) - julianday(created_at) FROM tasks
+        WHERE status = 1
+            AND julianday(completed_at) >= julianday(:start_date)
+            AND julianday(completed_at) < julianday(:end_date)
+"#;
 
-    path += DB_FILE_NAME;
+/// Age in days of every task that is still open
+const GET_OPEN_TASK_AGES: &str = r#"
+    SELECT julianday(:now) - julianday(created_at) FROM tasks
+        WHERE status = 0 AND julianday(created_at) <= julianday(:now)
+"#;
 
-    let conn = match Connection::open(path) {
-        Ok(val) => val,
-        Err(err) => return Err(err.to_string()),
-    };
+/// Open tasks whose reminder is due and has not fired yet
+const GET_DUE_REMINDERS: &str = r#"
+    SELECT id, status, title, due_date, priority, category, all_day, remind_at, tags,
+        uuid, created_at, updated_at, remind_before FROM tasks
+        WHERE status = 0 AND reminded_at IS NULL AND julianday(remind_at) <= julianday(:now)
+        ORDER BY remind_at"#;
+
+const MARK_REMINDED: &str = r#"
+    UPDATE tasks SET reminded_at = :reminded_at WHERE id = :id"#;
+
+/// Open tasks and how many of them are overdue, answered from idx_tasks_status_due_date alone
+const GET_OPEN_TASK_COUNTS: &str = r#"
+    SELECT COUNT(*) AS open, COALESCE(SUM(julianday(due_date) < julianday(:now)), 0) AS overdue
+        FROM tasks
+        WHERE status = 0"#;
+
+/// The `{{running}}` session, NULL when there is none, followed by the `{{counts}}` of open tasks,
+/// the one statement `prompt` runs
+const GET_PROMPT_STATUS: &str = r#"
+    WITH running AS ({{running}}), counts AS ({{counts}})
+    SELECT running.*, counts.open, counts.overdue FROM counts LEFT JOIN running ON 1"#;
+
+const GET_COMPLETED_TASKS: &str = r#"
+    SELECT id, status, title, due_date, priority, category, all_day, remind_at, tags,
+        uuid, created_at, updated_at, remind_before FROM tasks
+        WHERE status = 1
+            AND julianday(completed_at) >= julianday(:start_date)
+            AND julianday(completed_at) < julianday(:end_date)
+        ORDER BY completed_at"#;
+
+/// Tasks due in the range that are overdue at `:now` or were completed after their due date
+const GET_SLIPPED_TASKS: &str = r#"
+    SELECT id, status, title, due_date, priority, category, all_day, remind_at, tags,
+        uuid, created_at, updated_at, remind_before FROM tasks
+        WHERE julianday(due_date) >= julianday(:start_date)
+            AND julianday(due_date) < julianday(:end_date)
+            AND (
+                (status = 0 AND julianday(due_date) < julianday(:now))
+                OR (status = 1 AND julianday(completed_at) > julianday(due_date))
+            )
+        ORDER BY due_date"#;
 
-    match conn.execute(CREATE_TASKS_TABLE, []) {
-        Ok(_) => {}
-        Err(err) => return Err(err.to_string()),
-    };
+/// Seconds spent in finished or paused work sessions per category
+const GET_FOCUS_BY_CATEGORY: &str = r#"
+    SELECT
+        COALESCE(category, '-') AS category,
+        SUM(elapsed) AS seconds
+    FROM pomodoro
+    WHERE type = :type
+        AND elapsed > 0
+        AND julianday(start_time) >= julianday(:start_date)
+        AND julianday(start_time) < julianday(:end_date)
+    GROUP BY COALESCE(category, '-')
+    ORDER BY seconds DESC"#;
+
+const INSERT_GOAL: &str = r#"
+    INSERT INTO goals (metric, period, target) VALUES (:metric, :period, :target)
+        RETURNING id"#;
+
+const GET_GOALS: &str = r#"SELECT id, metric, period, target FROM goals ORDER BY id"#;
+
+const DELETE_GOAL: &str = r#"DELETE FROM goals WHERE id = :id"#;
+
+/// Finished work sessions per local day
+const GET_DAILY_POMODOROS: &str = r#"
+    SELECT DATE(start_time, 'localtime') AS day, COUNT(*) FROM pomodoro
+        WHERE type = :type AND status = 2
+        GROUP BY day"#;
+
+/// Seconds focused in work sessions per local day, whether they were finished or not
+const GET_DAILY_FOCUS: &str = r#"
+    SELECT DATE(start_time, 'localtime') AS day, SUM(elapsed) FROM pomodoro
+        WHERE type = :type AND elapsed > 0
+        GROUP BY day"#;
+
+/// Tasks closed per local day
+const GET_DAILY_CLOSED_TASKS: &str = r#"
+    SELECT DATE(completed_at, 'localtime') AS day, COUNT(*) FROM tasks
+        WHERE status = 1 AND completed_at IS NOT NULL
+        GROUP BY day"#;
+
+const INSERT_TIME_ENTRY: &str = r#"
+    INSERT INTO time_entries (task_id, title, category, start_time)
+        VALUES (:task_id, :title, :category, :start_time)
+        RETURNING id"#;
+
+const GET_RUNNING_TIME_ENTRY: &str = r#"
+    SELECT id, task_id, title, category, start_time, end_time FROM time_entries
+        WHERE end_time IS NULL
+        ORDER BY start_time DESC
+        LIMIT 1"#;
+
+const FINISH_TIME_ENTRY: &str = r#"
+    UPDATE time_entries SET end_time = :end_time WHERE id = :id AND end_time IS NULL"#;
+
+/// Time entries and focused work sessions, entries still running count up to `:now`
+const TRACKED_SPANS: &str = r#"
+    SELECT task_id, title, category, start_time,
+        CAST(ROUND((julianday(COALESCE(end_time, :now)) - julianday(start_time)) * 86400) AS INTEGER) AS seconds
+    FROM time_entries
+    UNION ALL
+    SELECT task_id, title, category, start_time, elapsed AS seconds FROM pomodoro
+        WHERE type = :type AND elapsed > 0
+"#;
 
-    match conn.execute(CREATE_POMODORO_TABLE, []) {
-        Ok(_) => {}
-        Err(err) => return Err(err.to_string()),
-    };
+/// Seconds and entries per `{{bucket}}` of the `{{spans}}` started in the range
+const GET_TRACKED_TIME: &str = r#"
+WITH spans AS ({{spans}})
+SELECT {{bucket}} AS bucket, COUNT(*), SUM(s.seconds) AS seconds
+FROM spans s
+    LEFT JOIN tasks t ON t.id = s.task_id
+WHERE julianday(s.start_time) >= julianday(:start_date)
+    AND julianday(s.start_time) < julianday(:end_date)
+GROUP BY bucket
+ORDER BY seconds DESC, bucket"#;
+
+/// Seconds of the `{{spans}}` started in the range per day in the home time zone, category and
+/// task
+const GET_TIMESHEET: &str = r#"
+WITH spans AS ({{spans}})
+SELECT
+    home_strftime('%Y-%m-%d', s.start_time) AS day,
+    COALESCE(s.category, '-') AS category,
+    CASE WHEN t.id IS NULL THEN s.title ELSE '#' || t.id || ' ' || t.title END AS task,
+    SUM(s.seconds) AS seconds
+FROM spans s
+    LEFT JOIN tasks t ON t.id = s.task_id
+WHERE julianday(s.start_time) >= julianday(:start_date)
+    AND julianday(s.start_time) < julianday(:end_date)
+GROUP BY 1, 2, 3
+HAVING SUM(s.seconds) > 0
+ORDER BY 1, 2, 3"#;
+
+/// How many of the tables every version of the schema starts with exist
+const COUNT_BASE_TABLES: &str = r#"
+    SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name IN ('tasks', 'pomodoro')"#;
+
+/// What orphaned rows are, a query counting them and the statement repairing them
+const ORPHANED_ROWS: &[(&str, &str, &str)] = &[
+    (
+        "sessions linked to a task that does not exist",
+        "SELECT COUNT(*) FROM pomodoro WHERE task_id NOT IN (SELECT id FROM tasks)",
+        "UPDATE pomodoro SET task_id = NULL WHERE task_id NOT IN (SELECT id FROM tasks)",
+    ),
+    (
+        "time entries linked to a task that does not exist",
+        "SELECT COUNT(*) FROM time_entries WHERE task_id NOT IN (SELECT id FROM tasks)",
+        "UPDATE time_entries SET task_id = NULL WHERE task_id NOT IN (SELECT id FROM tasks)",
+    ),
+    (
+        "interruptions of a session that does not exist",
+        "SELECT COUNT(*) FROM pomodoro_interruptions WHERE pomodoro_id NOT IN (SELECT id FROM pomodoro)",
+        "DELETE FROM pomodoro_interruptions WHERE pomodoro_id NOT IN (SELECT id FROM pomodoro)",
+    ),
+];
+
+/// Table and column of the RFC 3339 times read back into records, and whether they can be NULL
+const TIME_COLUMNS: &[(&str, &str, bool)] = &[
+    ("tasks", "due_date", false),
+    ("tasks", "remind_at", true),
+    ("pomodoro", "start_time", false),
+    ("pomodoro", "end_time", true),
+    ("time_entries", "start_time", false),
+    ("time_entries", "end_time", true),
+];
+
+/// Sessions still marked as running after their time ran out, the timer died without stopping them
+const GET_STALE_POMODOROS: &str = r#"
+    SELECT id, title, start_time FROM pomodoro
+        WHERE status = 0
+            AND end_time IS NULL
+            AND julianday(start_time, '+' || duration || ' seconds') < julianday(:now)
+        ORDER BY start_time"#;
+
+/// Stops a stale session after the time it had recorded
+const STOP_STALE_POMODORO: &str = r#"
+    UPDATE pomodoro
+    SET
+        status = 1,
+        end_time = strftime('%Y-%m-%dT%H:%M:%SZ', start_time, '+' || elapsed || ' seconds')
+    WHERE id = :id"#;
 
-    Ok(())
+/// Tasks as `sync` exchanges them, in the order of `SyncTable::Tasks.columns()`
+const GET_SYNC_TASKS: &str = r#"
+    SELECT uuid, updated_at, title, status, due_date, all_day, priority, category, tags, remind_at,
+        reminded_at, completed_at, created_at, remind_before
+    FROM tasks"#;
+
+const UPSERT_SYNC_TASK: &str = r#"
+    INSERT INTO tasks (uuid, updated_at, title, status, due_date, all_day, priority, category,
+            tags, remind_at, reminded_at, completed_at, created_at, remind_before)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
+    ON CONFLICT (uuid) DO UPDATE SET
+        updated_at = excluded.updated_at,
+        title = excluded.title,
+        status = excluded.status,
+        due_date = excluded.due_date,
+        all_day = excluded.all_day,
+        priority = excluded.priority,
+        category = excluded.category,
+        tags = excluded.tags,
+        remind_at = excluded.remind_at,
+        reminded_at = excluded.reminded_at,
+        completed_at = excluded.completed_at,
+        created_at = excluded.created_at,
+        remind_before = excluded.remind_before"#;
+
+/// Sessions as `sync` exchanges them, in the order of `SyncTable::Sessions.columns()`. The task
+/// is referred to by its uuid.
+const GET_SYNC_POMODOROS: &str = r#"
+    SELECT p.uuid, p.updated_at, p.title, p.type, p.start_time, p.end_time, p.duration, p.elapsed,
+        p.status, p.category, t.uuid, p.created_at
+    FROM pomodoro p
+        LEFT JOIN tasks t ON t.id = p.task_id"#;
+
+const UPSERT_SYNC_POMODORO: &str = r#"
+    INSERT INTO pomodoro (uuid, updated_at, title, type, start_time, end_time, duration, elapsed,
+            status, category, task_id, created_at)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, (SELECT id FROM tasks WHERE uuid = ?11), ?12)
+    ON CONFLICT (uuid) DO UPDATE SET
+        updated_at = excluded.updated_at,
+        title = excluded.title,
+        type = excluded.type,
+        start_time = excluded.start_time,
+        end_time = excluded.end_time,
+        duration = excluded.duration,
+        elapsed = excluded.elapsed,
+        status = excluded.status,
+        category = excluded.category,
+        task_id = excluded.task_id,
+        created_at = excluded.created_at"#;
+
+/// Header and `{{bucket}}` of the tracked time per task, tasks show with their id
+pub(crate) const TIME_BY_TASK: (&str, &str) = (
+    "Task",
+    "CASE WHEN t.id IS NULL THEN s.title ELSE '#' || t.id || ' ' || t.title END",
+);
+
+pub(crate) const TIME_BY_CATEGORY: (&str, &str) = ("Category", "COALESCE(s.category, '-')");
+
+/// Creates the tables of a new database and brings them up to date.
+pub(crate) fn create_schema(conn: &Connection) -> Result<(), StoreError> {
+    conn.execute(CREATE_TASKS_TABLE, [])?;
+    conn.execute(CREATE_POMODORO_TABLE, [])?;
+
+    migrate(conn)
 }
 
-fn get_connection() -> Result<Connection, String> {
-    let home_dir = match get_home_directory() {
-        Ok(val) => val,
-        Err(_) => {
-            return Err(
-                "Could connect to the database, please run the init command again".to_string(),
-            )
-        }
-    };
-
-    let path = home_dir + DB_FILE_PATH + DB_FILE_NAME;
+/// Brings the schema up to date by running the migrations the database has not seen yet.
+pub(crate) fn migrate(conn: &Connection) -> Result<(), StoreError> {
+    let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        conn.execute_batch(&format!(
+            "BEGIN; {} PRAGMA user_version = {}; COMMIT;",
+            migration,
+            i + 1
+        ))
+        .map_err(|err| {
+            let _ = conn.execute_batch("ROLLBACK");
+            StoreError::Migration {
+                version: i + 1,
+                source: err,
+            }
+        })?;
+    }
 
-    Connection::open(path).map_err(|err| err.to_string())
+    Ok(())
 }
 
 // TODD: add priority and category filters later.
-pub fn get_tasks(ls_args: &LSArgs) -> Result<Vec<Task>, String> {
-    let conn = get_connection()?;
-    let now = Local::now();
-    let due_date = (now + chrono::Duration::days(ls_args.days as i64)).to_rfc3339();
+pub(crate) fn get_tasks(
+    conn: &Connection,
+    ls_args: &LSArgs,
+    due_before: DateTime<Local>,
+) -> Result<Vec<Task>, StoreError> {
+    let due_before = due_before.to_rfc3339();
 
     let mut query = GET_TASKS.to_string();
     let mut params_values: Vec<(&str, &dyn ToSql)> =
-        vec![(":due_date", &due_date), (":limit", &ls_args.limit)];
+        vec![(":due_before", &due_before), (":limit", &ls_args.limit)];
 
     let p_value: usize;
     match ls_args.priority {
@@ -198,121 +676,236 @@ pub fn get_tasks(ls_args: &LSArgs) -> Result<Vec<Task>, String> {
         },
     };
 
-    let mut stmt = conn
-        .prepare(query.as_str())
-        .map_err(|err| err.to_string())?;
+    let mut stmt = conn.prepare(query.as_str())?;
 
-    let tasks_iter = stmt.query_map(params_values.as_slice(), parse_task);
-
-    let tasks_iter = match tasks_iter {
-        Ok(val) => val,
-        Err(err) => return Err(err.to_string()),
-    };
+    let tasks_iter = stmt.query_map(params_values.as_slice(), parse_task)?;
 
     let mut tasks: Vec<Task> = Vec::new();
 
     for task in tasks_iter {
-        tasks.push(task.unwrap());
+        tasks.push(task?);
     }
 
     Ok(tasks)
 }
 
-pub fn save_task(task: &mut Task) -> Result<(), String> {
-    let conn = match get_connection() {
-        Ok(val) => val,
-        Err(err) => return Err(err.to_string()),
-    };
-
-    let mut stmt = conn.prepare(INSERT_TASK).map_err(|err| err.to_string())?;
+/// The uuid, a fresh one unless `uuid` is given, and the current time for a record about to be
+/// inserted.
+fn new_meta(uuid: &str) -> RecordMeta {
+    // Kept to the milliseconds that are written.
+    let now = Utc::now().trunc_subsecs(3);
+    RecordMeta {
+        uuid: match uuid {
+            "" => new_uuid(),
+            uuid => uuid.to_lowercase(),
+        },
+        created_at: Some(now.with_timezone(&Local)),
+        updated_at: Some(now.with_timezone(&Local)),
+    }
+}
 
-    println!("{:?}", task);
+/// How a record's creation time is written, the same way the `updated_at` triggers write theirs.
+fn meta_time(meta: &RecordMeta) -> Option<String> {
+    meta.created_at.map(|at| {
+        at.with_timezone(&Utc)
+            .format("%Y-%m-%dT%H:%M:%S%.3fZ")
+            .to_string()
+    })
+}
 
-    let status = task.status as usize;
-    println!("status as usize: {}", status);
+pub(crate) fn save_task(conn: &Connection, task: &mut Task) -> Result<(), StoreError> {
+    let meta = new_meta(&task.meta.uuid);
+    let mut stmt = conn.prepare(INSERT_TASK)?;
 
-    let res = stmt.execute(named_params! {
-        ":status": status,
+    stmt.execute(named_params! {
+        ":status": task.status as usize,
         ":title": task.title,
-        ":due_date": task.due_date.to_rfc3339(),
+        ":due_date": task.due_date.at.to_rfc3339(),
+        ":all_day": task.due_date.all_day,
+        ":remind_at": task.remind_at().map(|at| at.to_rfc3339()),
+        ":remind_before": remind_before(task),
         ":priority": task.priority as usize,
         ":category": task.category,
-    });
+        ":uuid": meta.uuid,
+        ":created_at": meta_time(&meta),
+        ":tags": join_tags(&task.tags),
+    })?;
+
+    task.id = conn.last_insert_rowid() as u64;
+    task.meta = meta;
+    Ok(())
+}
 
-    match res {
-        Ok(_) => Ok(()),
-        Err(err) => Err(err.to_string()),
+/// How a reminder relative to the due date is kept, NULL for one at a fixed time.
+fn remind_before(task: &Task) -> Option<i64> {
+    match task.remind {
+        Some(Remind::Before(offset)) => Some(offset.num_seconds()),
+        _ => None,
     }
 }
 
-pub fn get_task_by_id(task_id: usize) -> Result<Task, String> {
-    let conn = match get_connection() {
-        Ok(val) => val,
-        Err(err) => return Err(err.to_string()),
-    };
+/// How the tags of a task are kept, NULL for none.
+fn join_tags(tags: &[String]) -> Option<String> {
+    Some(tags.join(" ")).filter(|tags| !tags.is_empty())
+}
+
+/// Every task, open or done, in the order they were added.
+pub(crate) fn query_all_tasks(conn: &Connection) -> Result<Vec<Task>, StoreError> {
+    query_tasks(conn, GET_ALL_TASKS, &[])
+}
+
+/// Writes the fields of the task back, all but its id and metadata.
+pub(crate) fn update_task(conn: &Connection, task: &Task) -> Result<(), StoreError> {
+    let rows_affected = conn.execute(
+        UPDATE_TASK,
+        named_params! {
+            ":id": task.id,
+            ":status": task.status.to_usize(),
+            ":tags": join_tags(&task.tags),
+            ":title": task.title,
+            ":due_date": task.due_date.at.to_rfc3339(),
+            ":all_day": task.due_date.all_day,
+            ":priority": task.priority as usize,
+            ":category": task.category,
+            ":remind_at": task.remind_at().map(|at| at.to_rfc3339()),
+            ":remind_before": remind_before(task),
+        },
+    )?;
+
+    if rows_affected == 0 {
+        return Err(StoreError::NotFound {
+            record: "task",
+            id: task.id,
+        });
+    }
+
+    Ok(())
+}
 
+pub(crate) fn query_task_by_id(conn: &Connection, task_id: usize) -> Result<Task, StoreError> {
     conn.query_row(GET_TASK_BY_ID, params![task_id], parse_task)
-        .map_err(|err| err.to_string())
+        .map_err(|err| StoreError::or_not_found(err, "task", task_id as u64))
 }
 
-pub fn done_task(task_id: usize) -> Result<(), String> {
-    let conn = match get_connection() {
-        Ok(val) => val,
-        Err(err) => return Err(err.to_string()),
-    };
+/// The one task whose uuid starts with `prefix`.
+pub(crate) fn query_task_by_uuid_prefix(
+    conn: &Connection,
+    prefix: &str,
+) -> Result<Task, StoreError> {
+    let mut tasks = query_tasks(
+        conn,
+        GET_TASKS_BY_UUID_PREFIX,
+        named_params! {":prefix": prefix.to_lowercase()},
+    )?;
+
+    match tasks.len() {
+        0 => Err(StoreError::UnknownUuid {
+            record: "task",
+            prefix: prefix.to_string(),
+        }),
+        1 => Ok(tasks.remove(0)),
+        _ => Err(StoreError::Invalid(format!(
+            "The uuid prefix {} matches more than one task, give more of it",
+            prefix
+        ))),
+    }
+}
+
+/// How many characters of their uuids tell all the tasks apart.
+pub(crate) fn query_uuid_prefix_len(conn: &Connection) -> Result<usize, StoreError> {
+    let mut stmt = conn.prepare(GET_TASK_UUIDS)?;
+    let uuids = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
 
-    let rows_affected = conn
-        .execute(DONE_TASK, params![task_id])
-        .map_err(|err| err.to_string())?;
+    Ok(unique_prefix_len(&uuids))
+}
+
+pub(crate) fn mark_task_done(conn: &Connection, task_id: usize) -> Result<(), StoreError> {
+    let rows_affected = conn.execute(DONE_TASK, params![task_id])?;
 
     if rows_affected == 0 {
-        return Err("Could not mark task as done".to_string());
+        return Err(StoreError::NotFound {
+            record: "task",
+            id: task_id as u64,
+        });
     };
 
     Ok(())
 }
 
-pub fn get_pomodoro(ls_args: &LSArgs) -> Result<Vec<PomoTask>, String> {
-    let conn = match get_connection() {
-        Ok(val) => val,
-        Err(err) => return Err(err.to_string()),
-    };
-    let mut stmt = match conn.prepare(GET_POMODORO_LIST) {
-        Ok(val) => val,
-        Err(err) => return Err(err.to_string()),
-    };
+/// Open tasks whose reminder is due at `now` and has not fired yet.
+pub(crate) fn get_due_reminders(
+    conn: &Connection,
+    now: DateTime<Local>,
+) -> Result<Vec<Task>, StoreError> {
+    query_tasks(
+        conn,
+        GET_DUE_REMINDERS,
+        named_params! {":now": now.to_rfc3339()},
+    )
+}
+
+/// The number of open tasks, of those overdue at `now` and the session running at `now` if any.
+pub(crate) fn query_prompt_status(
+    conn: &Connection,
+    now: DateTime<Local>,
+) -> Result<(u64, u64, Option<PomoTask>), StoreError> {
+    let sql = GET_PROMPT_STATUS
+        .replace("{{running}}", GET_RUNNING_POMODORO)
+        .replace("{{counts}}", GET_OPEN_TASK_COUNTS);
+    conn.query_row(&sql, named_params! {":now": now.to_rfc3339()}, |row| {
+        let running = match row.get::<_, Option<u64>>(0)? {
+            Some(_) => Some(parse_pomo_task(row)?),
+            None => None,
+        };
+        Ok((row.get(13)?, row.get(14)?, running))
+    })
+    .map_err(StoreError::from)
+}
+
+pub(crate) fn set_reminded(
+    conn: &Connection,
+    task_id: u64,
+    reminded_at: DateTime<Local>,
+) -> Result<(), StoreError> {
+    conn.execute(
+        MARK_REMINDED,
+        named_params! {
+            ":id": task_id,
+            ":reminded_at": reminded_at.to_rfc3339(),
+        },
+    )?;
+
+    Ok(())
+}
+
+pub(crate) fn get_pomodoro(conn: &Connection, limit: usize) -> Result<Vec<PomoTask>, StoreError> {
+    let mut stmt = conn.prepare(GET_POMODORO_LIST)?;
 
     let pomo_tasks_iter = stmt.query_map(
         named_params! {
-            ":limit": ls_args.limit,
+            ":limit": limit,
         },
         parse_pomo_task,
-    );
-
-    let pomo_tasks_iter = match pomo_tasks_iter {
-        Ok(val) => val,
-        Err(err) => return Err(err.to_string()),
-    };
+    )?;
 
     let mut pomo_tasks: Vec<PomoTask> = Vec::new();
 
     for pomo_task in pomo_tasks_iter {
-        pomo_tasks.push(pomo_task.unwrap());
+        pomo_tasks.push(pomo_task?);
     }
 
     Ok(pomo_tasks)
 }
 
-pub fn add_pomodoro(pomo_task: &mut PomoTask) -> Result<(), String> {
-    let conn = match get_connection() {
-        Ok(val) => val,
-        Err(err) => return Err(err.to_string()),
-    };
-
-    let mut stmt = conn.prepare(INSERT_POMO).map_err(|e| e.to_string())?;
+/// Records a session starting now.
+pub(crate) fn add_pomodoro(conn: &Connection, pomo_task: &mut PomoTask) -> Result<(), StoreError> {
+    let meta = new_meta("");
+    let mut stmt = conn.prepare(INSERT_POMO)?;
 
     let start_time = Local::now();
-    let res = stmt.query_row(
+    let id = stmt.query_row(
         named_params! {
             ":type": pomo_task.pomo_type.to_usize(),
             ":title": pomo_task.title,
@@ -320,192 +913,2304 @@ pub fn add_pomodoro(pomo_task: &mut PomoTask) -> Result<(), String> {
             ":duration": pomo_task.duration.to_i64(),
             ":start_time": start_time.to_rfc3339(),
             ":status": pomo_task.status.to_usize(),
-
+            ":task_id": pomo_task.task_id,
+            ":uuid": meta.uuid,
+            ":created_at": meta_time(&meta),
         },
-        |row| Ok(row.get::<_, u64>(0)),
-    );
-
-    let id = match res {
-        Ok(val) => val.unwrap(),
-        Err(err) => return Err(err.to_string()),
-    };
+        |row| row.get::<_, u64>(0),
+    )?;
 
     pomo_task.id = id;
     pomo_task.start_time = start_time;
+    pomo_task.meta = meta;
 
     Ok(())
 }
 
-pub fn update_pomodoro(pomo: &PomoTask) -> Result<(), String> {
-    let conn = match get_connection() {
-        Ok(val) => val,
-        Err(err) => return Err(err.to_string()),
-    };
-
-    let rows_affected = conn.execute(
+pub(crate) fn update_pomodoro(conn: &Connection, pomo: &PomoTask) -> Result<(), StoreError> {
+    conn.execute(
         UPDATE_POMODORO,
         named_params! {
             ":id": pomo.id,
             ":status": pomo.status.to_usize(),
+            ":duration": pomo.duration.to_i64(),
+            ":elapsed": pomo.elapsed.to_i64(),
             ":end_date": pomo.end_time.to_rfc3339(),
         },
+    )?;
+
+    Ok(())
+}
+
+/// Records a finished session that ran without the timer, unless it overlaps another session.
+pub(crate) fn insert_logged_pomodoro(
+    conn: &Connection,
+    pomo_task: &mut PomoTask,
+) -> Result<(), StoreError> {
+    let overlapping = conn.query_row(
+        GET_OVERLAPPING_POMODORO,
+        named_params! {
+            ":start_time": pomo_task.start_time.to_rfc3339(),
+            ":end_time": pomo_task.end_time.to_rfc3339(),
+        },
+        |row| Ok((row.get::<_, u64>(0)?, row.get::<_, String>(1)?)),
     );
 
-    match rows_affected {
-        Ok(_) => Ok(()),
-        Err(err) => Err(err.to_string()),
+    match overlapping {
+        Ok((id, title)) => {
+            return Err(StoreError::Conflict(format!(
+                "The session overlaps session #{} ({})",
+                id, title
+            )))
+        }
+        Err(rusqlite::Error::QueryReturnedNoRows) => {}
+        Err(err) => return Err(err.into()),
     }
+
+    let meta = new_meta("");
+    pomo_task.id = conn.query_row(
+        INSERT_LOGGED_POMO,
+        named_params! {
+            ":type": pomo_task.pomo_type.to_usize(),
+            ":title": pomo_task.title,
+            ":start_time": pomo_task.start_time.to_rfc3339(),
+            ":end_time": pomo_task.end_time.to_rfc3339(),
+            ":duration": pomo_task.duration.to_i64(),
+            ":elapsed": pomo_task.elapsed.to_i64(),
+            ":status": pomo_task.status.to_usize(),
+            ":category": pomo_task.category,
+            ":task_id": pomo_task.task_id,
+            ":uuid": meta.uuid,
+            ":created_at": meta_time(&meta),
+        },
+        |row| row.get(0),
+    )?;
+    pomo_task.meta = meta;
+
+    Ok(())
 }
 
-pub fn get_analysis(ls_args: &AnalyzeArgs) -> Result<Vec<DailyAnalysis>, String> {
-    let conn = match get_connection() {
-        Ok(val) => val,
-        Err(err) => return Err(err.to_string()),
-    };
+/// Logs an interruption noted during the session.
+pub(crate) fn insert_interruption(
+    conn: &Connection,
+    pomodoro_id: u64,
+    kind: InterruptionKind,
+    note: &str,
+    created_at: DateTime<Local>,
+) -> Result<(), StoreError> {
+    conn.execute(
+        INSERT_INTERRUPTION,
+        named_params! {
+            ":pomodoro_id": pomodoro_id,
+            ":kind": kind.to_usize(),
+            ":note": note,
+            ":created_at": created_at.to_rfc3339(),
+        },
+    )?;
+
+    Ok(())
+}
 
-    let base_date_time = Local::now() - chrono::Duration::days(ls_args.days as i64);
-    let base_date = base_date_time.to_utc();
-    let end_date = (Local::now() + chrono::Duration::days(ls_args.days as i64)).to_utc();
+/// The session running in the foreground or background at `now`, if any.
+pub(crate) fn query_running_pomodoro(
+    conn: &Connection,
+    now: DateTime<Local>,
+) -> Result<Option<PomoTask>, StoreError> {
+    match conn.query_row(
+        GET_RUNNING_POMODORO,
+        named_params! {":now": now.to_rfc3339()},
+        parse_pomo_task,
+    ) {
+        Ok(session) => Ok(Some(session)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
 
-    let mut stmt = match conn.prepare(GET_TASK_ANALYTICS) {
-        Ok(val) => val,
-        Err(err) => return Err(err.to_string()),
+/// The interruptions of each work session in the range and of each hour of the day.
+pub(crate) fn query_interruption_analysis(
+    conn: &Connection,
+    start_date: DateTime<Local>,
+    end_date: DateTime<Local>,
+) -> Result<(Vec<SessionInterruptions>, Vec<HourInterruptions>), StoreError> {
+    let range = named_params! {
+        ":start_date": start_date.to_rfc3339(),
+        ":end_date": end_date.to_rfc3339(),
     };
 
-    let daily_analytics_iter = stmt.query_map(
+    let sessions = conn
+        .prepare(GET_SESSION_INTERRUPTIONS)
+        .and_then(|mut stmt| {
+            stmt.query_map(range, |row| {
+                let start_time: String = row.get(2)?;
+                Ok(SessionInterruptions {
+                    id: row.get(0)?,
+                    title: row.get(1)?,
+                    start_time: DateTime::parse_from_rfc3339(&start_time)
+                        .map(|d| d.with_timezone(&Local))
+                        .map_err(|e| {
+                            rusqlite::Error::FromSqlConversionFailure(
+                                2,
+                                rusqlite::types::Type::Text,
+                                Box::new(e),
+                            )
+                        })?,
+                    category: row.get(3)?,
+                    internal: row.get(4)?,
+                    external: row.get(5)?,
+                })
+            })?
+            .collect()
+        })?;
+
+    let hours = conn
+        .prepare(GET_INTERRUPTIONS_BY_HOUR)
+        .and_then(|mut stmt| {
+            stmt.query_map(range, |row| {
+                Ok(HourInterruptions {
+                    hour: row.get(0)?,
+                    internal: row.get(1)?,
+                    external: row.get(2)?,
+                })
+            })?
+            .collect()
+        })?;
+
+    Ok((sessions, hours))
+}
+
+pub(crate) fn insert_goal(conn: &Connection, goal: &mut Goal) -> Result<(), StoreError> {
+    goal.id = conn.query_row(
+        INSERT_GOAL,
         named_params! {
-            ":start_date": base_date.to_rfc3339(),
-            ":end_date": end_date.to_rfc3339(),
+            ":metric": goal.metric.to_usize(),
+            ":period": goal.period.to_usize(),
+            ":target": goal.target,
         },
-        parse_daily_analysis,
-    );
+        |row| row.get(0),
+    )?;
 
-    let daily_analytics_iter = match daily_analytics_iter {
-        Ok(val) => val,
-        Err(err) => return Err(err.to_string()),
-    };
+    Ok(())
+}
 
-    let mut daily_analytics: Vec<DailyAnalysis> = Vec::new();
+pub(crate) fn remove_goal(conn: &Connection, goal_id: u64) -> Result<(), StoreError> {
+    let rows_affected = conn.execute(DELETE_GOAL, named_params! {":id": goal_id})?;
 
-    for daily_analysis in daily_analytics_iter {
-        let daily_analysis = match daily_analysis {
-            Ok(val) => val,
-            Err(err) => return Err(err.to_string()),
-        };
+    if rows_affected == 0 {
+        return Err(StoreError::NotFound {
+            record: "goal",
+            id: goal_id,
+        });
+    }
+
+    Ok(())
+}
 
-        daily_analytics.push(daily_analysis);
+/// Every goal with its progress in the period containing `today` and its streaks.
+pub(crate) fn query_goal_progress(
+    conn: &Connection,
+    today: NaiveDate,
+) -> Result<Vec<GoalProgress>, StoreError> {
+    let goals: Vec<Goal> = conn.prepare(GET_GOALS).and_then(|mut stmt| {
+        stmt.query_map([], |row| {
+            Ok(Goal {
+                id: row.get(0)?,
+                metric: GoalMetric::from_usize(row.get(1)?),
+                period: GoalPeriod::from_usize(row.get(2)?),
+                target: row.get(3)?,
+            })
+        })?
+        .collect()
+    })?;
+
+    let mut progress = Vec::new();
+    for goal in goals {
+        let daily = query_daily_totals(conn, goal.metric)?;
+        progress.push(GoalProgress::from_daily(goal, &daily, today));
     }
 
-    Ok(daily_analytics)
+    Ok(progress)
 }
 
-fn parse_task(row: &rusqlite::Row) -> Result<Task, rusqlite::Error> {
-    let date_str: String = row.get::<_, String>(3)?;
-    let due_date: DateTime<Local> = DateTime::parse_from_rfc3339(&date_str)
-        .unwrap()
-        .with_timezone(&Local);
-    Ok(Task {
-        id: row.get(0)?,
-        status: TaskStatus::from_usize(row.get::<_, usize>(1)?),
-        title: row.get(2)?,
-        due_date,
-        priority: Priority::from_usize(row.get::<_, usize>(4)?),
-        category: row.get(5)?,
-    })
+/// The metric's total of every local day that has any.
+fn query_daily_totals(
+    conn: &Connection,
+    metric: GoalMetric,
+) -> Result<Vec<(NaiveDate, i64)>, StoreError> {
+    let work = PomoType::Work.to_usize();
+    let (query, params): (&str, &[(&str, &dyn ToSql)]) = match metric {
+        GoalMetric::Pomodoros => (GET_DAILY_POMODOROS, named_params! {":type": work}),
+        GoalMetric::Focus => (GET_DAILY_FOCUS, named_params! {":type": work}),
+        GoalMetric::Tasks => (GET_DAILY_CLOSED_TASKS, named_params! {}),
+    };
+
+    conn.prepare(query)
+        .and_then(|mut stmt| {
+            stmt.query_map(params, |row| {
+                let day: String = row.get(0)?;
+                let day = NaiveDate::parse_from_str(&day, "%Y-%m-%d").map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(
+                        0,
+                        rusqlite::types::Type::Text,
+                        Box::new(e),
+                    )
+                })?;
+                Ok((day, row.get(1)?))
+            })?
+            .collect()
+        })
+        .map_err(StoreError::from)
 }
 
-fn parse_pomo_task(row: &rusqlite::Row) -> Result<PomoTask, rusqlite::Error> {
-    let start_date_str: String = row.get(3)?;
-    let start_date = DateTime::parse_from_rfc3339(&start_date_str)
-        .unwrap()
-        .with_timezone(&Local);
+/// Starts tracking time, unless another time entry is still running.
+pub(crate) fn insert_time_entry(
+    conn: &Connection,
+    entry: &mut TimeEntry,
+) -> Result<(), StoreError> {
+    if let Some(running) = query_running_time_entry(conn)? {
+        return Err(StoreError::Conflict(format!(
+            "Time entry #{} ({}) is still running, stop it with `tasklog track stop`",
+            running.id, running.title
+        )));
+    }
 
-    let end_date_str: String = row.get(4)?;
-    let end_date = DateTime::parse_from_rfc3339(&end_date_str)
-        .unwrap()
-        .with_timezone(&Local);
+    entry.id = conn.query_row(
+        INSERT_TIME_ENTRY,
+        named_params! {
+            ":task_id": entry.task_id,
+            ":title": entry.title,
+            ":category": entry.category,
+            ":start_time": entry.start_time.to_rfc3339(),
+        },
+        |row| row.get(0),
+    )?;
 
-    Ok(PomoTask {
-        id: row.get(0)?,
-        pomo_type: PomoType::from_usize(row.get::<_, usize>(1)?),
-        title: row.get(2)?,
-        duration: DurationField::from_i64(row.get::<_, i64>(5)?),
-        category: row.get(7)?,
-        status: PomoStatus::from_usize(row.get::<_, usize>(6)?),
-        start_time: start_date,
-        end_time: end_date,
-    })
+    Ok(())
 }
 
-fn parse_daily_analysis(row: &rusqlite::Row) -> Result<DailyAnalysis, rusqlite::Error> {
-    Ok(DailyAnalysis {
-        date: row.get::<_, String>(0)?,
-        total: row.get(1)?,
-        pending: row.get(2)?,
-        delivered_over_due: row.get(3)?,
-        burn_down_rate: row.get(4)?,
-    })
+pub(crate) fn query_running_time_entry(conn: &Connection) -> Result<Option<TimeEntry>, StoreError> {
+    match conn.query_row(GET_RUNNING_TIME_ENTRY, [], parse_time_entry) {
+        Ok(entry) => Ok(Some(entry)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::models::TaskStatus;
+/// Ends the time entry at `end_time`, entries already stopped keep their end.
+pub(crate) fn set_time_entry_end(
+    conn: &Connection,
+    entry: &mut TimeEntry,
+    end_time: DateTime<Local>,
+) -> Result<(), StoreError> {
+    let rows_affected = conn.execute(
+        FINISH_TIME_ENTRY,
+        named_params! {
+            ":id": entry.id,
+            ":end_time": end_time.to_rfc3339(),
+        },
+    )?;
 
-    use super::*;
-    use chrono::Duration;
-    use std::fs;
-    use std::path::Path;
+    if rows_affected == 0 {
+        return Err(StoreError::Conflict(format!(
+            "Time entry #{} was already stopped",
+            entry.id
+        )));
+    }
 
-    // Helper function to create a temporary database for testing
-    fn setup_test_db() -> Result<(Connection, String), String> {
-        // Create a temporary file path
-        let db_path = ":memory:";
+    entry.end_time = Some(end_time);
+    Ok(())
+}
 
-        // Ensure the database file does not exist
-        if Path::new(db_path).exists() {
-            fs::remove_file(db_path).map_err(|e| e.to_string())?;
-        }
+pub(crate) fn query_tracked_time(
+    conn: &Connection,
+    (group, bucket): (&'static str, &str),
+    start_date: DateTime<Local>,
+    end_date: DateTime<Local>,
+    now: DateTime<Local>,
+) -> Result<Vec<TimeAnalysis>, StoreError> {
+    let rows: Vec<(String, u64, i64)> = conn
+        .prepare(
+            &GET_TRACKED_TIME
+                .replace("{{spans}}", TRACKED_SPANS)
+                .replace("{{bucket}}", bucket),
+        )
+        .and_then(|mut stmt| {
+            stmt.query_map(
+                named_params! {
+                    ":type": PomoType::Work.to_usize(),
+                    ":start_date": start_date.to_rfc3339(),
+                    ":end_date": end_date.to_rfc3339(),
+                    ":now": now.to_rfc3339(),
+                },
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )?
+            .collect()
+        })?;
+
+    let total: i64 = rows.iter().map(|(_, _, seconds)| seconds).sum();
+    Ok(rows
+        .into_iter()
+        .map(|(bucket, entries, seconds)| TimeAnalysis {
+            group,
+            bucket,
+            entries,
+            seconds,
+            share: seconds as f64 * 100.0 / total.max(1) as f64,
+        })
+        .collect())
+}
 
-        // Initialize the database
-        let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
-        conn.execute(CREATE_TASKS_TABLE, [])
-            .map_err(|e| e.to_string())?;
-        conn.execute(CREATE_POMODORO_TABLE, [])
-            .map_err(|e| e.to_string())?;
+/// The time tracked in the range per local day, category and task.
+pub(crate) fn query_timesheet<Z>(
+    conn: &Connection,
+    home: Z,
+    start_date: DateTime<Local>,
+    end_date: DateTime<Local>,
+    now: DateTime<Local>,
+) -> Result<Vec<TimesheetLine>, StoreError>
+where
+    Z: TimeZone + Send + UnwindSafe + 'static,
+    Z::Offset: Display,
+{
+    register_home_strftime(conn, home)?;
+    conn.prepare(&GET_TIMESHEET.replace("{{spans}}", TRACKED_SPANS))
+        .and_then(|mut stmt| {
+            stmt.query_map(
+                named_params! {
+                    ":type": PomoType::Work.to_usize(),
+                    ":start_date": start_date.to_rfc3339(),
+                    ":end_date": end_date.to_rfc3339(),
+                    ":now": now.to_rfc3339(),
+                },
+                |row| {
+                    let day: String = row.get(0)?;
+                    Ok(TimesheetLine {
+                        date: NaiveDate::parse_from_str(&day, "%Y-%m-%d").map_err(|e| {
+                            rusqlite::Error::FromSqlConversionFailure(
+                                0,
+                                rusqlite::types::Type::Text,
+                                Box::new(e),
+                            )
+                        })?,
+                        category: row.get(1)?,
+                        task: row.get(2)?,
+                        seconds: row.get(3)?,
+                    })
+                },
+            )?
+            .collect()
+        })
+        .map_err(StoreError::from)
+}
 
-        Ok((conn, db_path.to_string()))
+/// Lets the queries format a time like `strftime` does, but in the home time zone rather than the
+/// one of the system, as `home_strftime(format, time)` with the formats of chrono.
+fn register_home_strftime<Z>(conn: &Connection, home: Z) -> Result<(), StoreError>
+where
+    Z: TimeZone + Send + UnwindSafe + 'static,
+    Z::Offset: Display,
+{
+    conn.create_scalar_function(
+        "home_strftime",
+        2,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        move |ctx| {
+            let format = ctx.get::<String>(0)?;
+            let time = parse_time(1, ctx.get(1)?)?;
+            Ok(time.with_timezone(&home).format(&format).to_string())
+        },
+    )?;
+
+    Ok(())
+}
+
+/// The tasks due in the range grouped by `group_by`, the days, ISO weeks and months they are due
+/// in taken in the `home` time zone.
+pub(crate) fn query_analysis_at<Z>(
+    conn: &Connection,
+    group_by: GroupBy,
+    home: Z,
+    start_date: DateTime<Local>,
+    end_date: DateTime<Local>,
+    now: DateTime<Local>,
+) -> Result<Vec<TaskAnalysis>, StoreError>
+where
+    Z: TimeZone + Send + UnwindSafe + 'static,
+    Z::Offset: Display,
+{
+    register_home_strftime(conn, home)?;
+
+    let (bucket, order_by) = match group_by {
+        GroupBy::Day => ("home_strftime('%Y-%m-%d', due_date)", "b.bucket DESC"),
+        GroupBy::Week => ("home_strftime('%G-W%V', due_date)", "b.bucket DESC"),
+        GroupBy::Month => ("home_strftime('%Y-%m', due_date)", "b.bucket DESC"),
+        GroupBy::Category => ("COALESCE(category, '-')", "b.total DESC, b.bucket"),
+        GroupBy::Priority => (
+            "CASE priority WHEN 1 THEN 'Low' WHEN 2 THEN 'Medium' WHEN 3 THEN 'High' ELSE 'Urgent' END",
+            "b.priority DESC",
+        ),
+    };
+
+    let query = GET_TASK_ANALYTICS
+        .replace("{{bucket}}", bucket)
+        .replace("{{order_by}}", order_by);
+
+    let mut stmt = conn.prepare(query.as_str())?;
+
+    let analytics_iter = stmt.query_map(
+        named_params! {
+            ":start_date": start_date.to_rfc3339(),
+            ":end_date": end_date.to_rfc3339(),
+            ":now": now.to_rfc3339(),
+        },
+        parse_task_analysis,
+    )?;
+
+    let mut analytics: Vec<TaskAnalysis> = Vec::new();
+
+    for analysis in analytics_iter {
+        analytics.push(analysis?);
     }
 
-    // Helper function to clean up the test database file
-    fn cleanup_test_db(db_path: &str) -> Result<(), String> {
-        if Path::new(db_path).exists() {
-            fs::remove_file(db_path).map_err(|e| e.to_string())?;
-        }
-        Ok(())
+    Ok(analytics)
+}
+
+pub(crate) fn query_report(
+    conn: &Connection,
+    start_date: DateTime<Local>,
+    end_date: DateTime<Local>,
+    now: DateTime<Local>,
+) -> Result<ReportData, StoreError> {
+    let start = start_date.to_rfc3339();
+    let end = end_date.to_rfc3339();
+
+    let completed = query_tasks(
+        conn,
+        GET_COMPLETED_TASKS,
+        named_params! {":start_date": start, ":end_date": end},
+    )?;
+
+    let slipped = query_tasks(
+        conn,
+        GET_SLIPPED_TASKS,
+        named_params! {":start_date": start, ":end_date": end, ":now": now.to_rfc3339()},
+    )?;
+
+    let mut stmt = conn.prepare(GET_FOCUS_BY_CATEGORY)?;
+    let focus_iter = stmt.query_map(
+        named_params! {
+            ":type": PomoType::Work.to_usize(),
+            ":start_date": start,
+            ":end_date": end,
+        },
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)),
+    )?;
+
+    let mut focus: Vec<(String, i64)> = Vec::new();
+    for category in focus_iter {
+        focus.push(category?);
     }
 
-    #[test]
-    fn test_done_task() -> Result<(), String> {
-        let (conn, db_path) = setup_test_db()?;
+    Ok(ReportData {
+        from: start_date,
+        to: end_date,
+        completed,
+        slipped,
+        focus,
+    })
+}
 
-        // Insert a task
-        let now = Local::now();
-        let due_date = now + Duration::days(5);
-        conn.execute(
-            INSERT_TASK,
-            params![0, "Task to Done", due_date.to_rfc3339(), 2, "Category E"],
-        )
-        .map_err(|e| e.to_string())?;
-        let task_id = conn.last_insert_rowid();
+fn query_tasks(
+    conn: &Connection,
+    query: &str,
+    params: &[(&str, &dyn ToSql)],
+) -> Result<Vec<Task>, StoreError> {
+    let mut stmt = conn.prepare(query)?;
 
-        // Mark the task as done
-        done_task(task_id as usize)?;
+    let tasks_iter = stmt.query_map(params, parse_task)?;
 
-        // Verify the task's status was updated
-        let updated_task = get_task_by_id(task_id as usize)?;
-        assert_eq!(updated_task.status, TaskStatus::Done);
+    let mut tasks: Vec<Task> = Vec::new();
+    for task in tasks_iter {
+        tasks.push(task?);
+    }
 
-        cleanup_test_db(&db_path)?; // Clean up the database file
+    Ok(tasks)
+}
+
+pub(crate) fn query_flow_analysis(
+    conn: &Connection,
+    start_date: DateTime<Local>,
+    end_date: DateTime<Local>,
+    now: DateTime<Local>,
+) -> Result<FlowAnalysis, StoreError> {
+    let cycle_times = query_days(
+        conn,
+        GET_TASK_CYCLE_TIMES,
+        named_params! {
+            ":start_date": start_date.to_rfc3339(),
+            ":end_date": end_date.to_rfc3339(),
+        },
+    )?;
+
+    let open_ages = query_days(
+        conn,
+        GET_OPEN_TASK_AGES,
+        named_params! {
+            ":now": now.to_rfc3339(),
+        },
+    )?;
+
+    let range_days = (end_date - start_date).num_seconds() as f64 / 86_400.0;
+
+    Ok(FlowAnalysis::from_samples(
+        cycle_times,
+        open_ages,
+        range_days,
+    ))
+}
+
+/// The records of the table keyed by uuid, every column as the text `sync` writes.
+pub(crate) fn query_sync_records(
+    conn: &Connection,
+    table: SyncTable,
+) -> Result<BTreeMap<String, SyncRecord>, StoreError> {
+    let sql = match table {
+        SyncTable::Tasks => GET_SYNC_TASKS,
+        SyncTable::Sessions => GET_SYNC_POMODOROS,
+    };
+
+    let mut stmt = conn.prepare(sql)?;
+    let records = stmt
+        .query_map([], |row| {
+            let mut fields = Vec::with_capacity(row.as_ref().column_count());
+            for i in 0..row.as_ref().column_count() {
+                fields.push(match row.get::<_, Value>(i)? {
+                    Value::Null => None,
+                    Value::Integer(n) => Some(n.to_string()),
+                    Value::Real(n) => Some(n.to_string()),
+                    Value::Text(s) => Some(s),
+                    Value::Blob(_) => {
+                        return Err(rusqlite::Error::InvalidColumnType(
+                            i,
+                            row.as_ref().column_name(i)?.to_string(),
+                            rusqlite::types::Type::Blob,
+                        ))
+                    }
+                });
+            }
+
+            let uuid = fields.remove(0).unwrap_or_default();
+            let updated_at = fields.remove(0).unwrap_or_default();
+            Ok((
+                uuid.clone(),
+                SyncRecord {
+                    uuid,
+                    updated_at,
+                    fields,
+                },
+            ))
+        })?
+        .collect::<Result<BTreeMap<_, _>, _>>()?;
+
+    Ok(records)
+}
+
+/// Inserts the records or updates the ones with the same uuid, keeping their modification times.
+pub(crate) fn upsert_sync_records(
+    conn: &Connection,
+    table: SyncTable,
+    records: &[SyncRecord],
+) -> Result<(), StoreError> {
+    let tx = conn.unchecked_transaction()?;
+    let mut stmt = tx.prepare(match table {
+        SyncTable::Tasks => UPSERT_SYNC_TASK,
+        SyncTable::Sessions => UPSERT_SYNC_POMODORO,
+    })?;
+
+    for record in records {
+        let values = [Some(&record.uuid), Some(&record.updated_at)]
+            .into_iter()
+            .chain(record.fields.iter().map(Option::as_ref));
+        stmt.execute(params_from_iter(values))?;
+    }
+
+    drop(stmt);
+    tx.commit()?;
+    Ok(())
+}
+
+/// Runs the checks of `tasklog doctor` on the database, `fix` repairs what they find where it can.
+pub(crate) fn diagnose(
+    conn: &Connection,
+    now: DateTime<Local>,
+    fix: bool,
+) -> Result<Vec<Diagnosis>, StoreError> {
+    let mut diagnoses = vec![check_schema(conn, fix)?];
+    // Without the tables there is nothing else to look at.
+    if !has_base_tables(conn)? {
+        return Ok(diagnoses);
+    }
+
+    diagnoses.push(check_integrity(conn)?);
+    // The other checks read columns of the current schema, one left unmigrated has not got them.
+    let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version != MIGRATIONS.len() {
+        return Ok(diagnoses);
+    }
+
+    diagnoses.push(check_orphans(conn, fix)?);
+    diagnoses.push(check_timestamps(conn, fix)?);
+    diagnoses.push(check_stale_sessions(conn, now, fix)?);
+
+    Ok(diagnoses)
+}
+
+/// Whether the database can stand in for the current one, a backup about to be restored.
+pub(crate) fn check_backup(conn: &Connection) -> Result<(), StoreError> {
+    if !has_base_tables(conn)? {
+        return Err(StoreError::Invalid(
+            "The file is not a tasklog database".to_string(),
+        ));
+    }
+
+    let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version > MIGRATIONS.len() {
+        return Err(StoreError::Invalid(format!(
+            "The backup has schema version {}, newer than the {} this build knows",
+            version,
+            MIGRATIONS.len()
+        )));
+    }
+
+    let integrity: String = conn.query_row("PRAGMA quick_check", [], |row| row.get(0))?;
+    if integrity != "ok" {
+        return Err(StoreError::Invalid(format!(
+            "The backup is damaged: {}",
+            integrity
+        )));
+    }
+
+    Ok(())
+}
+
+fn has_base_tables(conn: &Connection) -> Result<bool, StoreError> {
+    let tables: usize = conn.query_row(COUNT_BASE_TABLES, [], |row| row.get(0))?;
+    Ok(tables == 2)
+}
+
+fn check_schema(conn: &Connection, fix: bool) -> Result<Diagnosis, StoreError> {
+    let mut diagnosis = Diagnosis::new("schema");
+    let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+    if !has_base_tables(conn)? {
+        if fix {
+            create_schema(conn)?;
+        }
+        diagnosis.found("the tables have not been created", fix);
+    } else if version < MIGRATIONS.len() {
+        if fix {
+            migrate(conn)?;
+        }
+        diagnosis.found(
+            format!(
+                "version {} of {}, {} migrations pending",
+                version,
+                MIGRATIONS.len(),
+                MIGRATIONS.len() - version
+            ),
+            fix,
+        );
+    } else if version > MIGRATIONS.len() {
+        diagnosis.found(
+            format!(
+                "version {} is newer than the {} this build knows",
+                version,
+                MIGRATIONS.len()
+            ),
+            false,
+        );
+    }
+
+    Ok(diagnosis)
+}
+
+fn check_integrity(conn: &Connection) -> Result<Diagnosis, StoreError> {
+    let mut diagnosis = Diagnosis::new("integrity");
+
+    let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+    for message in stmt.query_map([], |row| row.get::<_, String>(0))? {
+        let message = message?;
+        if message != "ok" {
+            diagnosis.found(message, false);
+        }
+    }
+
+    Ok(diagnosis)
+}
+
+fn check_orphans(conn: &Connection, fix: bool) -> Result<Diagnosis, StoreError> {
+    let mut diagnosis = Diagnosis::new("orphaned rows");
+
+    for (what, count, repair) in ORPHANED_ROWS {
+        let orphans: usize = conn.query_row(count, [], |row| row.get(0))?;
+        if orphans == 0 {
+            continue;
+        }
+        if fix {
+            conn.execute(repair, [])?;
+        }
+        diagnosis.found(format!("{}: {}", what, orphans), fix);
+    }
+
+    Ok(diagnosis)
+}
+
+/// Times SQLite understands are rewritten as RFC 3339, the others are cleared when the column
+/// allows it and left for the user otherwise.
+fn check_timestamps(conn: &Connection, fix: bool) -> Result<Diagnosis, StoreError> {
+    let mut diagnosis = Diagnosis::new("timestamps");
+
+    for (table, column, nullable) in TIME_COLUMNS {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, CAST({column} AS TEXT) FROM {table} WHERE {column} IS NOT NULL ORDER BY id"
+        ))?;
+        let unreadable = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, u64>(0)?, row.get::<_, String>(1)?))
+            })?
+            .filter(|row| match row {
+                Ok((_, value)) => DateTime::parse_from_rfc3339(value).is_err(),
+                Err(_) => true,
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for (id, value) in unreadable {
+            let fixed = fix && repair_timestamp(conn, table, column, *nullable, id)?;
+            diagnosis.found(
+                format!(
+                    "{} #{} has an unreadable {}: '{}'",
+                    table, id, column, value
+                ),
+                fixed,
+            );
+        }
+    }
+
+    Ok(diagnosis)
+}
+
+fn repair_timestamp(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    nullable: bool,
+    id: u64,
+) -> Result<bool, StoreError> {
+    let rewritten = conn.execute(
+        &format!(
+            "UPDATE {table} SET {column} = strftime('%Y-%m-%dT%H:%M:%SZ', {column})
+                WHERE id = ?1 AND julianday({column}) IS NOT NULL"
+        ),
+        [id],
+    )?;
+    if rewritten > 0 {
+        return Ok(true);
+    }
+    if nullable {
+        conn.execute(
+            &format!("UPDATE {table} SET {column} = NULL WHERE id = ?1"),
+            [id],
+        )?;
+        return Ok(true);
+    }
+
+    Ok(false)
+}
+
+fn check_stale_sessions(
+    conn: &Connection,
+    now: DateTime<Local>,
+    fix: bool,
+) -> Result<Diagnosis, StoreError> {
+    let mut diagnosis = Diagnosis::new("stale sessions");
+
+    let mut stmt = conn.prepare(GET_STALE_POMODOROS)?;
+    let stale = stmt
+        .query_map(named_params! {":now": now.to_rfc3339()}, |row| {
+            Ok((
+                row.get::<_, u64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for (id, title, start_time) in stale {
+        if fix {
+            conn.execute(STOP_STALE_POMODORO, named_params! {":id": id})?;
+        }
+        diagnosis.found(
+            format!(
+                "session #{} ({}) started {} is still marked as running",
+                id, title, start_time
+            ),
+            fix,
+        );
+    }
+
+    Ok(diagnosis)
+}
+
+fn query_days(
+    conn: &Connection,
+    query: &str,
+    params: &[(&str, &dyn ToSql)],
+) -> Result<Vec<f64>, StoreError> {
+    let mut stmt = conn.prepare(query)?;
+
+    let days_iter = stmt.query_map(params, |row| row.get::<_, f64>(0))?;
+
+    let mut days: Vec<f64> = Vec::new();
+    for day in days_iter {
+        days.push(day?);
+    }
+
+    Ok(days)
+}
+
+/// Reads the RFC 3339 time in column `i`, a malformed one is a conversion failure of the column.
+fn parse_time(i: usize, s: String) -> Result<DateTime<Local>, rusqlite::Error> {
+    DateTime::parse_from_rfc3339(&s)
+        .map(|d| d.with_timezone(&Local))
+        .map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(i, rusqlite::types::Type::Text, Box::new(e))
+        })
+}
+
+/// Reads a creation or modification time, SQLite writes `YYYY-MM-DD HH:MM:SS` in UTC and tasklog
+/// RFC 3339. Anything else is left out rather than failing the whole record.
+fn parse_stamp(s: Option<String>) -> Option<DateTime<Local>> {
+    let s = s?;
+    DateTime::parse_from_rfc3339(&s)
+        .map(|d| d.with_timezone(&Local))
+        .ok()
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(&s, "%Y-%m-%d %H:%M:%S%.f")
+                .ok()
+                .map(|d| d.and_utc().with_timezone(&Local))
+        })
+}
+
+/// The uuid and times that follow the fields of a record from column `i` on.
+fn parse_meta(row: &rusqlite::Row, i: usize) -> Result<RecordMeta, rusqlite::Error> {
+    Ok(RecordMeta {
+        uuid: row.get::<_, Option<String>>(i)?.unwrap_or_default(),
+        created_at: parse_stamp(row.get(i + 1)?),
+        updated_at: parse_stamp(row.get(i + 2)?),
+    })
+}
+
+fn parse_task(row: &rusqlite::Row) -> Result<Task, rusqlite::Error> {
+    let due_date = parse_time(3, row.get(3)?)?;
+    Ok(Task {
+        id: row.get(0)?,
+        status: TaskStatus::from_usize(row.get::<_, usize>(1)?),
+        title: row.get(2)?,
+        due_date: DueDate {
+            at: due_date,
+            all_day: row.get(6)?,
+        },
+        remind: match (
+            row.get::<_, Option<i64>>(12)?,
+            row.get::<_, Option<String>>(7)?,
+        ) {
+            (Some(before), _) => Some(Remind::Before(Duration::seconds(before))),
+            (None, Some(at)) => Some(Remind::At(parse_time(7, at)?)),
+            (None, None) => None,
+        },
+        priority: Priority::from_usize(row.get::<_, usize>(4)?),
+        category: row.get(5)?,
+        tags: row
+            .get::<_, Option<String>>(8)?
+            .map(|tags| tags.split_whitespace().map(str::to_string).collect())
+            .unwrap_or_default(),
+        meta: parse_meta(row, 9)?,
+    })
+}
+
+fn parse_pomo_task(row: &rusqlite::Row) -> Result<PomoTask, rusqlite::Error> {
+    let start_date = parse_time(3, row.get(3)?)?;
+
+    let elapsed = DurationField::from_i64(row.get::<_, i64>(9)?);
+
+    // Sessions still running have no end yet.
+    let end_date = match row.get::<_, Option<String>>(4)? {
+        Some(end_date) => parse_time(4, end_date)?,
+        None => elapsed.add_date(&start_date),
+    };
+
+    Ok(PomoTask {
+        id: row.get(0)?,
+        pomo_type: PomoType::from_usize(row.get::<_, usize>(1)?),
+        title: row.get(2)?,
+        duration: DurationField::from_i64(row.get::<_, i64>(5)?),
+        category: row.get(7)?,
+        task_id: row.get(8)?,
+        elapsed,
+        status: PomoStatus::from_usize(row.get::<_, usize>(6)?),
+        start_time: start_date,
+        end_time: end_date,
+        meta: parse_meta(row, 10)?,
+    })
+}
+
+fn parse_time_entry(row: &rusqlite::Row) -> Result<TimeEntry, rusqlite::Error> {
+    Ok(TimeEntry {
+        id: row.get(0)?,
+        task_id: row.get(1)?,
+        title: row.get(2)?,
+        category: row.get(3)?,
+        start_time: parse_time(4, row.get(4)?)?,
+        end_time: match row.get::<_, Option<String>>(5)? {
+            Some(end_time) => Some(parse_time(5, end_time)?),
+            None => None,
+        },
+    })
+}
+
+fn parse_task_analysis(row: &rusqlite::Row) -> Result<TaskAnalysis, rusqlite::Error> {
+    Ok(TaskAnalysis {
+        bucket: row.get::<_, String>(0)?,
+        total: row.get(1)?,
+        done: row.get(2)?,
+        open: row.get(3)?,
+        overdue: row.get(4)?,
+        done_late: row.get(5)?,
+        completion_rate: row.get(6)?,
+        on_time_rate: row.get(7)?,
+        avg_lead_time: row.get(8)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::{EditArgs, TaskRef, TaskStatus};
+
+    use super::*;
+    use chrono::Duration;
+    use std::error::Error;
+    use std::fs;
+    use std::path::Path;
+
+    // Helper function to create a temporary database for testing
+    fn setup_test_db() -> Result<(Connection, String), String> {
+        // Create a temporary file path
+        let db_path = ":memory:";
+
+        // Ensure the database file does not exist
+        if Path::new(db_path).exists() {
+            fs::remove_file(db_path).map_err(|e| e.to_string())?;
+        }
+
+        // Initialize the database
+        let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+        conn.execute(CREATE_TASKS_TABLE, [])
+            .map_err(|e| e.to_string())?;
+        conn.execute(CREATE_POMODORO_TABLE, [])
+            .map_err(|e| e.to_string())?;
+        migrate(&conn).map_err(|e| e.to_string())?;
+
+        Ok((conn, db_path.to_string()))
+    }
+
+    // Helper function to clean up the test database file
+    fn cleanup_test_db(db_path: &str) -> Result<(), Box<dyn Error>> {
+        if Path::new(db_path).exists() {
+            fs::remove_file(db_path).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_done_task() -> Result<(), Box<dyn Error>> {
+        let (conn, db_path) = setup_test_db()?;
+
+        // Insert a task
+        let now = Local::now();
+        let due_date = now + Duration::days(5);
+        conn.execute(
+            INSERT_TASK,
+            params![
+                0,
+                "Task to Done",
+                due_date.to_rfc3339(),
+                2,
+                "Category E",
+                false,
+                Option::<String>::None,
+                Option::<i64>::None,
+                Option::<String>::None,
+                "2026-06-15 12:00:00",
+                Option::<String>::None
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        let task_id = conn.last_insert_rowid();
+
+        // Mark the task as done
+        mark_task_done(&conn, task_id as usize)?;
+
+        // Verify the task's status was updated
+        let updated_task = query_task_by_id(&conn, task_id as usize)?;
+        assert_eq!(updated_task.status, TaskStatus::Done);
+
+        cleanup_test_db(&db_path)?; // Clean up the database file
+        Ok(())
+    }
+
+    #[test]
+    fn test_tasks_are_found_by_uuid_prefix() -> Result<(), Box<dyn Error>> {
+        let (conn, db_path) = setup_test_db()?;
+
+        let mut first = Task {
+            title: "First".to_string(),
+            ..Default::default()
+        };
+        let mut second = Task {
+            title: "Second".to_string(),
+            ..Default::default()
+        };
+        save_task(&conn, &mut first)?;
+        save_task(&conn, &mut second)?;
+
+        let stored = query_task_by_id(&conn, first.id as usize)?;
+        assert_eq!(stored.meta.uuid.len(), 36);
+        assert_eq!(stored.meta, first.meta);
+        assert_eq!(
+            query_task_by_uuid_prefix(&conn, &first.meta.uuid[..8])?.id,
+            first.id
+        );
+        assert_eq!(
+            query_task_by_uuid_prefix(&conn, &second.meta.uuid.to_uppercase())?.id,
+            second.id
+        );
+
+        // Rows inserted without one get a version 4 uuid from the trigger.
+        conn.execute("INSERT INTO tasks (title) VALUES ('Third')", [])
+            .map_err(|e| e.to_string())?;
+        let third: String = conn
+            .query_row("SELECT uuid FROM tasks WHERE id = 3", [], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        assert_eq!(third.len(), 36);
+        assert_eq!(&third[14..15], "4");
+        assert!("89ab".contains(&third[19..20]));
+        conn.execute("DELETE FROM tasks WHERE id = 3", [])
+            .map_err(|e| e.to_string())?;
+
+        conn.execute_batch(
+            "UPDATE tasks SET uuid = 'abcdef01-0000-4000-8000-000000000001' WHERE id = 1;
+             UPDATE tasks SET uuid = 'abcdef02-0000-4000-8000-000000000002' WHERE id = 2;",
+        )
+        .map_err(|e| e.to_string())?;
+        assert_eq!(query_uuid_prefix_len(&conn)?, 8);
+        assert!(matches!(
+            query_task_by_uuid_prefix(&conn, "abcdef"),
+            Err(StoreError::Invalid(_))
+        ));
+        assert!(matches!(
+            query_task_by_uuid_prefix(&conn, "0123"),
+            Err(StoreError::UnknownUuid { record: "task", .. })
+        ));
+
+        cleanup_test_db(&db_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_changes_move_updated_at() -> Result<(), Box<dyn Error>> {
+        let (conn, db_path) = setup_test_db()?;
+
+        let mut task = Task {
+            title: "Write the changelog".to_string(),
+            ..Default::default()
+        };
+        save_task(&conn, &mut task)?;
+        conn.execute(
+            "UPDATE tasks SET updated_at = '2026-01-01T00:00:00.000Z' WHERE id = ?1",
+            params![task.id],
+        )
+        .map_err(|e| e.to_string())?;
+        let before = query_task_by_id(&conn, task.id as usize)?;
+        assert_eq!(
+            before.meta.updated_at,
+            parse_stamp(Some("2026-01-01T00:00:00Z".into()))
+        );
+
+        update_task(
+            &conn,
+            &Task {
+                title: "Write the release notes".to_string(),
+                ..before.clone()
+            },
+        )?;
+        let after = query_task_by_id(&conn, task.id as usize)?;
+        assert_eq!(after.title, "Write the release notes");
+        assert_eq!(after.meta.uuid, before.meta.uuid);
+        assert_eq!(after.meta.created_at, before.meta.created_at);
+        assert!(after.meta.updated_at > before.meta.updated_at);
+
+        cleanup_test_db(&db_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupt_rows_are_errors() -> Result<(), Box<dyn Error>> {
+        let (conn, db_path) = setup_test_db()?;
+
+        conn.execute(
+            INSERT_TASK,
+            params![
+                0,
+                "Task with a broken date",
+                "next tuesday",
+                2,
+                "Category E",
+                false,
+                Option::<String>::None,
+                Option::<i64>::None,
+                Option::<String>::None,
+                "2026-06-15 12:00:00",
+                Option::<String>::None
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+
+        let err = query_task_by_id(&conn, conn.last_insert_rowid() as usize).unwrap_err();
+        assert!(matches!(err, StoreError::Corrupt(_)));
+
+        conn.execute(
+            "INSERT INTO tasks (title, due_date, remind_at) VALUES (?1, ?2, ?3)",
+            params![
+                "Task with a broken reminder",
+                at(NOW).to_rfc3339(),
+                "an hour before"
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+
+        let err = query_task_by_id(&conn, conn.last_insert_rowid() as usize).unwrap_err();
+        assert!(matches!(err, StoreError::Corrupt(_)));
+
+        cleanup_test_db(&db_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_done_task_records_completion() -> Result<(), Box<dyn Error>> {
+        let (conn, db_path) = setup_test_db()?;
+
+        let due_date = Local::now() + Duration::days(1);
+        conn.execute(
+            INSERT_TASK,
+            params![
+                0,
+                "Task to Done",
+                due_date.to_rfc3339(),
+                2,
+                "Category E",
+                false,
+                Option::<String>::None,
+                Option::<i64>::None,
+                Option::<String>::None,
+                "2026-06-15 12:00:00",
+                Option::<String>::None
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        let task_id = conn.last_insert_rowid();
+
+        mark_task_done(&conn, task_id as usize)?;
+
+        let completed: Option<String> = conn
+            .query_row(
+                "SELECT completed_at FROM tasks WHERE id = ?1",
+                params![task_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        assert!(completed.is_some());
+
+        cleanup_test_db(&db_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_diagnose_repairs_what_it_can() -> Result<(), Box<dyn Error>> {
+        let (conn, db_path) = setup_test_db()?;
+        let now = DateTime::parse_from_rfc3339("2026-06-15T12:00:00+00:00")
+            .map_err(|e| e.to_string())?
+            .with_timezone(&Local);
+
+        // Other tools writing the file may not enforce the foreign keys.
+        conn.execute_batch(
+            r#"
+            PRAGMA foreign_keys = OFF;
+            INSERT INTO tasks (title, due_date) VALUES ('Readable', '2026-06-15T10:00:00+00:00');
+            INSERT INTO tasks (title, due_date) VALUES ('Written by SQLite', '2026-06-15 10:00:00');
+            INSERT INTO tasks (title, due_date, remind_at)
+                VALUES ('Lost', 'next tuesday', 'soon');
+            INSERT INTO pomodoro (type, title, start_time, duration, elapsed, status, task_id)
+                VALUES (1, 'Crashed', '2026-06-15T09:00:00+00:00', 1500, 600, 0, 42);
+            INSERT INTO pomodoro_interruptions (pomodoro_id, note) VALUES (42, 'Phone');
+            PRAGMA foreign_keys = ON;
+            "#,
+        )
+        .map_err(|e| e.to_string())?;
+
+        let found = |diagnoses: &[Diagnosis], check: &str| -> Vec<bool> {
+            diagnoses
+                .iter()
+                .find(|d| d.check == check)
+                .map(|d| d.findings.iter().map(|f| f.fixed).collect())
+                .unwrap_or_default()
+        };
+
+        let diagnoses = diagnose(&conn, now, false)?;
+        assert!(found(&diagnoses, "schema").is_empty());
+        assert!(found(&diagnoses, "integrity").is_empty());
+        assert_eq!(found(&diagnoses, "orphaned rows"), vec![false, false]);
+        assert_eq!(found(&diagnoses, "timestamps"), vec![false, false, false]);
+        assert_eq!(found(&diagnoses, "stale sessions"), vec![false]);
+
+        let diagnoses = diagnose(&conn, now, true)?;
+        assert_eq!(found(&diagnoses, "orphaned rows"), vec![true, true]);
+        // A due date nobody can read cannot be cleared either.
+        assert_eq!(found(&diagnoses, "timestamps"), vec![true, false, true]);
+        assert_eq!(found(&diagnoses, "stale sessions"), vec![true]);
+
+        let diagnoses = diagnose(&conn, now, false)?;
+        assert_eq!(found(&diagnoses, "timestamps"), vec![false]);
+        for check in ["orphaned rows", "stale sessions"] {
+            assert!(found(&diagnoses, check).is_empty());
+        }
+
+        let session = &get_pomodoro(&conn, 10)?[0];
+        assert_eq!(session.status, PomoStatus::Paused);
+        assert_eq!(session.task_id, None);
+        assert_eq!(session.end_time, session.start_time + Duration::minutes(10));
+
+        cleanup_test_db(&db_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_diagnose_creates_missing_tables() -> Result<(), Box<dyn Error>> {
+        let conn = Connection::open_in_memory().map_err(|e| e.to_string())?;
+
+        let diagnoses = diagnose(&conn, Local::now(), false)?;
+        assert_eq!(diagnoses.len(), 1);
+        assert!(!diagnoses[0].findings[0].fixed);
+
+        let diagnoses = diagnose(&conn, Local::now(), true)?;
+        assert!(diagnoses[0].findings[0].fixed);
+        assert!(diagnose(&conn, Local::now(), false)?
+            .iter()
+            .all(|d| d.findings.is_empty()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_diagnose_pending_migrations() -> Result<(), Box<dyn Error>> {
+        let conn = Connection::open_in_memory().map_err(|e| e.to_string())?;
+        conn.execute(CREATE_TASKS_TABLE, [])
+            .map_err(|e| e.to_string())?;
+        conn.execute(CREATE_POMODORO_TABLE, [])
+            .map_err(|e| e.to_string())?;
+
+        let diagnoses = diagnose(&conn, Local::now(), false)?;
+        assert_eq!(diagnoses.len(), 2);
+        assert!(diagnoses[0].findings[0]
+            .message
+            .contains("migrations pending"));
+        assert!(!diagnoses[0].findings[0].fixed);
+
+        let diagnoses = diagnose(&conn, Local::now(), true)?;
+        assert!(diagnoses[0].findings[0].fixed);
+        assert_eq!(diagnoses.len(), 5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrate_is_idempotent() -> Result<(), Box<dyn Error>> {
+        let (conn, db_path) = setup_test_db()?;
+
+        migrate(&conn)?;
+        let version: usize = conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        assert_eq!(version, MIGRATIONS.len());
+
+        cleanup_test_db(&db_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_migration_backfills_elapsed_time() -> Result<(), Box<dyn Error>> {
+        let conn = Connection::open_in_memory().map_err(|e| e.to_string())?;
+
+        // Build the schema as it was before elapsed time was stored.
+        let version = MIGRATIONS
+            .iter()
+            .position(|m| m.contains("ADD COLUMN elapsed"))
+            .unwrap();
+        conn.execute(CREATE_TASKS_TABLE, [])
+            .map_err(|e| e.to_string())?;
+        conn.execute(CREATE_POMODORO_TABLE, [])
+            .map_err(|e| e.to_string())?;
+        for migration in &MIGRATIONS[..version] {
+            conn.execute_batch(migration).map_err(|e| e.to_string())?;
+        }
+        conn.execute_batch(&format!("PRAGMA user_version = {};", version))
+            .map_err(|e| e.to_string())?;
+
+        for (title, end, duration) in [
+            ("quit early", Some("2026-06-15 09:03"), 1500),
+            ("ran over", Some("2026-06-15 09:40"), 1500),
+            ("running", None, 1500),
+        ] {
+            conn.execute(
+                "INSERT INTO pomodoro (type, title, start_time, end_time, duration) VALUES (1, ?1, ?2, ?3, ?4)",
+                params![
+                    title,
+                    at("2026-06-15 09:00").to_rfc3339(),
+                    end.map(|e| at(e).to_rfc3339()),
+                    duration
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+
+        migrate(&conn)?;
+
+        let elapsed: Vec<i64> = conn
+            .prepare("SELECT elapsed FROM pomodoro ORDER BY id")
+            .and_then(|mut stmt| stmt.query_map([], |row| row.get(0))?.collect())
+            .map_err(|e| e.to_string())?;
+        assert_eq!(elapsed, vec![180, 1500, 0]);
+
+        Ok(())
+    }
+
+    struct TaskFixture {
+        category: &'static str,
+        priority: Priority,
+        created: &'static str,
+        due: &'static str,
+        completed: Option<&'static str>,
+    }
+
+    // All fixture times are local, analyses run against this fixed clock.
+    const NOW: &str = "2026-06-15 12:00";
+
+    fn at(time: &str) -> DateTime<Local> {
+        chrono::NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M")
+            .unwrap()
+            .and_local_timezone(Local)
+            .unwrap()
+    }
+
+    fn utc_timestamp(time: &str) -> String {
+        at(time).to_utc().format("%Y-%m-%d %H:%M:%S").to_string()
+    }
+
+    fn insert_fixtures(conn: &Connection, fixtures: &[TaskFixture]) -> Result<(), Box<dyn Error>> {
+        for f in fixtures {
+            conn.execute(
+                "INSERT INTO tasks (status, title, due_date, priority, category, created_at, completed_at)
+                    VALUES (?1, 'fixture', ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    TaskStatus::from_bool(f.completed.is_some()).to_usize(),
+                    at(f.due).to_rfc3339(),
+                    f.priority.to_usize(),
+                    f.category,
+                    utc_timestamp(f.created),
+                    f.completed.map(utc_timestamp),
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn sprint_fixtures() -> Vec<TaskFixture> {
+        vec![
+            // done a day early, 1 day of cycle time
+            TaskFixture {
+                category: "work",
+                priority: Priority::High,
+                created: "2026-06-08 09:00",
+                due: "2026-06-10 18:00",
+                completed: Some("2026-06-09 09:00"),
+            },
+            // done 6 hours late, 3.5 days of cycle time
+            TaskFixture {
+                category: "work",
+                priority: Priority::Low,
+                created: "2026-06-08 09:00",
+                due: "2026-06-11 15:00",
+                completed: Some("2026-06-11 21:00"),
+            },
+            // done on the due day before the deadline, 6 days of cycle time
+            TaskFixture {
+                category: "home",
+                priority: Priority::Medium,
+                created: "2026-06-03 10:00",
+                due: "2026-06-09 18:00",
+                completed: Some("2026-06-09 10:00"),
+            },
+            // still open and overdue, 10 days old
+            TaskFixture {
+                category: "home",
+                priority: Priority::Urgent,
+                created: "2026-06-05 12:00",
+                due: "2026-06-12 12:00",
+                completed: None,
+            },
+            // still open, due next week, 2 days old
+            TaskFixture {
+                category: "work",
+                priority: Priority::High,
+                created: "2026-06-13 12:00",
+                due: "2026-06-17 12:00",
+                completed: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_analysis_grouped_by_category() -> Result<(), Box<dyn Error>> {
+        let (conn, db_path) = setup_test_db()?;
+        insert_fixtures(&conn, &sprint_fixtures())?;
+
+        let analysis = query_analysis_at(
+            &conn,
+            GroupBy::Category,
+            Local,
+            at("2026-06-08 00:00"),
+            at("2026-06-22 00:00"),
+            at(NOW),
+        )?;
+        let buckets: Vec<&str> = analysis.iter().map(|a| a.bucket.as_str()).collect();
+        assert_eq!(buckets, vec!["work", "home"]);
+
+        let work = &analysis[0];
+        assert_eq!(work.total, 3);
+        assert_eq!(work.done, 2);
+        assert_eq!(work.open, 1);
+        assert_eq!(work.overdue, 0);
+        assert_eq!(work.done_late, 1);
+        assert!((work.completion_rate - 66.67).abs() < 0.01);
+        assert_eq!(work.on_time_rate, Some(50.0));
+        assert!((work.avg_lead_time.unwrap() - 2.25).abs() < 0.001);
+
+        let home = &analysis[1];
+        assert_eq!(home.total, 2);
+        assert_eq!(home.done, 1);
+        assert_eq!(home.open, 1);
+        assert_eq!(home.overdue, 1);
+        assert_eq!(home.done_late, 0);
+        assert_eq!(home.completion_rate, 50.0);
+        assert_eq!(home.on_time_rate, Some(100.0));
+        assert!((home.avg_lead_time.unwrap() - 6.0).abs() < 0.001);
+
+        cleanup_test_db(&db_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_analysis_grouped_by_day_and_week() -> Result<(), Box<dyn Error>> {
+        let (conn, db_path) = setup_test_db()?;
+        insert_fixtures(&conn, &sprint_fixtures())?;
+
+        let daily = query_analysis_at(
+            &conn,
+            GroupBy::Day,
+            Local,
+            at("2026-06-09 00:00"),
+            at("2026-06-12 00:00"),
+            at(NOW),
+        )?;
+        let days: Vec<(&str, u64)> = daily.iter().map(|a| (a.bucket.as_str(), a.total)).collect();
+        assert_eq!(
+            days,
+            vec![("2026-06-11", 1), ("2026-06-10", 1), ("2026-06-09", 1)]
+        );
+
+        let weekly = query_analysis_at(
+            &conn,
+            GroupBy::Week,
+            Local,
+            at("2026-06-01 00:00"),
+            at("2026-06-30 00:00"),
+            at(NOW),
+        )?;
+        let weeks: Vec<(&str, u64)> = weekly
+            .iter()
+            .map(|a| (a.bucket.as_str(), a.total))
+            .collect();
+        // ISO weeks, the one of Monday June 8th is the 24th.
+        assert_eq!(weeks, vec![("2026-W25", 1), ("2026-W24", 4)]);
+
+        cleanup_test_db(&db_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_analysis_buckets_in_the_home_time_zone() -> Result<(), Box<dyn Error>> {
+        let (conn, db_path) = setup_test_db()?;
+        for due in [
+            "2026-06-15T02:00:00+00:00",
+            "2026-06-15T14:00:00+00:00",
+            "2027-01-01T12:00:00+00:00",
+        ] {
+            conn.execute(
+                "INSERT INTO tasks (title, due_date) VALUES ('Due', ?1)",
+                [due],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+
+        let buckets = |group_by| -> Result<Vec<String>, Box<dyn Error>> {
+            let analysis = query_analysis_at(
+                &conn,
+                group_by,
+                chrono_tz::America::New_York,
+                at("2026-01-01 00:00"),
+                at("2028-01-01 00:00"),
+                at(NOW),
+            )?;
+            Ok(analysis.into_iter().map(|a| a.bucket).collect())
+        };
+
+        // 02:00 UTC is still Sunday June 14th in New York, the last day of ISO week 24.
+        assert_eq!(
+            buckets(GroupBy::Day)?,
+            vec!["2027-01-01", "2026-06-15", "2026-06-14"]
+        );
+        // January 1st 2027 is a Friday, in the last ISO week of 2026.
+        assert_eq!(
+            buckets(GroupBy::Week)?,
+            vec!["2026-W53", "2026-W25", "2026-W24"]
+        );
+
+        cleanup_test_db(&db_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_analysis_grouped_by_priority() -> Result<(), Box<dyn Error>> {
+        let (conn, db_path) = setup_test_db()?;
+        insert_fixtures(&conn, &sprint_fixtures())?;
+
+        let analysis = query_analysis_at(
+            &conn,
+            GroupBy::Priority,
+            Local,
+            at("2026-06-01 00:00"),
+            at("2026-06-30 00:00"),
+            at(NOW),
+        )?;
+        let buckets: Vec<(&str, u64)> = analysis
+            .iter()
+            .map(|a| (a.bucket.as_str(), a.total))
+            .collect();
+        assert_eq!(
+            buckets,
+            vec![("Urgent", 1), ("High", 2), ("Medium", 1), ("Low", 1)]
+        );
+
+        cleanup_test_db(&db_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_flow_analysis() -> Result<(), Box<dyn Error>> {
+        let (conn, db_path) = setup_test_db()?;
+        insert_fixtures(&conn, &sprint_fixtures())?;
+
+        let flow = query_flow_analysis(
+            &conn,
+            at("2026-06-08 00:00"),
+            at("2026-06-15 00:00"),
+            at(NOW),
+        )?;
+
+        assert_eq!(flow.completed, 3);
+        assert!((flow.throughput - 3.0).abs() < 0.001);
+        assert!((flow.cycle_time_p50.unwrap() - 3.5).abs() < 0.001);
+        assert!((flow.cycle_time_p90.unwrap() - 6.0).abs() < 0.001);
+        assert_eq!(flow.open, 2);
+        assert!((flow.open_age_avg.unwrap() - 6.0).abs() < 0.001);
+        assert!((flow.open_age_max.unwrap() - 10.0).abs() < 0.001);
+
+        cleanup_test_db(&db_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_flow_analysis_empty_range() -> Result<(), Box<dyn Error>> {
+        let (conn, db_path) = setup_test_db()?;
+
+        let flow = query_flow_analysis(
+            &conn,
+            at("2026-06-08 00:00"),
+            at("2026-06-15 00:00"),
+            at(NOW),
+        )?;
+        assert_eq!(flow, FlowAnalysis::default());
+
+        cleanup_test_db(&db_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_report_data() -> Result<(), Box<dyn Error>> {
+        let (conn, db_path) = setup_test_db()?;
+        insert_fixtures(&conn, &sprint_fixtures())?;
+
+        for (category, start, end, focused_minutes) in [
+            ("deep", "2026-06-09 09:00", "2026-06-09 09:25", 25),
+            // paused for ten minutes
+            ("deep", "2026-06-10 09:00", "2026-06-10 09:50", 40),
+            ("mail", "2026-06-10 14:00", "2026-06-10 14:10", 10),
+            // outside of the week
+            ("deep", "2026-06-01 09:00", "2026-06-01 10:00", 60),
+        ] {
+            conn.execute(
+                "INSERT INTO pomodoro (type, title, start_time, end_time, status, category, elapsed)
+                    VALUES (?1, 'focus', ?2, ?3, 2, ?4, ?5)",
+                params![
+                    PomoType::Work.to_usize(),
+                    at(start).to_rfc3339(),
+                    at(end).to_rfc3339(),
+                    category,
+                    focused_minutes * 60
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+
+        let report = query_report(
+            &conn,
+            at("2026-06-08 00:00"),
+            at("2026-06-15 00:00"),
+            at(NOW),
+        )?;
+
+        assert_eq!(report.completed.len(), 3);
+        assert!(report
+            .completed
+            .iter()
+            .all(|t| t.status == TaskStatus::Done));
+
+        let slipped: Vec<Priority> = report.slipped.iter().map(|t| t.priority).collect();
+        assert_eq!(slipped, vec![Priority::Low, Priority::Urgent]);
+
+        assert_eq!(
+            report.focus,
+            vec![("deep".to_string(), 65 * 60), ("mail".to_string(), 10 * 60)]
+        );
+
+        cleanup_test_db(&db_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_due_reminders_fire_once() -> Result<(), Box<dyn Error>> {
+        let (conn, db_path) = setup_test_db()?;
+
+        for (title, status, remind_at) in [
+            ("due", TaskStatus::Open, Some("2026-06-15 11:00")),
+            ("later", TaskStatus::Open, Some("2026-06-15 13:00")),
+            ("done", TaskStatus::Done, Some("2026-06-15 10:00")),
+            ("silent", TaskStatus::Open, None),
+        ] {
+            conn.execute(
+                "INSERT INTO tasks (status, title, due_date, remind_at) VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    status.to_usize(),
+                    title,
+                    at("2026-06-16 12:00").to_rfc3339(),
+                    remind_at.map(|r| at(r).to_rfc3339())
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+
+        let due_reminders = |now: &str| {
+            query_tasks(
+                &conn,
+                GET_DUE_REMINDERS,
+                named_params! {":now": at(now).to_rfc3339()},
+            )
+        };
+
+        let due = due_reminders(NOW)?;
+        let titles: Vec<&str> = due.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(titles, vec!["due"]);
+        assert_eq!(due[0].remind_at(), Some(at("2026-06-15 11:00")));
+
+        set_reminded(&conn, due[0].id, at(NOW))?;
+        assert!(due_reminders(NOW)?.is_empty());
+
+        let titles: Vec<String> = due_reminders("2026-06-15 14:00")?
+            .into_iter()
+            .map(|t| t.title)
+            .collect();
+        assert_eq!(titles, vec!["later"]);
+
+        cleanup_test_db(&db_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_relative_reminder_follows_the_due_date() -> Result<(), Box<dyn Error>> {
+        let (conn, db_path) = setup_test_db()?;
+
+        let mut task = Task {
+            title: "Send the invoices".to_string(),
+            due_date: DueDate::timed(at("2026-06-16 12:00")),
+            remind: Some(Remind::Before(Duration::hours(1))),
+            ..Default::default()
+        };
+        save_task(&conn, &mut task)?;
+        let saved = query_task_by_id(&conn, task.id as usize)?;
+        assert_eq!(saved.remind, Some(Remind::Before(Duration::hours(1))));
+        assert_eq!(saved.remind_at(), Some(at("2026-06-16 11:00")));
+
+        for due in ["2026-06-16 09:00", "2026-06-18 12:00"] {
+            let edit = EditArgs {
+                id: TaskRef::Id(task.id),
+                title: None,
+                due_date: Some(DueDate::timed(at(due))),
+                priority: None,
+                category: None,
+                remind: None,
+            };
+            update_task(
+                &conn,
+                &edit.apply(&query_task_by_id(&conn, task.id as usize)?),
+            )?;
+
+            let edited = query_task_by_id(&conn, task.id as usize)?;
+            assert_eq!(edited.remind, Some(Remind::Before(Duration::hours(1))));
+            assert_eq!(edited.remind_at(), Some(at(due) - Duration::hours(1)));
+        }
+
+        // A reminder at a fixed time stays where it is.
+        let mut fixed = query_task_by_id(&conn, task.id as usize)?;
+        fixed.remind = Some(Remind::At(at("2026-06-17 08:00")));
+        update_task(&conn, &fixed)?;
+        let edited = query_task_by_id(&conn, task.id as usize)?;
+        assert_eq!(edited.remind, Some(Remind::At(at("2026-06-17 08:00"))));
+
+        cleanup_test_db(&db_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_prompt_status() -> Result<(), Box<dyn Error>> {
+        let (conn, db_path) = setup_test_db()?;
+        let (open, overdue, running) = query_prompt_status(&conn, at(NOW))?;
+        assert_eq!((open, overdue), (0, 0));
+        assert!(running.is_none());
+
+        for (title, status, due) in [
+            ("overdue", TaskStatus::Open, "2026-06-14 09:00"),
+            ("upcoming", TaskStatus::Open, "2026-06-16 09:00"),
+            ("done late", TaskStatus::Done, "2026-06-14 09:00"),
+        ] {
+            conn.execute(
+                "INSERT INTO tasks (status, title, due_date) VALUES (?1, ?2, ?3)",
+                params![status.to_usize(), title, at(due).to_rfc3339()],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+
+        let (open, overdue, running) = query_prompt_status(&conn, at(NOW))?;
+        assert_eq!((open, overdue), (2, 1));
+        assert!(running.is_none());
+
+        for (title, status, start) in [
+            ("Finished", PomoStatus::Finished, "2026-06-15 11:00"),
+            ("Running", PomoStatus::Running, "2026-06-15 11:50"),
+        ] {
+            conn.execute(
+                "INSERT INTO pomodoro (title, status, start_time) VALUES (?1, ?2, ?3)",
+                params![title, status.to_usize(), at(start).to_rfc3339()],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        let (open, overdue, running) = query_prompt_status(&conn, at(NOW))?;
+        assert_eq!((open, overdue), (2, 1));
+        assert_eq!(running.map(|s| s.title).as_deref(), Some("Running"));
+
+        let sql = GET_PROMPT_STATUS
+            .replace("{{running}}", GET_RUNNING_POMODORO)
+            .replace("{{counts}}", GET_OPEN_TASK_COUNTS);
+        let mut stmt = conn.prepare(&format!("EXPLAIN QUERY PLAN {}", sql))?;
+        let plan = stmt
+            .query_map(named_params! {":now": at(NOW).to_rfc3339()}, |row| {
+                row.get::<_, String>(3)
+            })?
+            .collect::<Result<Vec<_>, _>>()?
+            .join("\n");
+        assert!(plan.contains("COVERING INDEX idx_tasks_status_due_date"));
+        assert!(plan.contains("INDEX idx_pomodoro_status_start_time"));
+
+        cleanup_test_db(&db_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_interruptions_are_logged_with_the_session() -> Result<(), Box<dyn Error>> {
+        let (conn, db_path) = setup_test_db()?;
+
+        conn.execute(
+            "INSERT INTO pomodoro (type, title, start_time) VALUES (1, 'Write', ?1)",
+            params![at(NOW).to_rfc3339()],
+        )
+        .map_err(|e| e.to_string())?;
+        let pomodoro_id = conn.last_insert_rowid() as u64;
+
+        insert_interruption(
+            &conn,
+            pomodoro_id,
+            InterruptionKind::External,
+            "Slack ping",
+            at("2026-06-15 12:05"),
+        )?;
+        insert_interruption(
+            &conn,
+            pomodoro_id,
+            InterruptionKind::Internal,
+            "coffee",
+            at("2026-06-15 12:12"),
+        )?;
+
+        let notes: Vec<(String, String)> = conn
+            .prepare(
+                "SELECT note, created_at FROM pomodoro_interruptions WHERE pomodoro_id = ?1 ORDER BY id",
+            )
+            .and_then(|mut stmt| {
+                stmt.query_map(params![pomodoro_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect()
+            })
+            .map_err(|e| e.to_string())?;
+
+        assert_eq!(
+            notes,
+            vec![
+                (
+                    "Slack ping".to_string(),
+                    at("2026-06-15 12:05").to_rfc3339()
+                ),
+                ("coffee".to_string(), at("2026-06-15 12:12").to_rfc3339()),
+            ]
+        );
+
+        cleanup_test_db(&db_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_interruption_analysis() -> Result<(), Box<dyn Error>> {
+        let (conn, db_path) = setup_test_db()?;
+
+        let mut ids = Vec::new();
+        for (pomo_type, title, start, category, status) in [
+            (PomoType::Work, "Spec", "2026-06-15 09:00", Some("work"), 2),
+            (PomoType::Rest, "Spec", "2026-06-15 09:25", Some("work"), 2),
+            (PomoType::Work, "Read", "2026-06-15 14:00", None, 2),
+            (PomoType::Work, "Older", "2026-06-01 14:00", None, 2),
+            (PomoType::Work, "Now", "2026-06-15 11:50", None, 0),
+        ] {
+            conn.execute(
+                "INSERT INTO pomodoro (type, title, start_time, category, status) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![pomo_type.to_usize(), title, at(start).to_rfc3339(), category, status],
+            )
+            .map_err(|e| e.to_string())?;
+            ids.push(conn.last_insert_rowid() as u64);
+        }
+
+        for (session, kind, created_at) in [
+            (0, InterruptionKind::External, "2026-06-15 09:05"),
+            (0, InterruptionKind::Internal, "2026-06-15 09:10"),
+            (0, InterruptionKind::External, "2026-06-15 09:20"),
+            (2, InterruptionKind::Internal, "2026-06-15 14:10"),
+            (3, InterruptionKind::Internal, "2026-06-01 14:10"),
+        ] {
+            insert_interruption(&conn, ids[session], kind, "note", at(created_at))?;
+        }
+
+        let (sessions, hours) =
+            query_interruption_analysis(&conn, at("2026-06-15 00:00"), at("2026-06-16 00:00"))?;
+
+        let counts: Vec<(&str, u64, u64)> = sessions
+            .iter()
+            .map(|s| (s.title.as_str(), s.internal, s.external))
+            .collect();
+        assert_eq!(counts, vec![("Spec", 1, 2), ("Now", 0, 0), ("Read", 1, 0)]);
+        assert_eq!(sessions[0].start_time, at("2026-06-15 09:00"));
+
+        assert_eq!(
+            hours,
+            vec![
+                HourInterruptions {
+                    hour: "09".to_string(),
+                    internal: 1,
+                    external: 2
+                },
+                HourInterruptions {
+                    hour: "14".to_string(),
+                    internal: 1,
+                    external: 0
+                },
+            ]
+        );
+
+        // The session started at 11:50 runs until 12:15.
+        assert_eq!(
+            query_running_pomodoro(&conn, at(NOW))?.map(|session| session.id),
+            Some(ids[4])
+        );
+        assert!(query_running_pomodoro(&conn, at("2026-06-15 12:30"))?.is_none());
+
+        cleanup_test_db(&db_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_goal_progress_counts_the_tables() -> Result<(), Box<dyn Error>> {
+        let (conn, db_path) = setup_test_db()?;
+
+        for (metric, period, target) in [
+            (GoalMetric::Pomodoros, GoalPeriod::Day, 2),
+            (GoalMetric::Focus, GoalPeriod::Week, 45 * 60),
+            (GoalMetric::Tasks, GoalPeriod::Day, 1),
+        ] {
+            let mut goal = Goal {
+                id: 0,
+                metric,
+                period,
+                target,
+            };
+            insert_goal(&conn, &mut goal)?;
+            assert!(goal.id > 0);
+        }
+
+        for (pomo_type, start, status, elapsed_minutes) in [
+            (PomoType::Work, "2026-06-14 09:00", 2, 25),
+            (PomoType::Work, "2026-06-14 10:00", 2, 25),
+            (PomoType::Rest, "2026-06-14 10:25", 2, 5),
+            (PomoType::Work, "2026-06-15 09:00", 2, 25),
+            // quit halfway, focused but not a pomodoro
+            (PomoType::Work, "2026-06-15 10:00", 1, 10),
+        ] {
+            conn.execute(
+                "INSERT INTO pomodoro (type, title, start_time, status, elapsed) VALUES (?1, 'focus', ?2, ?3, ?4)",
+                params![pomo_type.to_usize(), at(start).to_rfc3339(), status, elapsed_minutes * 60],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        insert_fixtures(&conn, &sprint_fixtures())?;
+
+        let progress = query_goal_progress(&conn, at(NOW).date_naive())?;
+        let summary: Vec<(i64, u64, u64)> = progress
+            .iter()
+            .map(|p| (p.current, p.streak, p.longest_streak))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                // 1 of 2 today after reaching it yesterday
+                (1, 1, 1),
+                // 50 minutes on sunday reached last week, 35 so far this week
+                (35 * 60, 1, 1),
+                // tasks closed on 06-09 and 06-11
+                (0, 0, 1),
+            ]
+        );
+
+        conn.execute(DELETE_GOAL, named_params! {":id": progress[0].goal.id})
+            .map_err(|e| e.to_string())?;
+        assert_eq!(query_goal_progress(&conn, at(NOW).date_naive())?.len(), 2);
+
+        cleanup_test_db(&db_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_logged_sessions_do_not_overlap() -> Result<(), Box<dyn Error>> {
+        let (conn, db_path) = setup_test_db()?;
+
+        let session = |start: &str, end: &str| PomoTask {
+            title: "Review".to_string(),
+            status: PomoStatus::Finished,
+            duration: DurationField(at(end) - at(start)),
+            elapsed: DurationField(at(end) - at(start)),
+            start_time: at(start),
+            end_time: at(end),
+            ..Default::default()
+        };
+
+        let mut first = session("2026-06-15 09:00", "2026-06-15 09:50");
+        insert_logged_pomodoro(&conn, &mut first)?;
+        assert!(first.id > 0);
+
+        // A session still running from 11:00 ends at 11:25.
+        conn.execute(
+            "INSERT INTO pomodoro (type, title, start_time, duration) VALUES (1, 'Running', ?1, 1500)",
+            params![at("2026-06-15 11:00").to_rfc3339()],
+        )
+        .map_err(|e| e.to_string())?;
+
+        for (start, end) in [
+            ("2026-06-15 09:40", "2026-06-15 10:10"),
+            ("2026-06-15 08:00", "2026-06-15 10:00"),
+            ("2026-06-15 11:20", "2026-06-15 11:40"),
+        ] {
+            assert!(insert_logged_pomodoro(&conn, &mut session(start, end)).is_err());
+        }
+
+        // Back to back sessions are fine.
+        let mut second = session("2026-06-15 09:50", "2026-06-15 10:15");
+        insert_logged_pomodoro(&conn, &mut second)?;
+
+        let logged = conn
+            .query_row(
+                "SELECT id, type, title, start_time, end_time, duration, status, category, task_id, elapsed, uuid, created_at, updated_at FROM pomodoro WHERE id = ?1",
+                params![second.id],
+                parse_pomo_task,
+            )
+            .map_err(|e| e.to_string())?;
+        assert_eq!(logged.status, PomoStatus::Finished);
+        assert_eq!(logged.start_time, at("2026-06-15 09:50"));
+        assert_eq!(logged.end_time, at("2026-06-15 10:15"));
+        assert_eq!(logged.elapsed.to_i64(), 25 * 60);
+
+        cleanup_test_db(&db_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_time_entries_run_one_at_a_time() -> Result<(), Box<dyn Error>> {
+        let (conn, db_path) = setup_test_db()?;
+        assert_eq!(query_running_time_entry(&conn)?, None);
+
+        let mut entry = TimeEntry {
+            id: 0,
+            task_id: None,
+            title: "Support".to_string(),
+            category: Some("ops".to_string()),
+            start_time: at("2026-06-15 09:00"),
+            end_time: None,
+        };
+        insert_time_entry(&conn, &mut entry)?;
+        assert_eq!(query_running_time_entry(&conn)?, Some(entry.clone()));
+
+        let mut second = TimeEntry {
+            id: 0,
+            start_time: at("2026-06-15 09:30"),
+            ..entry.clone()
+        };
+        assert!(insert_time_entry(&conn, &mut second).is_err());
+
+        set_time_entry_end(&conn, &mut entry, at("2026-06-15 10:15"))?;
+        assert_eq!(entry.elapsed_at(at(NOW)).num_minutes(), 75);
+        assert_eq!(query_running_time_entry(&conn)?, None);
+        assert!(set_time_entry_end(&conn, &mut entry, at(NOW)).is_err());
+
+        insert_time_entry(&conn, &mut second)?;
+
+        cleanup_test_db(&db_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_tracked_time_per_task_and_category() -> Result<(), Box<dyn Error>> {
+        let (conn, db_path) = setup_test_db()?;
+
+        conn.execute(
+            "INSERT INTO tasks (title, due_date, category) VALUES ('Release', ?1, 'work')",
+            params![at("2026-06-16 12:00").to_rfc3339()],
+        )
+        .map_err(|e| e.to_string())?;
+        let task_id = conn.last_insert_rowid() as u64;
+
+        for (task, title, category, start, end) in [
+            (
+                Some(task_id),
+                "Release",
+                Some("work"),
+                "2026-06-15 08:00",
+                Some("2026-06-15 09:00"),
+            ),
+            (
+                None,
+                "Support",
+                Some("ops"),
+                "2026-06-15 09:00",
+                Some("2026-06-15 09:30"),
+            ),
+            // still running at noon
+            (None, "Support", Some("ops"), "2026-06-15 11:30", None),
+            // outside of the range
+            (
+                None,
+                "Support",
+                Some("ops"),
+                "2026-06-14 09:00",
+                Some("2026-06-14 17:00"),
+            ),
+        ] {
+            conn.execute(
+                "INSERT INTO time_entries (task_id, title, category, start_time, end_time) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![task, title, category, at(start).to_rfc3339(), end.map(|e| at(e).to_rfc3339())],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+
+        for (pomo_type, task, elapsed_minutes) in [
+            (PomoType::Work, Some(task_id), 25),
+            (PomoType::Rest, None, 5),
+        ] {
+            conn.execute(
+                "INSERT INTO pomodoro (type, title, start_time, category, task_id, elapsed) VALUES (?1, 'Release', ?2, 'work', ?3, ?4)",
+                params![pomo_type.to_usize(), at("2026-06-15 10:00").to_rfc3339(), task, elapsed_minutes * 60],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+
+        let range = (at("2026-06-15 00:00"), at("2026-06-16 00:00"));
+        let per_task: Vec<(String, u64, i64)> =
+            query_tracked_time(&conn, TIME_BY_TASK, range.0, range.1, at(NOW))?
+                .into_iter()
+                .map(|t| (t.bucket, t.entries, t.seconds / 60))
+                .collect();
+        assert_eq!(
+            per_task,
+            vec![
+                (format!("#{} Release", task_id), 2, 85),
+                ("Support".to_string(), 2, 60),
+            ]
+        );
+
+        let per_category = query_tracked_time(&conn, TIME_BY_CATEGORY, range.0, range.1, at(NOW))?;
+        assert_eq!(per_category[0].bucket, "work");
+        assert!((per_category[0].share - 100.0 * 85.0 / 145.0).abs() < 0.001);
+        assert_eq!(per_category[1].bucket, "ops");
+        assert_eq!(per_category[1].seconds, 3600);
+
+        cleanup_test_db(&db_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_timesheet_per_day_category_and_task() -> Result<(), Box<dyn Error>> {
+        let (conn, db_path) = setup_test_db()?;
+
+        conn.execute(
+            "INSERT INTO tasks (title, due_date, category) VALUES ('Release', ?1, 'acme')",
+            params![at("2026-06-16 12:00").to_rfc3339()],
+        )
+        .map_err(|e| e.to_string())?;
+        let task_id = conn.last_insert_rowid() as u64;
+
+        for (task, title, category, start, end) in [
+            (
+                Some(task_id),
+                "Release",
+                "acme",
+                "2026-06-14 16:00",
+                "2026-06-14 16:40",
+            ),
+            (
+                None,
+                "Audit",
+                "initech",
+                "2026-06-15 08:00",
+                "2026-06-15 09:30",
+            ),
+        ] {
+            conn.execute(
+                "INSERT INTO time_entries (task_id, title, category, start_time, end_time) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![task, title, category, at(start).to_rfc3339(), at(end).to_rfc3339()],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        for (start, elapsed_minutes) in [("2026-06-14 10:00", 25), ("2026-06-14 11:00", 25)] {
+            conn.execute(
+                "INSERT INTO pomodoro (type, title, start_time, category, task_id, elapsed) VALUES (?1, 'Release', ?2, 'acme', ?3, ?4)",
+                params![PomoType::Work.to_usize(), at(start).to_rfc3339(), task_id, elapsed_minutes * 60],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+
+        let lines: Vec<(String, String, String, i64)> = query_timesheet(
+            &conn,
+            Local,
+            at("2026-06-14 00:00"),
+            at("2026-06-16 00:00"),
+            at(NOW),
+        )?
+        .into_iter()
+        .map(|l| (l.date.to_string(), l.category, l.task, l.seconds / 60))
+        .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (
+                    "2026-06-14".to_string(),
+                    "acme".to_string(),
+                    format!("#{} Release", task_id),
+                    90
+                ),
+                (
+                    "2026-06-15".to_string(),
+                    "initech".to_string(),
+                    "Audit".to_string(),
+                    90
+                ),
+            ]
+        );
+
+        // 02:00 UTC is still the evening before in New York.
+        conn.execute(
+            "INSERT INTO time_entries (title, start_time, end_time) VALUES ('Call', ?1, ?2)",
+            ["2026-06-20T02:00:00+00:00", "2026-06-20T03:00:00+00:00"],
+        )
+        .map_err(|e| e.to_string())?;
+        let utc = |s: &str| {
+            DateTime::parse_from_rfc3339(s)
+                .unwrap()
+                .with_timezone(&Local)
+        };
+        let days: Vec<String> = query_timesheet(
+            &conn,
+            chrono_tz::America::New_York,
+            utc("2026-06-19T04:00:00+00:00"),
+            utc("2026-06-20T04:00:00+00:00"),
+            at(NOW),
+        )?
+        .into_iter()
+        .map(|l| l.date.to_string())
+        .collect();
+        assert_eq!(days, vec!["2026-06-19"]);
+
+        cleanup_test_db(&db_path)?;
         Ok(())
     }
 }
@@ -0,0 +1,764 @@
+use crate::error::StoreError;
+use crate::helper::get_home_directory;
+use crate::models::{
+    Diagnosis, FlowAnalysis, Goal, GoalProgress, GroupBy, HourInterruptions, InterruptionKind,
+    LSArgs, PomoTask, ReportData, SessionInterruptions, SyncRecord, SyncTable, Task, TaskAnalysis,
+    TaskRef, TimeAnalysis, TimeEntry, TimesheetLine,
+};
+use crate::repository;
+use chrono::{DateTime, Local, NaiveDate};
+use chrono_tz::Tz;
+use rusqlite::backup::Progress;
+use rusqlite::{Connection, DatabaseName, OpenFlags};
+use std::collections::BTreeMap;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+const DB_FILE_PATH: &str = ".tasklog";
+const DB_FILE_NAME: &str = "db.sqlite";
+const BACKUP_DIR: &str = "backups";
+const DAILY_SNAPSHOT_PREFIX: &str = "daily-";
+const BACKUP_EXTENSION: &str = ".sqlite";
+
+/// Where tasks, pomodoro sessions, goals and time entries are kept. Implemented by [`TaskStore`]
+/// on SQLite and by [`MemoryStore`](crate::memory::MemoryStore), which keeps them in memory.
+pub trait Repository {
+    /// The tasks matching the filters of `ls`, due before `due_before`.
+    fn tasks(&self, filters: &LSArgs, due_before: DateTime<Local>)
+        -> Result<Vec<Task>, StoreError>;
+
+    /// Saves a new task and sets its id and metadata. A uuid the task already has is kept, as
+    /// for tasks imported from elsewhere.
+    fn add_task(&self, task: &mut Task) -> Result<(), StoreError>;
+
+    /// Every task, open or done, in the order they were added.
+    fn all_tasks(&self) -> Result<Vec<Task>, StoreError>;
+
+    fn task(&self, task_id: u64) -> Result<Task, StoreError>;
+
+    /// The one task whose uuid starts with `prefix`.
+    fn task_by_uuid(&self, prefix: &str) -> Result<Task, StoreError>;
+
+    /// The task named on the command line, by id or uuid prefix.
+    fn find_task(&self, task_ref: &TaskRef) -> Result<Task, StoreError> {
+        match task_ref {
+            TaskRef::Id(id) => self.task(*id),
+            TaskRef::Uuid(prefix) => self.task_by_uuid(prefix),
+        }
+    }
+
+    /// How many characters of their uuids tell all the tasks apart.
+    fn uuid_prefix_len(&self) -> Result<usize, StoreError>;
+
+    /// Saves every field of the task but its id and metadata.
+    fn update_task(&self, task: &Task) -> Result<(), StoreError>;
+
+    fn complete_task(&self, task_id: u64) -> Result<(), StoreError>;
+
+    /// Open tasks whose reminder is due at `now` and has not fired yet.
+    fn due_reminders(&self, now: DateTime<Local>) -> Result<Vec<Task>, StoreError>;
+
+    fn mark_reminded(&self, task_id: u64, reminded_at: DateTime<Local>) -> Result<(), StoreError>;
+
+    /// The number of open tasks, of those overdue at `now` and the session running at `now` if
+    /// any, all `prompt` shows.
+    fn prompt_status(
+        &self,
+        now: DateTime<Local>,
+    ) -> Result<(u64, u64, Option<PomoTask>), StoreError>;
+
+    /// The latest `limit` pomodoro sessions.
+    fn sessions(&self, limit: usize) -> Result<Vec<PomoTask>, StoreError>;
+
+    /// Records a session starting now and sets its id and start time.
+    fn add_session(&self, session: &mut PomoTask) -> Result<(), StoreError>;
+
+    fn update_session(&self, session: &PomoTask) -> Result<(), StoreError>;
+
+    /// Records a finished session that ran without the timer, unless it overlaps another session.
+    fn log_session(&self, session: &mut PomoTask) -> Result<(), StoreError>;
+
+    /// The session running in the foreground or background at `now`, if any.
+    fn running_session(&self, now: DateTime<Local>) -> Result<Option<PomoTask>, StoreError>;
+
+    fn add_interruption(
+        &self,
+        session_id: u64,
+        kind: InterruptionKind,
+        note: &str,
+        created_at: DateTime<Local>,
+    ) -> Result<(), StoreError>;
+
+    /// Saves a new goal and sets its id.
+    fn add_goal(&self, goal: &mut Goal) -> Result<(), StoreError>;
+
+    fn remove_goal(&self, goal_id: u64) -> Result<(), StoreError>;
+
+    /// Every goal with its progress in the period containing `today` and its streaks.
+    fn goal_progress(&self, today: NaiveDate) -> Result<Vec<GoalProgress>, StoreError>;
+
+    /// Starts tracking time, unless another time entry is still running.
+    fn start_time_entry(&self, entry: &mut TimeEntry) -> Result<(), StoreError>;
+
+    fn running_time_entry(&self) -> Result<Option<TimeEntry>, StoreError>;
+
+    /// Ends the time entry at `end_time`, entries already stopped keep their end.
+    fn finish_time_entry(
+        &self,
+        entry: &mut TimeEntry,
+        end_time: DateTime<Local>,
+    ) -> Result<(), StoreError>;
+}
+
+/// The analyses and reports aggregated over the stored records. They are written in SQL and only
+/// [`TaskStore`] implements them, handlers using them are tested against
+/// [`TaskStore::in_memory`].
+pub trait Analytics {
+    /// The interruptions of each work session in the range and of each hour of the day.
+    fn interruptions(
+        &self,
+        start_date: DateTime<Local>,
+        end_date: DateTime<Local>,
+    ) -> Result<(Vec<SessionInterruptions>, Vec<HourInterruptions>), StoreError>;
+
+    /// Time tracked in the range per task and per category, running entries count until `now`.
+    fn tracked_time(
+        &self,
+        start_date: DateTime<Local>,
+        end_date: DateTime<Local>,
+        now: DateTime<Local>,
+    ) -> Result<(Vec<TimeAnalysis>, Vec<TimeAnalysis>), StoreError>;
+
+    /// The time tracked in the range per day, category and task, days taken in the `home` time
+    /// zone.
+    fn timesheet(
+        &self,
+        home: Tz,
+        start_date: DateTime<Local>,
+        end_date: DateTime<Local>,
+        now: DateTime<Local>,
+    ) -> Result<Vec<TimesheetLine>, StoreError>;
+
+    /// The tasks due in the range per `group_by`, dates taken in the `home` time zone.
+    fn task_analysis(
+        &self,
+        group_by: GroupBy,
+        home: Tz,
+        start_date: DateTime<Local>,
+        end_date: DateTime<Local>,
+        now: DateTime<Local>,
+    ) -> Result<Vec<TaskAnalysis>, StoreError>;
+
+    fn flow_analysis(
+        &self,
+        start_date: DateTime<Local>,
+        end_date: DateTime<Local>,
+        now: DateTime<Local>,
+    ) -> Result<FlowAnalysis, StoreError>;
+
+    fn report(
+        &self,
+        start_date: DateTime<Local>,
+        end_date: DateTime<Local>,
+        now: DateTime<Local>,
+    ) -> Result<ReportData, StoreError>;
+}
+
+/// The SQLite backend, holding one connection to the database.
+///
+/// ```no_run
+/// use tasklog::models::Task;
+/// use tasklog::{Repository, TaskStore};
+///
+/// let store = TaskStore::open_default()?;
+/// let mut task = Task {
+///     title: "Write the changelog".to_string(),
+///     ..Default::default()
+/// };
+/// store.add_task(&mut task)?;
+/// store.complete_task(task.id)?;
+/// # Ok::<(), tasklog::StoreError>(())
+/// ```
+pub struct TaskStore {
+    conn: Connection,
+}
+
+impl TaskStore {
+    /// `~/.tasklog/db.sqlite`, where the CLI keeps its database.
+    pub fn default_path() -> Result<PathBuf, StoreError> {
+        let home_dir = get_home_directory()?;
+
+        Ok(PathBuf::from(home_dir)
+            .join(DB_FILE_PATH)
+            .join(DB_FILE_NAME))
+    }
+
+    /// Opens an existing database, upgrading its schema when it is older than this build.
+    pub fn open(path: impl AsRef<Path>) -> Result<TaskStore, StoreError> {
+        // Opening would create an empty file where `init` is meant to.
+        if !path.as_ref().exists() {
+            return Err(StoreError::Uninitialized(path.as_ref().to_path_buf()));
+        }
+
+        let conn = Connection::open(path)?;
+        repository::migrate(&conn)?;
+
+        Ok(TaskStore { conn })
+    }
+
+    /// Opens the database, creating it and its directory first when they do not exist yet.
+    pub fn init(path: impl AsRef<Path>) -> Result<TaskStore, StoreError> {
+        if let Some(dir) = path.as_ref().parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let conn = Connection::open(path)?;
+        repository::create_schema(&conn)?;
+
+        Ok(TaskStore { conn })
+    }
+
+    /// `~/.tasklog/backups`, where backups and snapshots go unless told otherwise.
+    pub fn default_backup_dir() -> Result<PathBuf, StoreError> {
+        Ok(TaskStore::default_path()?.with_file_name(BACKUP_DIR))
+    }
+
+    /// Opens an existing database read only and as it is, without creating or upgrading anything,
+    /// for `prompt` which runs on every shell prompt. `None` when there is no database yet.
+    pub fn open_read_only(path: impl AsRef<Path>) -> Result<Option<TaskStore>, StoreError> {
+        if !path.as_ref().exists() {
+            return Ok(None);
+        }
+
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        Ok(Some(TaskStore { conn }))
+    }
+
+    pub fn open_default() -> Result<TaskStore, StoreError> {
+        TaskStore::open(TaskStore::default_path()?)
+    }
+
+    pub fn init_default() -> Result<TaskStore, StoreError> {
+        TaskStore::init(TaskStore::default_path()?)
+    }
+
+    /// Checks the database at `path` for problems, repairing what can be repaired when `fix` is
+    /// set. Without it the database is opened read only and left as it is.
+    pub fn diagnose(
+        path: impl AsRef<Path>,
+        now: DateTime<Local>,
+        fix: bool,
+    ) -> Result<Vec<Diagnosis>, StoreError> {
+        let path = path.as_ref();
+        let permissions = check_permissions(path, fix)?;
+        if !path.exists() {
+            return Ok(vec![permissions]);
+        }
+
+        let flags = if fix {
+            OpenFlags::SQLITE_OPEN_READ_WRITE
+        } else {
+            OpenFlags::SQLITE_OPEN_READ_ONLY
+        };
+        let conn = Connection::open_with_flags(path, flags)?;
+
+        let mut diagnoses = vec![permissions];
+        diagnoses.extend(repository::diagnose(&conn, now, fix)?);
+        Ok(diagnoses)
+    }
+
+    /// Copies the database to `path` with SQLite's online backup, which gives a consistent copy
+    /// even while another process writes to it. An existing file is never overwritten.
+    pub fn backup(&self, path: impl AsRef<Path>) -> Result<(), StoreError> {
+        let path = path.as_ref();
+        if path.exists() {
+            return Err(StoreError::Conflict(format!(
+                "{} already exists",
+                path.display()
+            )));
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        self.conn.backup(DatabaseName::Main, path, None)?;
+        Ok(())
+    }
+
+    /// Checks that the file at `path` is a tasklog database this build can restore.
+    pub fn check_backup(path: impl AsRef<Path>) -> Result<(), StoreError> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Err(StoreError::Invalid(format!(
+                "There is no backup at {}",
+                path.display()
+            )));
+        }
+
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        repository::check_backup(&conn)
+    }
+
+    /// Replaces every record with the ones of the backup at `path`, upgrading its schema when it
+    /// was written by an older build.
+    pub fn restore(&mut self, path: impl AsRef<Path>) -> Result<(), StoreError> {
+        TaskStore::check_backup(&path)?;
+
+        self.conn
+            .restore(DatabaseName::Main, path, None::<fn(Progress)>)?;
+        repository::migrate(&self.conn)
+    }
+
+    /// Takes the snapshot of `today` into `dir` unless it was taken already, then removes the
+    /// daily snapshots older than the latest `keep`. Returns the path of a new snapshot.
+    pub fn snapshot_daily(
+        &self,
+        dir: impl AsRef<Path>,
+        today: NaiveDate,
+        keep: usize,
+    ) -> Result<Option<PathBuf>, StoreError> {
+        let dir = dir.as_ref();
+        let path = dir.join(format!(
+            "{}{}{}",
+            DAILY_SNAPSHOT_PREFIX,
+            today.format("%Y-%m-%d"),
+            BACKUP_EXTENSION
+        ));
+
+        let taken = if path.exists() {
+            None
+        } else {
+            self.backup(&path)?;
+            Some(path)
+        };
+
+        // The dates in the names sort them from the oldest to the latest.
+        let mut snapshots = fs::read_dir(dir)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        snapshots.retain(|p| {
+            p.file_name().and_then(|n| n.to_str()).is_some_and(|n| {
+                n.starts_with(DAILY_SNAPSHOT_PREFIX) && n.ends_with(BACKUP_EXTENSION)
+            })
+        });
+        snapshots.sort();
+        for old in &snapshots[..snapshots.len().saturating_sub(keep)] {
+            fs::remove_file(old)?;
+        }
+
+        Ok(taken)
+    }
+
+    /// The records of `table` keyed by uuid, as `sync` writes them.
+    pub fn sync_records(
+        &self,
+        table: SyncTable,
+    ) -> Result<BTreeMap<String, SyncRecord>, StoreError> {
+        repository::query_sync_records(&self.conn, table)
+    }
+
+    /// Stores records pulled by `sync`, replacing the ones with the same uuid.
+    pub fn apply_sync_records(
+        &self,
+        table: SyncTable,
+        records: &[SyncRecord],
+    ) -> Result<(), StoreError> {
+        repository::upsert_sync_records(&self.conn, table, records)
+    }
+
+    /// A throwaway database, handy for tests and dry runs.
+    pub fn in_memory() -> Result<TaskStore, StoreError> {
+        let conn = Connection::open_in_memory()?;
+        repository::create_schema(&conn)?;
+
+        Ok(TaskStore { conn })
+    }
+}
+
+/// Whether the database and its directory exist and can be written, `fix` creates a missing
+/// database and gives the user read and write access back.
+fn check_permissions(path: &Path, fix: bool) -> Result<Diagnosis, StoreError> {
+    let mut diagnosis = Diagnosis::new("permissions");
+
+    if !path.exists() {
+        if fix {
+            TaskStore::init(path)?;
+        }
+        diagnosis.found(format!("there is no database at {}", path.display()), fix);
+        return Ok(diagnosis);
+    }
+
+    // SQLite keeps its journal next to the database, so the directory has to be writable too.
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+    for (target, mode) in dir
+        .map(|dir| (dir, 0o700))
+        .into_iter()
+        .chain([(path, 0o600)])
+    {
+        let mut permissions = fs::metadata(target)?.permissions();
+        if permissions.mode() & mode == mode {
+            continue;
+        }
+        if fix {
+            permissions.set_mode(permissions.mode() | mode);
+            fs::set_permissions(target, permissions)?;
+        }
+        diagnosis.found(
+            format!("{} is not readable and writable by you", target.display()),
+            fix,
+        );
+    }
+
+    Ok(diagnosis)
+}
+
+impl Repository for TaskStore {
+    fn tasks(
+        &self,
+        filters: &LSArgs,
+        due_before: DateTime<Local>,
+    ) -> Result<Vec<Task>, StoreError> {
+        repository::get_tasks(&self.conn, filters, due_before)
+    }
+
+    fn add_task(&self, task: &mut Task) -> Result<(), StoreError> {
+        repository::save_task(&self.conn, task)
+    }
+
+    fn all_tasks(&self) -> Result<Vec<Task>, StoreError> {
+        repository::query_all_tasks(&self.conn)
+    }
+
+    fn task(&self, task_id: u64) -> Result<Task, StoreError> {
+        repository::query_task_by_id(&self.conn, task_id as usize)
+    }
+
+    fn task_by_uuid(&self, prefix: &str) -> Result<Task, StoreError> {
+        repository::query_task_by_uuid_prefix(&self.conn, prefix)
+    }
+
+    fn uuid_prefix_len(&self) -> Result<usize, StoreError> {
+        repository::query_uuid_prefix_len(&self.conn)
+    }
+
+    fn update_task(&self, task: &Task) -> Result<(), StoreError> {
+        repository::update_task(&self.conn, task)
+    }
+
+    fn complete_task(&self, task_id: u64) -> Result<(), StoreError> {
+        repository::mark_task_done(&self.conn, task_id as usize)
+    }
+
+    fn due_reminders(&self, now: DateTime<Local>) -> Result<Vec<Task>, StoreError> {
+        repository::get_due_reminders(&self.conn, now)
+    }
+
+    fn mark_reminded(&self, task_id: u64, reminded_at: DateTime<Local>) -> Result<(), StoreError> {
+        repository::set_reminded(&self.conn, task_id, reminded_at)
+    }
+
+    fn prompt_status(
+        &self,
+        now: DateTime<Local>,
+    ) -> Result<(u64, u64, Option<PomoTask>), StoreError> {
+        repository::query_prompt_status(&self.conn, now)
+    }
+
+    fn sessions(&self, limit: usize) -> Result<Vec<PomoTask>, StoreError> {
+        repository::get_pomodoro(&self.conn, limit)
+    }
+
+    fn add_session(&self, session: &mut PomoTask) -> Result<(), StoreError> {
+        repository::add_pomodoro(&self.conn, session)
+    }
+
+    fn update_session(&self, session: &PomoTask) -> Result<(), StoreError> {
+        repository::update_pomodoro(&self.conn, session)
+    }
+
+    fn log_session(&self, session: &mut PomoTask) -> Result<(), StoreError> {
+        repository::insert_logged_pomodoro(&self.conn, session)
+    }
+
+    fn running_session(&self, now: DateTime<Local>) -> Result<Option<PomoTask>, StoreError> {
+        repository::query_running_pomodoro(&self.conn, now)
+    }
+
+    fn add_interruption(
+        &self,
+        session_id: u64,
+        kind: InterruptionKind,
+        note: &str,
+        created_at: DateTime<Local>,
+    ) -> Result<(), StoreError> {
+        repository::insert_interruption(&self.conn, session_id, kind, note, created_at)
+    }
+
+    fn add_goal(&self, goal: &mut Goal) -> Result<(), StoreError> {
+        repository::insert_goal(&self.conn, goal)
+    }
+
+    fn remove_goal(&self, goal_id: u64) -> Result<(), StoreError> {
+        repository::remove_goal(&self.conn, goal_id)
+    }
+
+    fn goal_progress(&self, today: NaiveDate) -> Result<Vec<GoalProgress>, StoreError> {
+        repository::query_goal_progress(&self.conn, today)
+    }
+
+    fn start_time_entry(&self, entry: &mut TimeEntry) -> Result<(), StoreError> {
+        repository::insert_time_entry(&self.conn, entry)
+    }
+
+    fn running_time_entry(&self) -> Result<Option<TimeEntry>, StoreError> {
+        repository::query_running_time_entry(&self.conn)
+    }
+
+    fn finish_time_entry(
+        &self,
+        entry: &mut TimeEntry,
+        end_time: DateTime<Local>,
+    ) -> Result<(), StoreError> {
+        repository::set_time_entry_end(&self.conn, entry, end_time)
+    }
+}
+
+impl Analytics for TaskStore {
+    fn interruptions(
+        &self,
+        start_date: DateTime<Local>,
+        end_date: DateTime<Local>,
+    ) -> Result<(Vec<SessionInterruptions>, Vec<HourInterruptions>), StoreError> {
+        repository::query_interruption_analysis(&self.conn, start_date, end_date)
+    }
+
+    fn tracked_time(
+        &self,
+        start_date: DateTime<Local>,
+        end_date: DateTime<Local>,
+        now: DateTime<Local>,
+    ) -> Result<(Vec<TimeAnalysis>, Vec<TimeAnalysis>), StoreError> {
+        Ok((
+            repository::query_tracked_time(
+                &self.conn,
+                repository::TIME_BY_TASK,
+                start_date,
+                end_date,
+                now,
+            )?,
+            repository::query_tracked_time(
+                &self.conn,
+                repository::TIME_BY_CATEGORY,
+                start_date,
+                end_date,
+                now,
+            )?,
+        ))
+    }
+
+    fn timesheet(
+        &self,
+        home: Tz,
+        start_date: DateTime<Local>,
+        end_date: DateTime<Local>,
+        now: DateTime<Local>,
+    ) -> Result<Vec<TimesheetLine>, StoreError> {
+        repository::query_timesheet(&self.conn, home, start_date, end_date, now)
+    }
+
+    fn task_analysis(
+        &self,
+        group_by: GroupBy,
+        home: Tz,
+        start_date: DateTime<Local>,
+        end_date: DateTime<Local>,
+        now: DateTime<Local>,
+    ) -> Result<Vec<TaskAnalysis>, StoreError> {
+        repository::query_analysis_at(&self.conn, group_by, home, start_date, end_date, now)
+    }
+
+    fn flow_analysis(
+        &self,
+        start_date: DateTime<Local>,
+        end_date: DateTime<Local>,
+        now: DateTime<Local>,
+    ) -> Result<FlowAnalysis, StoreError> {
+        repository::query_flow_analysis(&self.conn, start_date, end_date, now)
+    }
+
+    fn report(
+        &self,
+        start_date: DateTime<Local>,
+        end_date: DateTime<Local>,
+        now: DateTime<Local>,
+    ) -> Result<ReportData, StoreError> {
+        repository::query_report(&self.conn, start_date, end_date, now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TaskStatus;
+
+    #[test]
+    fn test_task_round_trip() {
+        let store = TaskStore::in_memory().unwrap();
+        let mut task = Task {
+            title: "Write the changelog".to_string(),
+            ..Default::default()
+        };
+
+        store.add_task(&mut task).unwrap();
+        assert!(task.id > 0);
+
+        store.complete_task(task.id).unwrap();
+        assert_eq!(store.task(task.id).unwrap().status, TaskStatus::Done);
+
+        assert!(matches!(
+            store.task(task.id + 1),
+            Err(StoreError::NotFound { record: "task", .. })
+        ));
+        assert!(matches!(
+            store.complete_task(task.id + 1),
+            Err(StoreError::NotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_init_creates_directory() {
+        let dir = std::env::temp_dir().join(format!("tasklog-store-{}", std::process::id()));
+        let path = dir.join("nested").join(DB_FILE_NAME);
+
+        assert!(matches!(
+            TaskStore::open(&path),
+            Err(StoreError::Uninitialized(_))
+        ));
+        assert!(!path.exists());
+
+        TaskStore::init(&path).unwrap();
+        assert!(path.exists());
+        TaskStore::open(&path).unwrap();
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_diagnose_missing_database() {
+        let dir = std::env::temp_dir().join(format!("tasklog-doctor-{}", std::process::id()));
+        let path = dir.join(DB_FILE_NAME);
+
+        let diagnoses = TaskStore::diagnose(&path, Local::now(), false).unwrap();
+        assert_eq!(diagnoses.len(), 1);
+        assert!(!diagnoses[0].findings[0].fixed);
+        assert!(!path.exists());
+
+        TaskStore::diagnose(&path, Local::now(), true).unwrap();
+        let diagnoses = TaskStore::diagnose(&path, Local::now(), false).unwrap();
+        assert!(diagnoses.iter().all(|d| d.findings.is_empty()));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_backup_restore_and_snapshots() {
+        let dir = std::env::temp_dir().join(format!("tasklog-backup-{}", std::process::id()));
+        let mut store = TaskStore::init(dir.join(DB_FILE_NAME)).unwrap();
+        let mut task = Task {
+            title: "Keep me".to_string(),
+            ..Default::default()
+        };
+        store.add_task(&mut task).unwrap();
+
+        let backup = dir.join("backup.sqlite");
+        store.backup(&backup).unwrap();
+        assert!(matches!(
+            store.backup(&backup),
+            Err(StoreError::Conflict(_))
+        ));
+
+        store.complete_task(task.id).unwrap();
+        store.restore(&backup).unwrap();
+        assert_eq!(store.task(task.id).unwrap().status, TaskStatus::Open);
+
+        fs::write(dir.join("notes.txt"), "not a database").unwrap();
+        assert!(TaskStore::check_backup(dir.join("notes.txt")).is_err());
+        assert!(TaskStore::check_backup(dir.join("missing.sqlite")).is_err());
+
+        let snapshots = dir.join(BACKUP_DIR);
+        let day = NaiveDate::from_ymd_opt(2026, 6, 15).unwrap();
+        for days in 0..4 {
+            let taken = store
+                .snapshot_daily(&snapshots, day + chrono::Duration::days(days), 2)
+                .unwrap();
+            assert!(taken.is_some());
+        }
+        let latest = day + chrono::Duration::days(3);
+        assert!(store
+            .snapshot_daily(&snapshots, latest, 2)
+            .unwrap()
+            .is_none());
+        let mut kept: Vec<_> = fs::read_dir(&snapshots)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        kept.sort();
+        assert_eq!(
+            kept,
+            vec!["daily-2026-06-17.sqlite", "daily-2026-06-18.sqlite"]
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_sync_records_round_trip() {
+        let laptop = TaskStore::in_memory().unwrap();
+        let mut task = Task {
+            title: "Shared".to_string(),
+            ..Default::default()
+        };
+        laptop.add_task(&mut task).unwrap();
+        let mut session = PomoTask {
+            title: "Focus".to_string(),
+            task_id: Some(task.id),
+            ..Default::default()
+        };
+        laptop.add_session(&mut session).unwrap();
+
+        let desktop = TaskStore::in_memory().unwrap();
+        desktop.add_task(&mut Task::default()).unwrap();
+        for table in SyncTable::ALL {
+            let records: Vec<_> = laptop.sync_records(table).unwrap().into_values().collect();
+            assert_eq!(records.len(), 1);
+            desktop.apply_sync_records(table, &records).unwrap();
+        }
+
+        // The uuids and modification times travel, the ids are the desktop's own.
+        for table in SyncTable::ALL {
+            let theirs = laptop.sync_records(table).unwrap();
+            let ours = desktop.sync_records(table).unwrap();
+            for (uuid, record) in &theirs {
+                assert_eq!(ours.get(uuid), Some(record));
+            }
+        }
+        let pulled = desktop.sessions(10).unwrap();
+        assert_eq!(
+            desktop.task(pulled[0].task_id.unwrap()).unwrap().title,
+            "Shared"
+        );
+
+        let mut done = laptop.sync_records(SyncTable::Tasks).unwrap();
+        let record = done.values_mut().next().unwrap();
+        record.fields[1] = Some("1".to_string());
+        desktop
+            .apply_sync_records(SyncTable::Tasks, std::slice::from_ref(record))
+            .unwrap();
+        assert_eq!(desktop.sync_records(SyncTable::Tasks).unwrap().len(), 2);
+        assert_eq!(
+            desktop.task(pulled[0].task_id.unwrap()).unwrap().status,
+            TaskStatus::Done
+        );
+    }
+}
@@ -0,0 +1,598 @@
+use crate::error::AppError;
+use crate::helper::get_config_directory;
+use crate::models::{SyncRecord, SyncTable};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+const SYNC_DIR: &str = "/sync";
+const BRANCH: &str = "main";
+const REMOTE: &str = "origin";
+/// First line of every file, changes when the format does
+const FORMAT_HEADER: &str = "# tasklog sync 1";
+const NULL: &str = "\\N";
+
+/// Records of one table keyed by uuid, sorted so the files come out the same on every machine.
+pub type Records = BTreeMap<String, SyncRecord>;
+
+/// `~/.tasklog/sync`, the git repository `sync` works in unless `sync_dir` says otherwise.
+pub fn default_dir() -> Result<PathBuf, AppError> {
+    Ok(PathBuf::from(get_config_directory()? + SYNC_DIR))
+}
+
+/// The side of a record changed on both machines whose version was kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Local,
+    Remote,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub table: SyncTable,
+    pub uuid: String,
+    pub title: String,
+    pub kept: Side,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Merge {
+    /// Records of the other machines to store here
+    pub pulled: Vec<SyncRecord>,
+    /// How many records changed here and not on the other machines
+    pub pushed: usize,
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Merges the records changed here since `base`, the last sync, with the ones of `remote`. A
+/// record changed on one side only takes that change, one changed on both keeps the most recent
+/// change and is reported as a conflict. Records are never deleted, so one missing on a side was
+/// created on the other.
+pub fn merge(table: SyncTable, base: &Records, local: &Records, remote: &Records) -> Merge {
+    let mut merge = Merge {
+        pushed: local
+            .keys()
+            .filter(|uuid| !remote.contains_key(*uuid))
+            .count(),
+        ..Default::default()
+    };
+
+    for (uuid, theirs) in remote {
+        let ours = match local.get(uuid) {
+            None => {
+                merge.pulled.push(theirs.clone());
+                continue;
+            }
+            Some(ours) if ours == theirs => continue,
+            Some(ours) => ours,
+        };
+
+        let base = base.get(uuid);
+        if base == Some(ours) {
+            merge.pulled.push(theirs.clone());
+        } else if base == Some(theirs) {
+            merge.pushed += 1;
+        } else {
+            // Ties go to this machine, the other one decides the same way when it syncs next.
+            let kept = if modified_at(theirs) > modified_at(ours) {
+                merge.pulled.push(theirs.clone());
+                Side::Remote
+            } else {
+                merge.pushed += 1;
+                Side::Local
+            };
+            merge.conflicts.push(Conflict {
+                table,
+                uuid: uuid.clone(),
+                title: ours.title().to_string(),
+                kept,
+            });
+        }
+    }
+
+    merge
+}
+
+/// When the record was changed, SQLite writes `YYYY-MM-DD HH:MM:SS` in UTC and tasklog RFC 3339.
+fn modified_at(record: &SyncRecord) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(&record.updated_at)
+        .map(|d| d.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(&record.updated_at, "%Y-%m-%d %H:%M:%S%.f")
+                .ok()
+                .map(|d| d.and_utc())
+        })
+}
+
+/// Writes the records as a header line naming the columns followed by one tab separated line per
+/// record, sorted by uuid so that git diffs show the records that changed.
+pub fn render(table: SyncTable, records: &Records) -> String {
+    let mut out = format!("{}\n{}\n", FORMAT_HEADER, header(table));
+    for record in records.values() {
+        let fields = [Some(&record.uuid), Some(&record.updated_at)]
+            .into_iter()
+            .chain(record.fields.iter().map(Option::as_ref))
+            .map(|field| field.map_or(NULL.to_string(), |f| escape(f)))
+            .collect::<Vec<_>>();
+        out.push_str(&fields.join("\t"));
+        out.push('\n');
+    }
+    out
+}
+
+/// Reads the records back. Columns added since the file was written read as NULL, a file with
+/// columns this tasklog does not know comes from a newer one and is refused.
+pub fn parse(table: SyncTable, contents: &str) -> Result<Records, AppError> {
+    let mut lines = contents.lines();
+    if lines.next() != Some(FORMAT_HEADER) {
+        return Err(AppError::Parse(format!(
+            "{} is not in a format this tasklog knows",
+            table.file_name()
+        )));
+    }
+    let header = lines
+        .next()
+        .unwrap_or_default()
+        .split('\t')
+        .collect::<Vec<_>>();
+    let positions = match header.get(..2) {
+        Some(["uuid", "updated_at"]) => header[2..]
+            .iter()
+            .map(|name| table.columns().iter().position(|column| column == name))
+            .collect::<Option<Vec<_>>>(),
+        _ => None,
+    }
+    .ok_or_else(|| {
+        AppError::Parse(format!(
+            "{} has other columns than this tasklog, update it on every machine",
+            table.file_name()
+        ))
+    })?;
+
+    let mut records = Records::new();
+    for (i, line) in lines.enumerate() {
+        let mut values = line
+            .split('\t')
+            .map(|field| (field != NULL).then(|| unescape(field)))
+            .collect::<Vec<_>>();
+        if values.len() != header.len() {
+            return Err(AppError::Parse(format!(
+                "{} line {}: expected {} columns",
+                table.file_name(),
+                i + 3,
+                header.len()
+            )));
+        }
+
+        let uuid = values.remove(0).unwrap_or_default();
+        let updated_at = values.remove(0).unwrap_or_default();
+        let mut fields = vec![None; table.columns().len()];
+        for (value, position) in values.into_iter().zip(&positions) {
+            fields[*position] = value;
+        }
+        records.insert(
+            uuid.clone(),
+            SyncRecord {
+                uuid,
+                updated_at,
+                fields,
+            },
+        );
+    }
+
+    Ok(records)
+}
+
+fn header(table: SyncTable) -> String {
+    ["uuid", "updated_at"]
+        .iter()
+        .chain(table.columns())
+        .copied()
+        .collect::<Vec<_>>()
+        .join("\t")
+}
+
+fn escape(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn unescape(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// The git repository the records are committed to, its `origin` the one shared between machines.
+pub struct SyncRepo {
+    dir: PathBuf,
+    /// Whether git has no author configured, as on machines that never used it
+    anonymous: bool,
+}
+
+impl SyncRepo {
+    /// Opens the repository in `dir`, creating it when needed, and points `origin` at `remote`. A
+    /// remote that is a local path and does not exist yet is created as a bare repository.
+    pub fn open(dir: &Path, remote: Option<&str>) -> Result<SyncRepo, AppError> {
+        fs::create_dir_all(dir).map_err(|e| {
+            AppError::from(e).context(format!("Could not create {}", dir.display()))
+        })?;
+        let mut repo = SyncRepo {
+            dir: dir.to_path_buf(),
+            anonymous: false,
+        };
+
+        if !dir.join(".git").exists() {
+            repo.git(&["init", "-q", "--initial-branch", BRANCH])?;
+        }
+
+        repo.anonymous = repo.query(&["config", "user.email"])?.is_none();
+
+        if let Some(remote) = remote {
+            let is_path = !remote.contains(':');
+            if is_path && !Path::new(remote).exists() {
+                run(Command::new("git").args([
+                    "init",
+                    "-q",
+                    "--bare",
+                    "--initial-branch",
+                    BRANCH,
+                    remote,
+                ]))?;
+            }
+
+            match repo.query(&["remote", "get-url", REMOTE])? {
+                Some(url) if url == remote => {}
+                Some(_) => {
+                    repo.git(&["remote", "set-url", REMOTE, remote])?;
+                }
+                None => {
+                    repo.git(&["remote", "add", REMOTE, remote])?;
+                }
+            }
+        }
+
+        Ok(repo)
+    }
+
+    /// The commit of the last sync, none before the first one.
+    pub fn head(&self) -> Result<Option<String>, AppError> {
+        self.query(&["rev-parse", "-q", "--verify", "HEAD"])
+    }
+
+    /// Fetches what the other machines pushed, returning their latest commit.
+    pub fn fetch(&self) -> Result<Option<String>, AppError> {
+        self.git(&["fetch", "-q", REMOTE])?;
+        self.query(&[
+            "rev-parse",
+            "-q",
+            "--verify",
+            &format!("refs/remotes/{}/{}", REMOTE, BRANCH),
+        ])
+    }
+
+    /// The records of the table as of the commit, none when it has no file for the table.
+    pub fn records_at(&self, commit: &str, table: SyncTable) -> Result<Records, AppError> {
+        match self.query(&["show", &format!("{}:{}", commit, table.file_name())])? {
+            Some(contents) => parse(table, &contents),
+            None => Ok(Records::new()),
+        }
+    }
+
+    /// Makes the commit of the other machines a parent of the next one. The branch moves to it
+    /// when there is nothing here it does not have, otherwise a merge is started that keeps the
+    /// files of this machine, which the merged records are then written over.
+    pub fn join(&self, remote_head: &str) -> Result<(), AppError> {
+        let head = self.head()?;
+        let is_ancestor = |ancestor: &str, of: &str| -> Result<bool, AppError> {
+            Ok(self
+                .query(&["merge-base", "--is-ancestor", ancestor, of])?
+                .is_some())
+        };
+
+        match head {
+            Some(head) if is_ancestor(remote_head, &head)? => Ok(()),
+            Some(head) if !is_ancestor(&head, remote_head)? => self
+                .git(&["merge", "-q", "--no-commit", "-s", "ours", remote_head])
+                .map(|_| ()),
+            _ => self.git(&["reset", "-q", remote_head]).map(|_| ()),
+        }
+    }
+
+    /// Writes the files and commits them, unless nothing changed. Returns whether it committed.
+    pub fn commit(&self, files: &[(SyncTable, String)], message: &str) -> Result<bool, AppError> {
+        for (table, contents) in files {
+            let path = self.dir.join(table.file_name());
+            fs::write(&path, contents).map_err(|e| {
+                AppError::from(e).context(format!("Could not write {}", path.display()))
+            })?;
+            self.git(&["add", "--", table.file_name()])?;
+        }
+
+        let merging = self
+            .query(&["rev-parse", "-q", "--verify", "MERGE_HEAD"])?
+            .is_some();
+        let changed = self.query(&["diff", "--cached", "--quiet"])?.is_none();
+        if !merging && !changed {
+            return Ok(false);
+        }
+
+        self.git(&["commit", "-q", "-m", message])?;
+
+        Ok(true)
+    }
+
+    pub fn push(&self) -> Result<(), AppError> {
+        self.git(&["push", "-q", REMOTE, BRANCH])
+            .map(|_| ())
+            .map_err(|e| {
+                e.context("Could not push, another machine may have synced meanwhile, sync again")
+            })
+    }
+
+    fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new("git");
+        command.arg("-C").arg(&self.dir);
+        if self.anonymous {
+            command.args([
+                "-c",
+                "user.name=tasklog",
+                "-c",
+                "user.email=tasklog@localhost",
+            ]);
+        }
+        command.args(args);
+        command
+    }
+
+    /// Runs git, failing with what it printed on stderr.
+    fn git(&self, args: &[&str]) -> Result<String, AppError> {
+        run(&mut self.command(args))
+    }
+
+    /// Runs a git command that answers with its exit status, none when it exits with an error.
+    fn query(&self, args: &[&str]) -> Result<Option<String>, AppError> {
+        let output = output(&mut self.command(args))?;
+        Ok(output.status.success().then(|| {
+            String::from_utf8_lossy(&output.stdout)
+                .trim_end()
+                .to_string()
+        }))
+    }
+}
+
+fn output(command: &mut Command) -> Result<Output, AppError> {
+    command
+        .output()
+        .map_err(|e| AppError::from(e).context("Could not run git (is it installed?)"))
+}
+
+fn run(command: &mut Command) -> Result<String, AppError> {
+    let output = output(command)?;
+    if !output.status.success() {
+        return Err(AppError::Io(format!(
+            "git failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .trim_end()
+        .to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(uuid: &str, updated_at: &str, title: &str) -> SyncRecord {
+        SyncRecord {
+            uuid: uuid.to_string(),
+            updated_at: updated_at.to_string(),
+            fields: vec![Some(title.to_string()), None],
+        }
+    }
+
+    fn records(list: &[SyncRecord]) -> Records {
+        list.iter().map(|r| (r.uuid.clone(), r.clone())).collect()
+    }
+
+    #[test]
+    fn test_render_and_parse_round_trip() {
+        let mut task = record("a", "2026-06-15 10:00:00", "Tabs\tand\nlines \\N");
+        task.fields = SyncTable::Tasks
+            .columns()
+            .iter()
+            .enumerate()
+            .map(|(i, _)| (i % 2 == 0).then(|| task.title().to_string()))
+            .collect();
+        let tasks = records(&[task]);
+
+        let rendered = render(SyncTable::Tasks, &tasks);
+        assert_eq!(rendered.lines().count(), 3);
+        assert_eq!(parse(SyncTable::Tasks, &rendered).unwrap(), tasks);
+
+        assert!(parse(SyncTable::Sessions, &rendered).is_err());
+        assert!(parse(SyncTable::Tasks, "title\n").is_err());
+
+        // Files written before a column was added read it as NULL.
+        let older = format!(
+            "{}\nuuid\tupdated_at\ttitle\nb\t2026-06-15 10:00:00\tOld\n",
+            FORMAT_HEADER
+        );
+        let parsed = parse(SyncTable::Tasks, &older).unwrap();
+        assert_eq!(parsed["b"].title(), "Old");
+        assert!(parsed["b"].fields[1..].iter().all(Option::is_none));
+        let newer = format!("{}\nuuid\tupdated_at\ttitle\tcolor\n", FORMAT_HEADER);
+        assert!(parse(SyncTable::Tasks, &newer).is_err());
+    }
+
+    #[test]
+    fn test_merge() {
+        let base = records(&[
+            record("same", "2026-06-15 10:00:00", "Same"),
+            record("ours", "2026-06-15 10:00:00", "Ours"),
+            record("theirs", "2026-06-15 10:00:00", "Theirs"),
+            record("both", "2026-06-15 10:00:00", "Both"),
+        ]);
+        let local = records(&[
+            record("same", "2026-06-15 10:00:00", "Same"),
+            record("ours", "2026-06-15 11:00:00", "Ours, edited"),
+            record("theirs", "2026-06-15 10:00:00", "Theirs"),
+            record("both", "2026-06-15 12:00:00", "Both, edited here"),
+            record("new here", "2026-06-15 12:00:00", "New here"),
+        ]);
+        let remote = records(&[
+            record("same", "2026-06-15 10:00:00", "Same"),
+            record("ours", "2026-06-15 10:00:00", "Ours"),
+            record("theirs", "2026-06-15T11:00:00+00:00", "Theirs, edited"),
+            record("both", "2026-06-15T12:30:00+00:00", "Both, edited there"),
+            record("new there", "2026-06-15T12:00:00+00:00", "New there"),
+        ]);
+
+        let merge = merge(SyncTable::Tasks, &base, &local, &remote);
+        let pulled: Vec<&str> = merge.pulled.iter().map(|r| r.title()).collect();
+        assert_eq!(
+            pulled,
+            vec!["Both, edited there", "New there", "Theirs, edited"]
+        );
+        assert_eq!(merge.pushed, 2);
+        assert_eq!(
+            merge.conflicts,
+            vec![Conflict {
+                table: SyncTable::Tasks,
+                uuid: "both".to_string(),
+                title: "Both, edited here".to_string(),
+                kept: Side::Remote,
+            }]
+        );
+    }
+
+    fn task(uuid: &str, updated_at: &str, title: &str) -> SyncRecord {
+        let mut task = record(uuid, updated_at, title);
+        task.fields.resize(SyncTable::Tasks.columns().len(), None);
+        task
+    }
+
+    /// Syncs the tasks of one machine the way `tasklog sync` does, an offline sync commits
+    /// without fetching or pushing.
+    fn sync_tasks(repo: &SyncRepo, local: &mut Records, offline: bool) -> Merge {
+        let table = SyncTable::Tasks;
+        let head = repo.head().unwrap();
+        let remote_head = if offline { None } else { repo.fetch().unwrap() };
+        let base = match &head {
+            Some(head) => repo.records_at(head, table).unwrap(),
+            None => Records::new(),
+        };
+        let theirs = match &remote_head {
+            Some(remote_head) => repo.records_at(remote_head, table).unwrap(),
+            None => base.clone(),
+        };
+
+        let merge = merge(table, &base, local, &theirs);
+        for record in &merge.pulled {
+            local.insert(record.uuid.clone(), record.clone());
+        }
+
+        if let Some(remote_head) = &remote_head {
+            repo.join(remote_head).unwrap();
+        }
+        repo.commit(&[(table, render(table, local))], "Sync")
+            .unwrap();
+        if !offline {
+            repo.push().unwrap();
+        }
+
+        merge
+    }
+
+    #[test]
+    fn test_sync_two_clones_through_a_bare_remote() {
+        let dir = std::env::temp_dir().join(format!("tasklog-sync-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let remote = dir.join("remote.git");
+        let remote = remote.to_str().unwrap();
+        let a = SyncRepo::open(&dir.join("a"), Some(remote)).unwrap();
+        let b = SyncRepo::open(&dir.join("b"), Some(remote)).unwrap();
+        assert!(dir.join("remote.git").join("HEAD").exists());
+
+        // Fast-forward: b has nothing of its own and moves to the commit of a.
+        let mut on_a = records(&[task("x", "2026-06-15 10:00:00", "Write report")]);
+        let mut on_b = Records::new();
+        assert_eq!(sync_tasks(&a, &mut on_a, false).pushed, 1);
+        let merge = sync_tasks(&b, &mut on_b, false);
+        assert_eq!(merge.pulled.len(), 1);
+        assert_eq!(on_b, on_a);
+        assert_eq!(b.head().unwrap(), a.head().unwrap());
+
+        // Diverged: b commits offline while a pushes, b's next sync merges both histories.
+        on_b.insert("z".to_string(), task("z", "2026-06-15 11:00:00", "Call"));
+        sync_tasks(&b, &mut on_b, true);
+        on_a.insert(
+            "y".to_string(),
+            task("y", "2026-06-15 11:00:00", "Pay rent"),
+        );
+        sync_tasks(&a, &mut on_a, false);
+
+        let merge = sync_tasks(&b, &mut on_b, false);
+        assert_eq!(merge.pulled.len(), 1);
+        assert_eq!(merge.pushed, 1);
+        let merged = b.head().unwrap().unwrap();
+        let parents = b
+            .git(&["rev-list", "--parents", "-n", "1", &merged])
+            .unwrap();
+        assert_eq!(parents.split(' ').count(), 3);
+
+        sync_tasks(&a, &mut on_a, false);
+        assert_eq!(on_a.keys().collect::<Vec<_>>(), vec!["x", "y", "z"]);
+        assert_eq!(on_b, on_a);
+        assert_eq!(a.head().unwrap(), Some(merged));
+
+        // Conflict: both change the same task, the later change wins on both machines.
+        on_a.insert(
+            "x".to_string(),
+            task("x", "2026-06-15 12:00:00", "Write report, a"),
+        );
+        on_b.insert(
+            "x".to_string(),
+            task("x", "2026-06-15 12:30:00", "Write report, b"),
+        );
+        sync_tasks(&a, &mut on_a, false);
+        let merge = sync_tasks(&b, &mut on_b, false);
+        assert_eq!(
+            merge.conflicts,
+            vec![Conflict {
+                table: SyncTable::Tasks,
+                uuid: "x".to_string(),
+                title: "Write report, b".to_string(),
+                kept: Side::Local,
+            }]
+        );
+
+        let merge = sync_tasks(&a, &mut on_a, false);
+        assert!(merge.conflicts.is_empty());
+        assert_eq!(on_a["x"].title(), "Write report, b");
+        assert_eq!(on_b, on_a);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
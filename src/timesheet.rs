@@ -0,0 +1,165 @@
+use crate::models::{TimesheetFormat, TimesheetLine};
+use crate::report::{escape_md_cell, format_focus};
+
+/// Rounds the lines up to multiples of `round` seconds and renders them with a total per day and
+/// category in markdown, or one row per line in csv.
+pub fn render(lines: &[TimesheetLine], round: Option<i64>, format: TimesheetFormat) -> String {
+    let lines: Vec<TimesheetLine> = lines
+        .iter()
+        .map(|line| TimesheetLine {
+            seconds: round.map_or(line.seconds, |r| round_up(line.seconds, r)),
+            ..line.clone()
+        })
+        .collect();
+
+    match format {
+        TimesheetFormat::Csv => render_csv(&lines),
+        TimesheetFormat::Md => render_markdown(&lines),
+    }
+}
+
+/// The smallest multiple of `round` that is not below `seconds`.
+pub fn round_up(seconds: i64, round: i64) -> i64 {
+    if round <= 0 {
+        return seconds;
+    }
+    (seconds + round - 1).div_euclid(round) * round
+}
+
+/// Hours with two decimals, the unit invoicing tools expect.
+fn hours(seconds: i64) -> String {
+    format!("{:.2}", seconds as f64 / 3600.0)
+}
+
+fn render_csv(lines: &[TimesheetLine]) -> String {
+    let mut csv = String::from("date,category,task,hours\n");
+    for line in lines {
+        csv.push_str(&format!(
+            "{},{},{},{}\n",
+            line.date.format("%Y-%m-%d"),
+            csv_field(&line.category),
+            csv_field(&line.task),
+            hours(line.seconds)
+        ));
+    }
+    csv
+}
+
+/// Quotes fields holding a separator, a quote or a line break.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+fn render_markdown(lines: &[TimesheetLine]) -> String {
+    let mut md =
+        String::from("| Date | Category | Task | Hours | Time |\n|---|---|---|---:|---:|\n");
+    for line in lines {
+        md.push_str(&format!(
+            "| {} | {} | {} | {} | {} |\n",
+            line.date.format("%Y-%m-%d"),
+            escape_md_cell(&line.category),
+            escape_md_cell(&line.task),
+            hours(line.seconds),
+            format_focus(line.seconds)
+        ));
+    }
+
+    let mut days: Vec<(String, String, i64)> = Vec::new();
+    for line in lines {
+        let date = line.date.format("%Y-%m-%d").to_string();
+        match days
+            .iter_mut()
+            .find(|(d, c, _)| *d == date && *c == line.category)
+        {
+            Some((_, _, seconds)) => *seconds += line.seconds,
+            None => days.push((date, line.category.clone(), line.seconds)),
+        }
+    }
+
+    md.push_str("\n| Date | Category | Hours |\n|---|---|---:|\n");
+    for (date, category, seconds) in &days {
+        md.push_str(&format!(
+            "| {} | {} | {} |\n",
+            date,
+            escape_md_cell(category),
+            hours(*seconds)
+        ));
+    }
+
+    let total: i64 = lines.iter().map(|line| line.seconds).sum();
+    md.push_str(&format!(
+        "\n**Total: {} hours ({})**\n",
+        hours(total),
+        format_focus(total)
+    ));
+    md
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn line(date: &str, category: &str, task: &str, minutes: i64) -> TimesheetLine {
+        TimesheetLine {
+            date: NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap(),
+            category: category.to_string(),
+            task: task.to_string(),
+            seconds: minutes * 60,
+        }
+    }
+
+    #[test]
+    fn test_round_up() {
+        assert_eq!(round_up(0, 900), 0);
+        assert_eq!(round_up(1, 900), 900);
+        assert_eq!(round_up(900, 900), 900);
+        assert_eq!(round_up(901, 900), 1800);
+        assert_eq!(round_up(901, 0), 901);
+    }
+
+    #[test]
+    fn test_render_csv() {
+        let lines = vec![
+            line("2026-06-15", "acme", "#4 Release, part 2", 50),
+            line("2026-06-15", "acme", "Say \"hi\"", 20),
+        ];
+
+        assert_eq!(
+            render(&lines, Some(15 * 60), TimesheetFormat::Csv),
+            "date,category,task,hours\n\
+             2026-06-15,acme,\"#4 Release, part 2\",1.00\n\
+             2026-06-15,acme,\"Say \"\"hi\"\"\",0.50\n"
+        );
+        assert!(render(&lines, None, TimesheetFormat::Csv).contains(",0.83\n"));
+    }
+
+    #[test]
+    fn test_render_markdown_totals() {
+        let lines = vec![
+            line("2026-06-15", "acme", "Release", 50),
+            line("2026-06-15", "acme", "Support", 20),
+            line("2026-06-15", "initech", "Audit", 90),
+            line("2026-06-16", "acme", "Release", 25),
+        ];
+
+        let md = render(&lines, Some(15 * 60), TimesheetFormat::Md);
+        assert!(md.contains("| 2026-06-15 | acme | Release | 1.00 | 1h 00m |"));
+        assert!(md.contains("| 2026-06-15 | acme | 1.50 |"));
+        assert!(md.contains("| 2026-06-15 | initech | 1.50 |"));
+        assert!(md.contains("| 2026-06-16 | acme | 0.50 |"));
+        assert!(md.ends_with("**Total: 3.50 hours (3h 30m)**\n"));
+
+        let piped = render(
+            &[line("2026-06-15", "a|b", "Fix | test", 30)],
+            None,
+            TimesheetFormat::Md,
+        );
+        assert!(piped.contains("| 2026-06-15 | a\\|b | Fix \\| test | 0.50 | 30m |"));
+        assert!(piped.contains("| 2026-06-15 | a\\|b | 0.50 |"));
+    }
+}
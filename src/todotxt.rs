@@ -0,0 +1,718 @@
+use crate::error::AppError;
+use crate::helper::get_config_directory;
+use crate::models::{new_uuid, DueDate, Priority, SyncTable, Task, TaskStatus};
+use crate::sync::{Conflict, Side};
+use chrono::{DateTime, Local, NaiveDate, TimeZone};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+const TODOTXT_DIR: &str = "/todotxt";
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// A task as one line of todo.txt holds it, what todo.txt has no place for left out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TodoItem {
+    /// The `uuid:` of lines written by tasklog
+    pub uuid: Option<String>,
+    pub done: bool,
+    /// The date after the `x`, when the line has one
+    pub completed: Option<NaiveDate>,
+    /// `(A)` to `(D)`, anything lower reads as low
+    pub priority: Option<Priority>,
+    /// The date before the description, when the line has one
+    pub created: Option<NaiveDate>,
+    /// The description without the project, contexts and keys kept apart
+    pub title: String,
+    /// The first `+project`, the others stay in the title
+    pub category: Option<String>,
+    /// The `@contexts`
+    pub tags: Vec<String>,
+    pub due: Option<NaiveDate>,
+}
+
+impl TodoItem {
+    /// Reads a line, `None` for a blank one. The dates are the line's own, tasklog keeps them
+    /// only to write them back.
+    pub fn parse(line: &str) -> Result<Option<TodoItem>, AppError> {
+        let mut tokens = line.split_whitespace().peekable();
+        if tokens.peek().is_none() {
+            return Ok(None);
+        }
+
+        let is_date = |token: &&str| NaiveDate::parse_from_str(token, DATE_FORMAT).is_ok();
+        let done = tokens.next_if_eq(&"x").is_some();
+        let mut priority = tokens.next_if(|token| parse_priority(token).is_some());
+        let date = |token: &str| NaiveDate::parse_from_str(token, DATE_FORMAT).ok();
+        let first = tokens.next_if(is_date).and_then(date);
+        let (completed, created) = match done {
+            true => (first, first.and(tokens.next_if(is_date)).and_then(date)),
+            false => (None, first),
+        };
+        priority = priority.or_else(|| tokens.next_if(|token| parse_priority(token).is_some()));
+
+        let mut item = TodoItem {
+            uuid: None,
+            done,
+            completed,
+            priority: priority.and_then(parse_priority),
+            created,
+            title: String::new(),
+            category: None,
+            tags: Vec::new(),
+            due: None,
+        };
+
+        let mut words = Vec::new();
+        for token in tokens {
+            match token.split_once(':') {
+                Some(("due", date)) => {
+                    item.due = Some(NaiveDate::parse_from_str(date, DATE_FORMAT).map_err(|_| {
+                        AppError::Parse(format!("'{}' is not a date (YYYY-MM-DD)", token))
+                    })?)
+                }
+                Some(("uuid", uuid)) if !uuid.is_empty() => item.uuid = Some(uuid.to_lowercase()),
+                Some(("pri", letter)) if parse_priority(&format!("({})", letter)).is_some() => {
+                    item.priority = item
+                        .priority
+                        .or_else(|| parse_priority(&format!("({})", letter)))
+                }
+                _ => match (token.strip_prefix('+'), token.strip_prefix('@')) {
+                    (Some(project), _) if !project.is_empty() && item.category.is_none() => {
+                        item.category = Some(project.to_string())
+                    }
+                    (_, Some(context)) if !context.is_empty() => {
+                        item.tags.push(context.to_string())
+                    }
+                    _ => words.push(token),
+                },
+            }
+        }
+
+        item.title = words.join(" ");
+        if item.title.is_empty() {
+            return Err(AppError::Parse("The task has no description".to_string()));
+        }
+
+        Ok(Some(item))
+    }
+
+    /// The line of the task, its due date the day it falls on in `tz`.
+    pub fn from_task<Z: TimeZone>(task: &Task, tz: &Z) -> TodoItem {
+        TodoItem {
+            uuid: Some(task.meta.uuid.clone()).filter(|uuid| !uuid.is_empty()),
+            done: task.status == TaskStatus::Done,
+            completed: None,
+            priority: Some(task.priority),
+            created: None,
+            title: task.title.clone(),
+            category: task.category.as_ref().map(|c| without_spaces(c)),
+            tags: task.tags.clone(),
+            due: Some(task.due_date.at.with_timezone(tz).date_naive()),
+        }
+    }
+
+    /// The task with the fields of the line. A due date on the day the task is already due keeps
+    /// its time, a line without one keeps the due date of the task.
+    pub fn apply<Z: TimeZone>(&self, task: &Task, tz: &Z) -> Result<Task, AppError> {
+        let mut task = task.clone();
+        task.title = self.title.clone();
+        task.status = match self.done {
+            true => TaskStatus::Done,
+            false => TaskStatus::Open,
+        };
+        task.priority = self.priority.unwrap_or(Priority::Medium);
+        task.category = self.category.clone();
+        task.tags = self.tags.clone();
+        if let Some(due) = self.due {
+            if task.due_date.at.with_timezone(tz).date_naive() != due {
+                task.due_date = DueDate::all_day_on(due, tz)?;
+            }
+        }
+        if task.meta.uuid.is_empty() {
+            task.meta.uuid = self.uuid.clone().unwrap_or_default();
+        }
+
+        Ok(task)
+    }
+
+    /// The line of the task written the way `line`, the one it came from, has it: the dates of
+    /// the line are kept, the priority it leaves out stays out while the task is of medium
+    /// priority and so does its due date. A task done since gets the day it last changed as its
+    /// completion date when the line has a creation date, which todo.txt only reads after one.
+    pub fn from_task_like<Z: TimeZone>(task: &Task, line: &TodoItem, tz: &Z) -> TodoItem {
+        let mut item = TodoItem::from_task(task, tz);
+        if line.priority.is_none() && item.priority == Some(Priority::Medium) {
+            item.priority = None;
+        }
+        if line.due.is_none() {
+            item.due = None;
+        }
+        item.created = line.created;
+        if item.done {
+            let changed = task
+                .meta
+                .updated_at
+                .map(|at| at.with_timezone(tz).date_naive());
+            item.completed = match line.done {
+                true => line.completed,
+                false => None,
+            }
+            .or(line.created.and(changed));
+        }
+
+        item
+    }
+
+    /// Whether the line has nothing the task does not have already.
+    pub fn describes<Z: TimeZone>(&self, task: &Task, tz: &Z) -> Result<bool, AppError> {
+        let ours = TodoItem::from_task(task, tz);
+        Ok(TodoItem::from_task(&self.apply(task, tz)?, tz) == ours)
+    }
+
+    /// A new task of the line, due at the end of today when the line has no due date. A line
+    /// without uuid gets a fresh one.
+    pub fn to_task<Z: TimeZone>(&self, tz: &Z) -> Result<Task, AppError> {
+        let today = Local::now().with_timezone(tz).date_naive();
+        let task = Task {
+            due_date: DueDate::all_day_on(self.due.unwrap_or(today), tz)?,
+            ..Default::default()
+        };
+
+        let mut task = self.apply(&task, tz)?;
+        if task.meta.uuid.is_empty() {
+            task.meta.uuid = new_uuid();
+        }
+        Ok(task)
+    }
+}
+
+/// Writes the line as todo.txt has it: done tasks marked `x` with their priority kept as `pri:`,
+/// open ones starting with theirs. The category goes before the other projects of the title, to
+/// be read back as the first.
+impl Display for TodoItem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let letter = self.priority.map(priority_letter);
+        let mut tokens = Vec::new();
+        match (self.done, letter) {
+            (true, _) => tokens.push("x".to_string()),
+            (false, Some(letter)) => tokens.push(format!("({})", letter)),
+            (false, None) => {}
+        }
+        if let (true, Some(completed)) = (self.done, self.completed) {
+            tokens.push(completed.format(DATE_FORMAT).to_string());
+        }
+        if let Some(created) = self.created {
+            tokens.push(created.format(DATE_FORMAT).to_string());
+        }
+        let mut title: Vec<String> = self.title.split(' ').map(str::to_string).collect();
+        if let Some(category) = &self.category {
+            let project = title
+                .iter()
+                .position(|word| word.len() > 1 && word.starts_with('+'))
+                .unwrap_or(title.len());
+            title.insert(project, format!("+{}", category));
+        }
+        tokens.push(title.join(" "));
+        tokens.extend(self.tags.iter().map(|tag| format!("@{}", tag)));
+        if let Some(due) = self.due {
+            tokens.push(format!("due:{}", due.format(DATE_FORMAT)));
+        }
+        if let (true, Some(letter)) = (self.done, letter) {
+            tokens.push(format!("pri:{}", letter));
+        }
+        if let Some(uuid) = &self.uuid {
+            tokens.push(format!("uuid:{}", uuid));
+        }
+
+        write!(f, "{}", tokens.join(" "))
+    }
+}
+
+/// `(A)` is urgent, `(B)` high, `(C)` medium and `(D)` or lower low.
+fn parse_priority(token: &str) -> Option<Priority> {
+    match token.as_bytes() {
+        [b'(', b'A', b')'] => Some(Priority::Urgent),
+        [b'(', b'B', b')'] => Some(Priority::High),
+        [b'(', b'C', b')'] => Some(Priority::Medium),
+        [b'(', b'D'..=b'Z', b')'] => Some(Priority::Low),
+        _ => None,
+    }
+}
+
+fn priority_letter(priority: Priority) -> char {
+    match priority {
+        Priority::Urgent => 'A',
+        Priority::High => 'B',
+        Priority::Medium => 'C',
+        Priority::Low => 'D',
+    }
+}
+
+/// A `+project` ends at the first space, so the ones of a category are written as `_`.
+fn without_spaces(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join("_")
+}
+
+/// The items of a todo.txt file, blank lines skipped.
+pub fn parse(contents: &str) -> Result<Vec<TodoItem>, AppError> {
+    let mut items = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        if let Some(item) =
+            TodoItem::parse(line).map_err(|e| e.context(format!("line {}", i + 1)))?
+        {
+            items.push(item);
+        }
+    }
+
+    Ok(items)
+}
+
+/// One line per task, in the order the tasks were added. A task with one of `lines`, by uuid, is
+/// written the way that line has it.
+pub fn render<Z: TimeZone>(tasks: &[Task], lines: &BTreeMap<String, TodoItem>, tz: &Z) -> String {
+    tasks
+        .iter()
+        .map(|task| match lines.get(&task.meta.uuid) {
+            Some(line) => format!("{}\n", TodoItem::from_task_like(task, line, tz)),
+            None => format!("{}\n", TodoItem::from_task(task, tz)),
+        })
+        .collect()
+}
+
+/// Where `sync todotxt` keeps the file as it wrote it last, `~/.tasklog/todotxt/` followed by the
+/// path of the file with its separators turned into `%`.
+pub fn base_path(file: &Path) -> Result<PathBuf, AppError> {
+    let file = std::path::absolute(file)
+        .map_err(|e| AppError::from(e).context(format!("Could not resolve {}", file.display())))?;
+    let name = file
+        .to_string_lossy()
+        .trim_start_matches('/')
+        .replace('/', "%");
+
+    Ok(PathBuf::from(get_config_directory()? + TODOTXT_DIR).join(name))
+}
+
+/// What `sync todotxt` changes to bring the tasks and the file up to date with each other.
+#[derive(Debug, Default)]
+pub struct Reconcile {
+    /// Lines that are no task yet
+    pub added: Vec<Task>,
+    /// Tasks whose line changed
+    pub updated: Vec<Task>,
+    /// How many tasks changed here and not in the file
+    pub pushed: usize,
+    /// Tasks changed on both sides, `Side::Remote` standing for the file
+    pub conflicts: Vec<Conflict>,
+    /// The line of each task in the file by uuid, for `render` to write it back the same way
+    pub lines: BTreeMap<String, TodoItem>,
+    /// Tasks whose line was removed from the file since a sync wrote it, by uuid
+    pub archived: BTreeSet<String>,
+}
+
+/// Compares the lines of the file with the tasks and with `base`, the tasks as the last sync wrote
+/// them with every field. A side changed since then takes over the other, a task changed on both
+/// keeps the most recent change, the file counting as changed when it was last written. A line
+/// without due date leaves the one of the task as it is. Tasks are never deleted: a line gone from
+/// the file was archived, as todo.txt moves done tasks to done.txt, and its task is not written
+/// again. Of the tasks the file has never had only the open ones are written to it.
+pub fn reconcile<Z: TimeZone>(
+    items: &[TodoItem],
+    base: &[TodoItem],
+    tasks: &[Task],
+    file_modified: Option<DateTime<Local>>,
+    tz: &Z,
+) -> Result<Reconcile, AppError> {
+    let base: BTreeMap<&str, &TodoItem> = base
+        .iter()
+        .filter_map(|item| Some((item.uuid.as_deref()?, item)))
+        .collect();
+    let tasks: BTreeMap<&str, &Task> = tasks
+        .iter()
+        .map(|task| (task.meta.uuid.as_str(), task))
+        .collect();
+
+    let referenced: BTreeSet<&str> = items.iter().filter_map(|i| i.uuid.as_deref()).collect();
+
+    let mut reconcile = Reconcile::default();
+    let mut seen = BTreeSet::new();
+    for item in items {
+        let task = match item.uuid.as_deref() {
+            Some(uuid) if seen.insert(uuid) => match tasks.get(uuid) {
+                Some(task) => *task,
+                // Added by another tasklog, it keeps its uuid here.
+                None => {
+                    reconcile.add(item, tz)?;
+                    continue;
+                }
+            },
+            // A line copied along with its uuid makes a task of its own.
+            Some(_) => {
+                let new = TodoItem {
+                    uuid: None,
+                    ..item.clone()
+                };
+                reconcile.add(&new, tz)?;
+                continue;
+            }
+            // A line without uuid is a task already here when it says all the task does, as the
+            // lines of an imported file do. The uuid is written to the line.
+            None => {
+                let mut same = None;
+                for (uuid, task) in &tasks {
+                    if !referenced.contains(uuid)
+                        && !seen.contains(uuid)
+                        && item.describes(task, tz)?
+                    {
+                        same = Some(*uuid);
+                        break;
+                    }
+                }
+                match same {
+                    Some(uuid) => {
+                        seen.insert(uuid);
+                        reconcile.lines.insert(uuid.to_string(), item.clone());
+                        reconcile.pushed += 1;
+                    }
+                    None => reconcile.add(item, tz)?,
+                }
+                continue;
+            }
+        };
+
+        let ours = TodoItem::from_task(task, tz);
+        let theirs = TodoItem::from_task(&item.apply(task, tz)?, tz);
+        let base = base.get(task.meta.uuid.as_str()).copied();
+
+        // A due date changed here is written even to a line that had none.
+        let mut line = item.clone();
+        if item.due.is_none() && base.is_some_and(|base| base.due != ours.due) {
+            line.due = ours.due;
+        }
+        reconcile.lines.insert(task.meta.uuid.clone(), line);
+        if theirs == ours {
+            continue;
+        }
+
+        let changed_here = base != Some(&ours);
+        let changed_there = base != Some(&theirs);
+        let kept = match (changed_here, changed_there) {
+            (false, _) => Side::Remote,
+            (true, false) => Side::Local,
+            (true, true) => {
+                let kept = match file_modified > task.meta.updated_at {
+                    true => Side::Remote,
+                    false => Side::Local,
+                };
+                reconcile.conflicts.push(Conflict {
+                    table: SyncTable::Tasks,
+                    uuid: task.meta.uuid.clone(),
+                    title: task.title.clone(),
+                    kept,
+                });
+                kept
+            }
+        };
+
+        match kept {
+            Side::Remote => reconcile.updated.push(item.apply(task, tz)?),
+            Side::Local => reconcile.pushed += 1,
+        }
+    }
+    for (uuid, task) in tasks.iter().filter(|(uuid, _)| !seen.contains(*uuid)) {
+        if base.contains_key(uuid) {
+            reconcile.archived.insert(uuid.to_string());
+        } else if task.status == TaskStatus::Open {
+            reconcile.pushed += 1;
+        }
+    }
+
+    Ok(reconcile)
+}
+
+impl Reconcile {
+    /// Whether the task goes into the file: it has a line there, or it is open and was never
+    /// archived from it.
+    pub fn writes(&self, task: &Task) -> bool {
+        self.lines.contains_key(&task.meta.uuid)
+            || (task.status == TaskStatus::Open && !self.archived.contains(&task.meta.uuid))
+    }
+
+    /// Whether the task goes into the base file, which also keeps the archived tasks to tell them
+    /// from the ones the file has never had.
+    pub fn remembers(&self, task: &Task) -> bool {
+        self.writes(task) || self.archived.contains(&task.meta.uuid)
+    }
+
+    /// Adds the task of a line that is no task yet.
+    fn add<Z: TimeZone>(&mut self, item: &TodoItem, tz: &Z) -> Result<(), AppError> {
+        let task = item.to_task(tz)?;
+        self.lines.insert(task.meta.uuid.clone(), item.clone());
+        self.added.push(task);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::RecordMeta;
+    use chrono::Utc;
+
+    fn task(uuid: &str, title: &str) -> Task {
+        Task {
+            title: title.to_string(),
+            due_date: DueDate::all_day_on(NaiveDate::from_ymd_opt(2026, 6, 15).unwrap(), &Utc)
+                .unwrap(),
+            meta: RecordMeta {
+                uuid: uuid.to_string(),
+                updated_at: Some(Local::now()),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn item(line: &str) -> TodoItem {
+        TodoItem::parse(line).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_parse_line() {
+        let parsed = item("(A) 2026-06-01 Call mom +family @phone @home due:2026-06-20 t:later");
+        assert_eq!(parsed.priority, Some(Priority::Urgent));
+        assert_eq!(parsed.title, "Call mom t:later");
+        assert_eq!(parsed.category.as_deref(), Some("family"));
+        assert_eq!(parsed.tags, vec!["phone", "home"]);
+        assert_eq!(parsed.due, NaiveDate::from_ymd_opt(2026, 6, 20));
+        assert_eq!(parsed.created, NaiveDate::from_ymd_opt(2026, 6, 1));
+        assert!(!parsed.done);
+
+        let done = item("x 2026-06-02 2026-06-01 Pay rent +home +bills pri:B uuid:AB12");
+        assert!(done.done);
+        assert_eq!(done.priority, Some(Priority::High));
+        assert_eq!(done.title, "Pay rent +bills");
+        assert_eq!(done.uuid.as_deref(), Some("ab12"));
+        assert_eq!(done.completed, NaiveDate::from_ymd_opt(2026, 6, 2));
+        assert_eq!(done.created, NaiveDate::from_ymd_opt(2026, 6, 1));
+        assert_eq!(item("x 2026-06-02 Pay rent").created, None);
+
+        assert_eq!(item("(F) Someday").priority, Some(Priority::Low));
+        assert_eq!(item("xylophone lessons").title, "xylophone lessons");
+        assert_eq!(TodoItem::parse("   ").unwrap(), None);
+        assert!(TodoItem::parse("(A) +project").is_err());
+        assert!(TodoItem::parse("Call due:soon").is_err());
+        assert_eq!(
+            parse("Call\n\n+x\n").unwrap_err().to_string(),
+            "line 3: The task has no description"
+        );
+    }
+
+    #[test]
+    fn test_render_round_trip() {
+        let mut done = task("1234", "Pay rent");
+        done.status = TaskStatus::Done;
+        done.priority = Priority::High;
+        done.category = Some("home stuff".to_string());
+        done.tags = vec!["bank".to_string()];
+        let open = task("5678", "Call mom");
+
+        let rendered = render(&[done.clone(), open.clone()], &BTreeMap::new(), &Utc);
+        assert_eq!(
+            rendered,
+            "x Pay rent +home_stuff @bank due:2026-06-15 pri:B uuid:1234\n\
+             (C) Call mom due:2026-06-15 uuid:5678\n"
+        );
+
+        let items = parse(&rendered).unwrap();
+        assert_eq!(items[0], TodoItem::from_task(&done, &Utc));
+        let applied = items[1].apply(&open, &Utc).unwrap();
+        assert_eq!(applied.due_date, open.due_date);
+        assert_eq!(applied.title, open.title);
+    }
+
+    #[test]
+    fn test_render_like_line() {
+        let lines = [
+            "2026-10-01 Call +a +b t:2026-10-20 @phone",
+            "Water the plants",
+            "(A) 2026-10-01 Plan the +work +trip due:2026-10-20",
+            "x 2026-10-03 2026-10-01 Pay rent +home",
+        ];
+        for line in lines {
+            let item = item(line);
+            let mut task = item.to_task(&Utc).unwrap();
+            assert_eq!(task.category, item.category);
+            let uuid = task.meta.uuid.clone();
+            let written = BTreeMap::from([(uuid.clone(), item)]);
+            assert_eq!(
+                render(&[task.clone()], &written, &Utc),
+                format!("{} uuid:{}\n", line, uuid)
+            );
+
+            // What changes here is written over what the line left out.
+            task.priority = Priority::High;
+            let rendered = render(&[task], &written, &Utc);
+            assert!(rendered.contains("pri:B") || rendered.starts_with("(B) "));
+        }
+
+        // A task done since keeps the creation date of its line.
+        let open = item("2026-10-01 Call mom");
+        let mut task = open.to_task(&Utc).unwrap();
+        task.status = TaskStatus::Done;
+        task.meta.updated_at = Some(Local.with_ymd_and_hms(2026, 10, 5, 12, 0, 0).unwrap());
+        let written = BTreeMap::from([(task.meta.uuid.clone(), open)]);
+        let done = item(render(&[task], &written, &Utc).trim());
+        assert_eq!(done.completed, NaiveDate::from_ymd_opt(2026, 10, 5));
+        assert_eq!(done.created, NaiveDate::from_ymd_opt(2026, 10, 1));
+    }
+
+    #[test]
+    fn test_reconcile() {
+        let tasks = vec![
+            task("same", "Same"),
+            task("ours", "Ours, edited"),
+            task("theirs", "Theirs"),
+            task("both", "Both, edited here"),
+            task("gone", "Gone from the file"),
+        ];
+        let base = parse(&render(
+            &[
+                task("same", "Same"),
+                task("ours", "Ours"),
+                task("theirs", "Theirs"),
+                task("both", "Both"),
+                task("gone", "Gone from the file"),
+            ],
+            &BTreeMap::new(),
+            &Utc,
+        ))
+        .unwrap();
+        let lines = [
+            "(C) Same due:2026-06-15 uuid:same",
+            "(C) Ours due:2026-06-15 uuid:ours",
+            "x (C) Theirs due:2026-06-15 uuid:theirs",
+            "(A) Both, edited there due:2026-06-15 uuid:both",
+            "New task +errands",
+            "Copied due:2026-06-15 uuid:same",
+            // Imported earlier, the line lacks the uuid of the task.
+            "Gone from the file",
+        ];
+        let items = parse(&lines.join("\n")).unwrap();
+
+        // The file was written before the tasks changed, this machine wins the conflict.
+        let earlier = Some(Local::now() - chrono::Duration::hours(1));
+        let merged = reconcile(&items, &base, &tasks, earlier, &Utc).unwrap();
+        assert_eq!(merged.added.len(), 2);
+        assert_eq!(merged.added[0].category.as_deref(), Some("errands"));
+        assert!(!["", "same"].contains(&merged.added[1].meta.uuid.as_str()));
+        assert_eq!(merged.updated.len(), 1);
+        assert_eq!(merged.updated[0].status, TaskStatus::Done);
+        assert_eq!(merged.pushed, 3);
+        assert_eq!(merged.conflicts.len(), 1);
+        assert_eq!(merged.conflicts[0].kept, Side::Local);
+
+        let later = Some(Local::now() + chrono::Duration::hours(1));
+        let merged = reconcile(&items, &base, &tasks, later, &Utc).unwrap();
+        assert_eq!(merged.updated.len(), 2);
+        assert_eq!(merged.updated[1].title, "Both, edited there");
+        assert_eq!(merged.updated[1].priority, Priority::Urgent);
+        assert_eq!(merged.conflicts[0].kept, Side::Remote);
+    }
+
+    #[test]
+    fn test_reconcile_archived_lines() {
+        let mut done = task("done", "Pay rent");
+        done.status = TaskStatus::Done;
+        let mut long_done = task("long-done", "File the taxes");
+        long_done.status = TaskStatus::Done;
+        let tasks = vec![
+            done.clone(),
+            task("dropped", "Call the bank"),
+            task("kept", "Water the plants"),
+            long_done,
+            task("new", "Book the flights"),
+        ];
+        let base = parse(&render(
+            &[
+                done,
+                task("dropped", "Call the bank"),
+                task("kept", "Water the plants"),
+            ],
+            &BTreeMap::new(),
+            &Utc,
+        ))
+        .unwrap();
+        let items = parse("(C) Water the plants due:2026-06-15 uuid:kept").unwrap();
+
+        let merged = reconcile(&items, &base, &tasks, None, &Utc).unwrap();
+        assert!(merged.added.is_empty() && merged.updated.is_empty());
+        assert_eq!(merged.pushed, 1);
+        let archived: Vec<&str> = merged.archived.iter().map(String::as_str).collect();
+        assert_eq!(archived, vec!["done", "dropped"]);
+
+        let uuids = |keep: &dyn Fn(&Task) -> bool| {
+            tasks
+                .iter()
+                .filter(|task| keep(task))
+                .map(|task| task.meta.uuid.as_str())
+                .collect::<Vec<_>>()
+        };
+        // Neither the archived lines nor the task done before the file knew it are written.
+        assert_eq!(uuids(&|task| merged.writes(task)), vec!["kept", "new"]);
+        assert_eq!(
+            uuids(&|task| merged.remembers(task)),
+            vec!["done", "dropped", "kept", "new"]
+        );
+
+        // The next sync still knows them as archived.
+        let base = parse(&render(
+            &tasks
+                .iter()
+                .filter(|task| merged.remembers(task))
+                .cloned()
+                .collect::<Vec<_>>(),
+            &BTreeMap::new(),
+            &Utc,
+        ))
+        .unwrap();
+        let items = parse(&render(
+            &tasks
+                .iter()
+                .filter(|task| merged.writes(task))
+                .cloned()
+                .collect::<Vec<_>>(),
+            &merged.lines,
+            &Utc,
+        ))
+        .unwrap();
+        let merged = reconcile(&items, &base, &tasks, None, &Utc).unwrap();
+        assert_eq!(merged.pushed, 0);
+        assert_eq!(uuids(&|task| merged.writes(task)), vec!["kept", "new"]);
+    }
+
+    #[test]
+    fn test_reconcile_undated_line() {
+        let mut task = task("undated", "Water the plants");
+        let base = parse(&render(&[task.clone()], &BTreeMap::new(), &Utc)).unwrap();
+        let items = parse("Water the plants uuid:undated").unwrap();
+
+        // The line leaves the due date of the task alone.
+        let merged = reconcile(&items, &base, &[task.clone()], None, &Utc).unwrap();
+        assert!(merged.updated.is_empty() && merged.conflicts.is_empty());
+        assert_eq!(
+            render(&[task.clone()], &merged.lines, &Utc),
+            "Water the plants uuid:undated\n"
+        );
+
+        // Until it changes here.
+        task.due_date =
+            DueDate::all_day_on(NaiveDate::from_ymd_opt(2026, 6, 20).unwrap(), &Utc).unwrap();
+        let merged = reconcile(&items, &base, &[task.clone()], None, &Utc).unwrap();
+        assert!(merged.updated.is_empty());
+        assert_eq!(
+            render(&[task], &merged.lines, &Utc),
+            "Water the plants due:2026-06-20 uuid:undated\n"
+        );
+    }
+}
@@ -0,0 +1,604 @@
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    execute, queue,
+    style::Print,
+    terminal::{self, Clear, ClearType},
+};
+use std::{
+    io::{self, Stdout, Write},
+    sync::mpsc::{Receiver, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+use tasklog::models::{
+    format_string_with_color, ratio_bar, AppState, Color, PomodoroEvent, TableRow, Task,
+};
+
+const BOX_WIDTH: u16 = 40;
+const BOX_HEIGHT: u16 = 4;
+const MAX_DIGIT_SCALE: usize = 3;
+const PROGRESS_BAR_WIDTH: usize = 50;
+
+const COUNTDOWN_HELP: &[&str] = &[
+    "+ / -   add / remove a minute",
+    "s       skip to the next session",
+    "n / e   note an internal / external interruption",
+    "?       toggle this help",
+    "q       quit",
+];
+
+/// Asks a yes or no question on stdout, anything but `y` or `yes` is a no.
+pub fn confirm(question: &str) -> io::Result<bool> {
+    print!("{} [y/N] ", question);
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Prints the items as a table under the headers of the first one, nothing when there are none.
+pub fn print_tables(items: &Vec<Box<dyn TableRow>>) {
+    let first_item = match items.first() {
+        None => return,
+        Some(item) => item,
+    };
+
+    let headers = first_item.headers();
+    let mut col_widths = headers.iter().map(|h| h.len()).collect::<Vec<usize>>();
+
+    // Padding counts characters, so measure in characters too.
+    for item in items {
+        let row = item.row();
+        for (i, cell) in row.iter().enumerate() {
+            if i < col_widths.len() {
+                col_widths[i] = col_widths[i].max(cell.chars().count());
+            }
+        }
+    }
+
+    print!("| ");
+    for (i, header) in headers.iter().enumerate() {
+        print!("{:<width$} | ", header, width = col_widths[i]);
+    }
+    println!();
+
+    // Print separator row
+    print!("|");
+    for width in &col_widths {
+        print!("-{}-|", "-".repeat(*width));
+    }
+    println!();
+
+    // Print each data row
+    for item in items {
+        let row = item.row();
+        print!("| ");
+        for (i, cell) in row.iter().enumerate() {
+            print!("{:<width$} | ", cell, width = col_widths[i]);
+        }
+        println!();
+    }
+}
+
+/// Prints the tasks as a table, nothing when there are none.
+pub fn print_tasks_table(tasks: &Vec<Task>) {
+    if tasks.is_empty() {
+        return;
+    }
+
+    // Define the column headers
+    let headers = ["id", "title", "status", "due_date", "priority", "category"];
+
+    // Calculate the width of each column based on content
+    let mut col_widths = vec![
+        headers[0].len(), // id
+        headers[1].len(), // title
+        headers[2].len(), // status
+        headers[3].len(), // due_date
+        headers[4].len(), // priority
+        headers[5].len(), // category
+    ];
+
+    // Update the column widths based on the task data
+    for task in tasks {
+        // ID column width
+        col_widths[0] = col_widths[0].max(task.id.to_string().len());
+
+        // Title column width
+        col_widths[1] = col_widths[1].max(task.title.len());
+
+        // Status column width
+        col_widths[2] = col_widths[2].max(format!("{:?}", task.status).len());
+
+        // Due date column width
+        col_widths[3] = col_widths[3].max(task.due_date.to_string().len());
+
+        // Priority column width
+        col_widths[4] = col_widths[4].max(format!("{:?}", task.priority).len());
+
+        // Category column width
+        let category_str = task.category.as_ref().map_or("", |s| s.as_str());
+        col_widths[5] = col_widths[5].max(category_str.len());
+    }
+
+    // Print header row with proper padding
+    print!("| ");
+    for (i, header) in headers.iter().enumerate() {
+        print!("{:<width$} | ", header, width = col_widths[i]);
+    }
+    println!();
+
+    // Print separator row
+    print!("|");
+    for width in &col_widths {
+        print!("-{}-|", "-".repeat(*width));
+    }
+    println!();
+
+    // Print each task row
+    for task in tasks {
+        print!("| ");
+        // ID column
+        print!("{:<width$} | ", task.id, width = col_widths[0]);
+
+        // Title column
+        print!("{:<width$} | ", task.title, width = col_widths[1]);
+
+        // Status column
+        print!(
+            "{:<width$} | ",
+            format!("{:?}", task.status),
+            width = col_widths[2]
+        );
+
+        // Due date column
+        print!(
+            "{:<width$} | ",
+            task.due_date.to_string(),
+            width = col_widths[3]
+        );
+
+        // Priority column
+        print!(
+            "{:<width$} | ",
+            format!("{:?}", task.priority),
+            width = col_widths[4]
+        );
+
+        // Category column
+        let category_str = task.category.as_ref().map_or("", |s| s.as_str());
+        print!("{:<width$} | ", category_str, width = col_widths[5]);
+
+        println!();
+    }
+}
+
+pub fn clear_terminal_screen() -> io::Result<()> {
+    let mut stout = std::io::stdout();
+    execute!(stout, Clear(ClearType::All), cursor::MoveTo(0, 0),)
+}
+
+/// Draws the countdown: the remaining time in block digits scaled to the terminal, the category
+/// and linked task of the session and a bar of the time elapsed so far. Falls back to plain text
+/// when the terminal is too small for the digits.
+pub fn draw_ui(stdout: &mut Stdout, state: &AppState) -> io::Result<()> {
+    let total_seconds = state.current_time.as_secs();
+    let minutes = total_seconds / 60;
+    let seconds = total_seconds % 60;
+    let time_str = if state.stopwatch && minutes >= 60 {
+        format!("{}:{:02}:{:02}", minutes / 60, minutes % 60, seconds)
+    } else {
+        format!("{:02}:{:02}", minutes, seconds)
+    };
+
+    let width = state.term_width as usize;
+    let mut rows = vec![(state.title.clone(), Color::Cyan)];
+
+    let context: Vec<String> = state
+        .category
+        .iter()
+        .map(|category| format!("[{}]", category))
+        .chain(state.task.clone())
+        .collect();
+    if !context.is_empty() {
+        rows.push((context.join("  "), Color::Green));
+    }
+
+    // Title, context, the bar, the line below it and the blank lines around the digits.
+    let reserved_rows = rows.len() + 4;
+    let scale = digit_scale(
+        &time_str,
+        width.saturating_sub(2),
+        (state.term_height as usize).saturating_sub(reserved_rows),
+    );
+
+    rows.push((String::new(), Color::Yellow));
+    if state.show_help {
+        for line in COUNTDOWN_HELP {
+            rows.push((format!("{:<48}", line), Color::Green));
+        }
+    } else if scale == 0 {
+        rows.push((time_str, Color::Yellow));
+    } else {
+        for line in big_digits(&time_str, scale) {
+            rows.push((line, Color::Yellow));
+        }
+    }
+    rows.push((String::new(), Color::Yellow));
+
+    // A stopwatch has no end to show progress towards.
+    if state.stopwatch {
+        rows.push(("q stop".to_string(), Color::Cyan));
+    } else {
+        let elapsed = state.duration.saturating_sub(state.current_time);
+        rows.push((
+            progress_bar(
+                elapsed,
+                state.duration,
+                width.saturating_sub(6).min(PROGRESS_BAR_WIDTH),
+            ),
+            Color::Cyan,
+        ));
+
+        match &state.note {
+            Some((kind, note)) => rows.push((
+                format!("{} interruption: {}_", String::from(*kind), note),
+                Color::Yellow,
+            )),
+            None => rows.push(("? help".to_string(), Color::Cyan)),
+        }
+    }
+
+    queue!(stdout, terminal::Clear(ClearType::All))?;
+
+    let start_row = (state.term_height as usize).saturating_sub(rows.len()) / 2;
+    for (i, (text, color)) in rows.iter().take(state.term_height as usize).enumerate() {
+        let text = text.chars().take(width).collect::<String>();
+        let col = width.saturating_sub(text.chars().count()) / 2;
+
+        queue!(
+            stdout,
+            cursor::MoveTo(col as u16, (start_row + i) as u16),
+            Print(format_string_with_color(&text, *color))
+        )?;
+    }
+
+    stdout.flush()
+}
+
+/// Draws the time's up screen with the progress of the goals and the end of session choices,
+/// `inverted` flashes the box.
+pub fn draw_end_ui(
+    stdout: &mut Stdout,
+    state: &AppState,
+    goals: &[String],
+    choices: &str,
+    inverted: bool,
+) -> io::Result<()> {
+    let mut lines = vec![
+        (state.title.as_str(), Color::Cyan),
+        ("Time's up!", Color::Green),
+    ];
+    if !goals.is_empty() {
+        lines.push(("", Color::Green));
+        lines.extend(goals.iter().map(|goal| (goal.as_str(), Color::Green)));
+        lines.push(("", Color::Green));
+    }
+    lines.push((choices, Color::Yellow));
+
+    draw_box(stdout, state, &lines, inverted)
+}
+
+/// Clears the screen and draws the lines centered in a bordered box in the middle of it.
+fn draw_box(
+    stdout: &mut Stdout,
+    state: &AppState,
+    lines: &[(&str, Color)],
+    inverted: bool,
+) -> io::Result<()> {
+    queue!(stdout, terminal::Clear(ClearType::All))?;
+
+    let box_height = BOX_HEIGHT + lines.len().saturating_sub(2) as u16;
+    let box_width = BOX_WIDTH.min(state.term_width);
+
+    let box_start_col = (state.term_width - box_width) / 2;
+
+    let box_start_row = if state.term_height >= box_height {
+        (state.term_height - box_height) / 2
+    } else {
+        0
+    };
+
+    let content_inner_width = (box_width.saturating_sub(2)) as usize;
+
+    let border_line = "-".repeat(box_width as usize);
+
+    let mut rows = vec![border_line.clone()];
+    for (text, color) in lines {
+        let text_to_display = text.chars().take(content_inner_width).collect::<String>();
+        let mut padded = format!("{:^width$}", text_to_display, width = content_inner_width);
+        padded = format_string_with_color(padded.as_str(), *color);
+        rows.push(format!("|{}|", padded));
+    }
+    rows.push(border_line);
+
+    for (i, row) in rows.iter().enumerate() {
+        let row = if inverted {
+            format!("\x1b[7m{}\x1b[27m", row)
+        } else {
+            row.clone()
+        };
+
+        queue!(
+            stdout,
+            cursor::MoveTo(box_start_col, box_start_row + i as u16),
+            Print(row)
+        )?;
+    }
+
+    stdout.flush()
+}
+
+/// Rows of a 3x5 block font, `big_digits` doubles every cell horizontally as terminal cells are
+/// about twice as tall as they are wide.
+fn glyph(c: char) -> [&'static str; 5] {
+    match c {
+        '0' => ["###", "# #", "# #", "# #", "###"],
+        '1' => [" # ", "## ", " # ", " # ", "###"],
+        '2' => ["###", "  #", "###", "#  ", "###"],
+        '3' => ["###", "  #", "###", "  #", "###"],
+        '4' => ["# #", "# #", "###", "  #", "  #"],
+        '5' => ["###", "#  ", "###", "  #", "###"],
+        '6' => ["###", "#  ", "###", "# #", "###"],
+        '7' => ["###", "  #", "  #", "  #", "  #"],
+        '8' => ["###", "# #", "###", "# #", "###"],
+        '9' => ["###", "# #", "###", "  #", "###"],
+        ':' => [" ", "#", " ", "#", " "],
+        _ => ["   ", "   ", "   ", "   ", "   "],
+    }
+}
+
+/// The text in block digits, every cell drawn `scale` times as wide and as tall.
+pub fn big_digits(text: &str, scale: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for row in 0..5 {
+        let line = text
+            .chars()
+            .map(|c| {
+                glyph(c)[row]
+                    .chars()
+                    .map(|cell| {
+                        let cell = if cell == '#' { "█" } else { " " };
+                        cell.repeat(2 * scale)
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<String>>()
+            .join(&" ".repeat(2 * scale));
+
+        for _ in 0..scale {
+            lines.push(line.clone());
+        }
+    }
+
+    lines
+}
+
+/// The largest scale, up to MAX_DIGIT_SCALE, at which the block digits of the text fit, 0 when
+/// they do not fit at all.
+pub fn digit_scale(text: &str, width: usize, height: usize) -> usize {
+    let unit_width = big_digits(text, 1)
+        .first()
+        .map(|line| line.chars().count())
+        .unwrap_or(0);
+    if unit_width == 0 {
+        return 0;
+    }
+
+    (width / unit_width).min(height / 5).min(MAX_DIGIT_SCALE)
+}
+
+/// A bar of `width` cells filled as far as `elapsed` got into `total`, followed by the percentage.
+pub fn progress_bar(elapsed: Duration, total: Duration, width: usize) -> String {
+    let ratio = if total.is_zero() {
+        1.0
+    } else {
+        elapsed.as_secs_f64() / total.as_secs_f64()
+    };
+
+    ratio_bar(ratio, width)
+}
+
+/// Counts down to `deadline`, sending the time left in whole seconds whenever it changes. The
+/// deadline is a monotonic instant, so a busy machine delays the updates but never the countdown.
+pub fn run_timer_thread(
+    deadline: Instant,
+    time_update_tx: Sender<Duration>,
+    quit_rx: Receiver<()>,
+) {
+    let mut last_sent = None;
+    loop {
+        // Check for quit signal non-blockingly.
+        if quit_rx.try_recv().is_ok() {
+            break; // Exit if quit signal received.
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let shown = whole_seconds(remaining);
+        if last_sent != Some(shown) {
+            // Send the current time to the main thread.
+            if time_update_tx.send(shown).is_err() {
+                break; // Main thread likely terminated.
+            }
+            last_sent = Some(shown);
+        }
+
+        // Stop if countdown reaches zero.
+        if remaining.is_zero() {
+            break;
+        }
+
+        // Sleep in small intervals to stay responsive to the quit signal.
+        thread::sleep(remaining.min(Duration::from_millis(100)));
+    }
+}
+
+/// Rounds up to whole seconds, a countdown shows 00:01 until the very end.
+pub fn whole_seconds(duration: Duration) -> Duration {
+    let seconds = duration.as_secs();
+    if duration.subsec_nanos() > 0 {
+        Duration::from_secs(seconds + 1)
+    } else {
+        Duration::from_secs(seconds)
+    }
+}
+
+pub fn run_event_thread(event_tx: Sender<PomodoroEvent>, quit_rx: Receiver<()>) {
+    loop {
+        // Check for quit signal non-blockingly.
+        if quit_rx.try_recv().is_ok() {
+            break; // Exit if quit signal received.
+        }
+
+        // Poll for terminal events with a timeout.
+        if event::poll(Duration::from_millis(200)).unwrap_or(false) {
+            match event::read() {
+                // For killing the app use 'ctrl+c', 'q' is handed over as a key so it can be typed
+                // into notes
+                Ok(Event::Key(KeyEvent {
+                    code: KeyCode::Char('c'),
+                    modifiers: KeyModifiers::CONTROL,
+                    ..
+                })) => {
+                    if event_tx.send(PomodoroEvent::Quit).is_err() {
+                        break; // Main thread likely terminated.
+                    }
+                    // Once quit is sent, this thread can exit.
+                    break;
+                }
+
+                // Any other key is handed to the main thread
+                Ok(Event::Key(KeyEvent {
+                    code: KeyCode::Char(c),
+                    ..
+                })) if event_tx.send(PomodoroEvent::Key(c)).is_err() => {
+                    break; // Main thread likely terminated.
+                }
+                Ok(Event::Key(KeyEvent {
+                    code: KeyCode::Enter,
+                    ..
+                })) if event_tx.send(PomodoroEvent::Key('\n')).is_err() => {
+                    break; // Main thread likely terminated.
+                }
+                Ok(Event::Key(KeyEvent {
+                    code: KeyCode::Backspace,
+                    ..
+                })) if event_tx.send(PomodoroEvent::Key('\x08')).is_err() => {
+                    break; // Main thread likely terminated.
+                }
+                Ok(Event::Key(KeyEvent {
+                    code: KeyCode::Esc, ..
+                })) if event_tx.send(PomodoroEvent::Key('\x1b')).is_err() => {
+                    break; // Main thread likely terminated.
+                }
+
+                // For resizing the terminal
+                Ok(Event::Resize(width, height))
+                    if event_tx.send(PomodoroEvent::Resize(width, height)).is_err() =>
+                {
+                    break; // Main thread likely terminated.
+                }
+
+                // any error should kill the event thread
+                Err(_) => {
+                    // Error reading event, could signal this or just break.
+                    let _ = event_tx.send(PomodoroEvent::Quit); // Signal main to quit on error
+                    break;
+                }
+                _ => {} // Ignore other events.
+            }
+        }
+        // No explicit sleep here as event::poll has a timeout.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_whole_seconds() {
+        assert_eq!(whole_seconds(Duration::ZERO), Duration::ZERO);
+        assert_eq!(
+            whole_seconds(Duration::from_millis(1)),
+            Duration::from_secs(1)
+        );
+        assert_eq!(
+            whole_seconds(Duration::from_millis(59_000)),
+            Duration::from_secs(59)
+        );
+        assert_eq!(
+            whole_seconds(Duration::from_millis(59_001)),
+            Duration::from_secs(60)
+        );
+    }
+
+    #[test]
+    fn test_timer_thread_counts_to_deadline() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let (_quit_tx, quit_rx) = std::sync::mpsc::channel();
+
+        run_timer_thread(Instant::now() + Duration::from_millis(1500), tx, quit_rx);
+
+        let updates: Vec<Duration> = rx.try_iter().collect();
+        assert_eq!(
+            updates,
+            vec![
+                Duration::from_secs(2),
+                Duration::from_secs(1),
+                Duration::ZERO
+            ]
+        );
+    }
+
+    #[test]
+    fn test_big_digits() {
+        let lines = big_digits("1:0", 1);
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0], "  ██        ██████");
+        assert_eq!(lines[1], "████    ██  ██  ██");
+        assert!(lines.iter().all(|l| l.chars().count() == 18));
+
+        let scaled = big_digits("25:00", 2);
+        assert_eq!(scaled.len(), 10);
+        assert_eq!(scaled[0], scaled[1]);
+        assert_eq!(
+            scaled[0].chars().count(),
+            2 * big_digits("25:00", 1)[0].chars().count()
+        );
+    }
+
+    #[test]
+    fn test_digit_scale_fits_the_terminal() {
+        // "00:00" is 34 cells wide and 5 tall at scale 1.
+        assert_eq!(digit_scale("00:00", 80, 20), 2);
+        assert_eq!(digit_scale("00:00", 200, 60), MAX_DIGIT_SCALE);
+        assert_eq!(digit_scale("00:00", 33, 20), 0);
+        assert_eq!(digit_scale("00:00", 80, 4), 0);
+        assert_eq!(digit_scale("00:00", 0, 0), 0);
+    }
+
+    #[test]
+    fn test_progress_bar() {
+        let minute = Duration::from_secs(60);
+        assert_eq!(progress_bar(Duration::ZERO, minute, 4), "░░░░   0%");
+        assert_eq!(progress_bar(minute / 2, minute, 4), "██░░  50%");
+        assert_eq!(progress_bar(minute * 2, minute, 4), "████ 100%");
+        assert_eq!(progress_bar(Duration::ZERO, Duration::ZERO, 0), " 100%");
+        assert_eq!(ratio_bar(0.25, 4), "█░░░  25%");
+        assert_eq!(ratio_bar(-1.0, 4), "░░░░   0%");
+    }
+}